@@ -14,16 +14,13 @@ fn main() -> anyhow::Result<()> {
 
 #[cfg(target_os = "linux")]
 fn main_linux() -> anyhow::Result<()> {
-    let device_names = wireguard_uapi::RouteSocket::connect()?.list_device_names()?;
-
+    let mut route = wireguard_uapi::RouteSocket::connect()?;
     let mut wg = wireguard_uapi::WgSocket::connect()?;
-    for device_name in &device_names {
-        let device = wg.get_device(wireguard_uapi::DeviceInterface::from_name(device_name))?;
-        print_device(&device);
-
-        if Some(device_name) != device_names.last() {
+    for (i, device) in wg.get_devices(&mut route)?.enumerate() {
+        if i > 0 {
             println!();
         }
+        print_device(&device?);
     }
 
     Ok(())
@@ -33,11 +30,7 @@ fn main_linux() -> anyhow::Result<()> {
 fn print_device(device: &Device) {
     println!("{}: {}", "interface".green(), device.ifname.green());
     if let Some(public_key) = &device.public_key {
-        println!(
-            "  {}: {}",
-            "public key".black().bold(),
-            base64::encode(public_key)
-        );
+        println!("  {}: {}", "public key".black().bold(), public_key);
     }
 
     if device.listen_port != 0 {
@@ -55,7 +48,7 @@ fn print_peer(peer: &Peer) {
     println!(
         "{}: {}",
         "peer".yellow(),
-        base64::encode(&peer.public_key).yellow()
+        peer.public_key.to_string().yellow()
     );
     if let Some(endpoint) = peer.endpoint {
         println!("  {}: {}", "endpoint".black().bold(), endpoint);
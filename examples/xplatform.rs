@@ -3,7 +3,6 @@ use wireguard_uapi::get::{AllowedIp, Device, Peer};
 
 fn main() -> anyhow::Result<()> {
     let sockets = std::fs::read_dir("/var/run/wireguard")?
-        .into_iter()
         .filter_map(|entry| entry.ok())
         .map(|entry| entry.path())
         .filter(|path| path.extension().map(|ext| ext == "sock").unwrap_or(false));
@@ -20,11 +19,7 @@ fn main() -> anyhow::Result<()> {
 fn print_device(device: &Device) {
     println!("{}: {}", "interface".green(), device.ifname.green());
     if let Some(public_key) = &device.public_key {
-        println!(
-            "  {}: {}",
-            "public key".black().bold(),
-            base64::encode(public_key)
-        );
+        println!("  {}: {}", "public key".black().bold(), public_key);
     }
 
     if device.listen_port != 0 {
@@ -41,7 +36,7 @@ fn print_peer(peer: &Peer) {
     println!(
         "{}: {}",
         "peer".yellow(),
-        base64::encode(&peer.public_key).yellow()
+        peer.public_key.to_string().yellow()
     );
     if let Some(endpoint) = peer.endpoint {
         println!("  {}: {}", "endpoint".black().bold(), endpoint);
@@ -0,0 +1,380 @@
+//! An in-memory [`WireGuardApi`] backend for unit tests. Enabled by the `mock`
+//! feature.
+//!
+//! [`MockApi`] applies set requests the same way the Linux kernel module does,
+//! so code that manages WireGuard interfaces can be tested without root or a
+//! real interface.
+//!
+//! ```
+//! use wireguard_uapi::api::mock::MockApi;
+//! use wireguard_uapi::api::{set, WireGuardApi};
+//! use wireguard_uapi::PrivateKey;
+//!
+//! let mut api = MockApi::new();
+//! api.add_device("wg0");
+//!
+//! let peer_key = PrivateKey::generate().public_key();
+//! let device = set::Device::default()
+//!     .listen_port(51820)
+//!     .peers(vec![set::Peer::from_public_key(peer_key)]);
+//! api.set_device("wg0", &device).unwrap();
+//!
+//! let device = api.get_device("wg0").unwrap();
+//! assert_eq!(device.listen_port, 51820);
+//! assert_eq!(device.peers[0].public_key, peer_key);
+//! ```
+
+use super::{set, ApiError, WireGuardApi};
+use crate::get;
+use crate::key::PresharedKey;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::time::Duration;
+
+#[derive(Debug, Default)]
+pub struct MockApi {
+    devices: Vec<get::Device>,
+    next_ifindex: u32,
+}
+
+impl MockApi {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an empty interface, similar to `ip link add wg0 type wireguard`.
+    /// An existing interface with the same name is returned unchanged.
+    pub fn add_device(&mut self, ifname: &str) -> &mut get::Device {
+        if let Some(index) = self.position(ifname) {
+            return &mut self.devices[index];
+        }
+
+        self.next_ifindex += 1;
+        self.devices.push(get::Device {
+            ifindex: self.next_ifindex,
+            ifname: ifname.to_string(),
+            private_key: None,
+            public_key: None,
+            listen_port: 0,
+            fwmark: 0,
+            peers: vec![],
+        });
+        self.devices.last_mut().unwrap()
+    }
+
+    pub fn remove_device(&mut self, ifname: &str) -> Option<get::Device> {
+        let index = self.position(ifname)?;
+        Some(self.devices.remove(index))
+    }
+
+    pub fn device(&self, ifname: &str) -> Option<&get::Device> {
+        self.devices.iter().find(|device| device.ifname == ifname)
+    }
+
+    /// Gives direct access to an interface, for example to simulate handshakes
+    /// or traffic on a peer.
+    pub fn device_mut(&mut self, ifname: &str) -> Option<&mut get::Device> {
+        self.devices
+            .iter_mut()
+            .find(|device| device.ifname == ifname)
+    }
+
+    pub fn devices(&self) -> &[get::Device] {
+        &self.devices
+    }
+
+    fn position(&self, ifname: &str) -> Option<usize> {
+        self.devices
+            .iter()
+            .position(|device| device.ifname == ifname)
+    }
+}
+
+impl WireGuardApi for MockApi {
+    fn get_device(&mut self, ifname: &str) -> Result<get::Device, ApiError> {
+        self.device(ifname)
+            .cloned()
+            .ok_or_else(|| ApiError::NoSuchDevice(ifname.to_string()))
+    }
+
+    fn set_device(&mut self, ifname: &str, device: &set::Device) -> Result<(), ApiError> {
+        let current = self
+            .device_mut(ifname)
+            .ok_or_else(|| ApiError::NoSuchDevice(ifname.to_string()))?;
+        apply_set_device(current, device);
+        Ok(())
+    }
+}
+
+fn apply_set_device(device: &mut get::Device, request: &set::Device) {
+    if let Some(listen_port) = request.listen_port {
+        device.listen_port = listen_port;
+    }
+
+    if let Some(fwmark) = request.fwmark {
+        device.fwmark = fwmark;
+    }
+
+    if request.replace_peers {
+        device.peers.clear();
+    }
+
+    if let Some(private_key) = &request.private_key {
+        if private_key.is_zero() {
+            device.private_key = None;
+            device.public_key = None;
+        } else {
+            let public_key = private_key.public_key();
+            // A device can't be its own peer.
+            device.peers.retain(|peer| peer.public_key != public_key);
+            device.private_key = Some(private_key.clone());
+            device.public_key = Some(public_key);
+        }
+    }
+
+    for peer in &request.peers {
+        apply_set_peer(device, peer);
+    }
+}
+
+fn apply_set_peer(device: &mut get::Device, request: &set::Peer) {
+    // Peers with the same public key as the device are silently ignored.
+    if device.public_key == Some(request.public_key) {
+        return;
+    }
+
+    let index = match device
+        .peers
+        .iter()
+        .position(|peer| peer.public_key == request.public_key)
+    {
+        Some(index) => index,
+        None if request.remove || request.update_only => return,
+        None => {
+            device.peers.push(get::Peer {
+                public_key: request.public_key,
+                preshared_key: PresharedKey::zero(),
+                endpoint: None,
+                persistent_keepalive_interval: 0,
+                last_handshake_time: Duration::from_secs(0),
+                rx_bytes: 0,
+                tx_bytes: 0,
+                allowed_ips: vec![],
+                protocol_version: 1,
+            });
+            device.peers.len() - 1
+        }
+    };
+
+    if request.remove {
+        device.peers.remove(index);
+        return;
+    }
+
+    let peer = &mut device.peers[index];
+
+    if let Some(preshared_key) = &request.preshared_key {
+        peer.preshared_key = preshared_key.clone();
+    }
+
+    if let Some(endpoint) = request.endpoint {
+        peer.endpoint = Some(endpoint);
+    }
+
+    if request.replace_allowed_ips {
+        peer.allowed_ips.clear();
+    }
+
+    if let Some(interval) = request.persistent_keepalive_interval {
+        peer.persistent_keepalive_interval = interval;
+    }
+
+    for allowed_ip in &request.allowed_ips {
+        let allowed_ip = to_get_allowed_ip(allowed_ip);
+
+        // An allowed IP can only belong to one peer. Adding it to this peer
+        // takes it away from any other.
+        for other in device.peers.iter_mut() {
+            other.allowed_ips.retain(|existing| existing != &allowed_ip);
+        }
+        device.peers[index].allowed_ips.push(allowed_ip);
+    }
+}
+
+/// The kernel stores allowed IPs with their host bits cleared.
+fn to_get_allowed_ip(allowed_ip: &set::AllowedIp) -> get::AllowedIp {
+    let (family, ipaddr, cidr_mask) = match allowed_ip.ipaddr {
+        IpAddr::V4(addr) => {
+            let cidr_mask = allowed_ip.cidr_mask.min(32);
+            let mask = u32::MAX.checked_shl(32 - u32::from(cidr_mask)).unwrap_or(0);
+            (2, Ipv4Addr::from(u32::from(addr) & mask).into(), cidr_mask)
+        }
+        IpAddr::V6(addr) => {
+            let cidr_mask = allowed_ip.cidr_mask.min(128);
+            let mask = u128::MAX
+                .checked_shl(128 - u32::from(cidr_mask))
+                .unwrap_or(0);
+            (
+                10,
+                Ipv6Addr::from(u128::from(addr) & mask).into(),
+                cidr_mask,
+            )
+        }
+    };
+
+    get::AllowedIp {
+        family,
+        ipaddr,
+        cidr_mask,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key::{PrivateKey, PublicKey};
+
+    fn allowed_ip(s: &str) -> anyhow::Result<set::AllowedIp> {
+        let allowed_ip: get::AllowedIp = s.parse()?;
+        Ok((&allowed_ip).into())
+    }
+
+    fn public_keys(device: &get::Device) -> Vec<PublicKey> {
+        device.peers.iter().map(|peer| peer.public_key).collect()
+    }
+
+    #[test]
+    fn unknown_device() {
+        let mut api = MockApi::new();
+        assert!(matches!(
+            api.get_device("wg0"),
+            Err(ApiError::NoSuchDevice(_))
+        ));
+        assert!(matches!(
+            api.set_device("wg0", &set::Device::default()),
+            Err(ApiError::NoSuchDevice(_))
+        ));
+    }
+
+    #[test]
+    fn set_private_key() -> anyhow::Result<()> {
+        let mut api = MockApi::new();
+        api.add_device("wg0");
+        let private_key = PrivateKey::generate();
+
+        let request = set::Device::default()
+            .private_key(private_key.clone())
+            .peers(vec![set::Peer::from_public_key(private_key.public_key())]);
+        api.set_device("wg0", &request)?;
+
+        let device = api.get_device("wg0")?;
+        assert_eq!(device.private_key, Some(private_key.clone()));
+        assert_eq!(device.public_key, Some(private_key.public_key()));
+        // The device's own public key is never added as a peer.
+        assert!(device.peers.is_empty());
+
+        let request = set::Device::default().private_key(PrivateKey::zero());
+        api.set_device("wg0", &request)?;
+        let device = api.get_device("wg0")?;
+        assert_eq!(device.private_key, None);
+        assert_eq!(device.public_key, None);
+        Ok(())
+    }
+
+    #[test]
+    fn peer_flags() -> anyhow::Result<()> {
+        let mut api = MockApi::new();
+        api.add_device("wg0");
+        let keys: Vec<PublicKey> = (0..3)
+            .map(|_| PrivateKey::generate().public_key())
+            .collect();
+
+        let request = set::Device::default().peers(vec![
+            set::Peer::from_public_key(keys[0]),
+            set::Peer::from_public_key(keys[1]),
+            set::Peer::from_public_key(keys[2]).update_only(true),
+        ]);
+        api.set_device("wg0", &request)?;
+        assert_eq!(public_keys(&api.get_device("wg0")?), &keys[..2]);
+
+        let request = set::Device::default().peers(vec![
+            set::Peer::from_public_key(keys[0]).remove(true),
+            set::Peer::from_public_key(keys[2]).remove(true),
+        ]);
+        api.set_device("wg0", &request)?;
+        assert_eq!(public_keys(&api.get_device("wg0")?), &keys[1..2]);
+
+        let request = set::Device::default()
+            .replace_peers(true)
+            .peers(vec![set::Peer::from_public_key(keys[2])]);
+        api.set_device("wg0", &request)?;
+        assert_eq!(public_keys(&api.get_device("wg0")?), &keys[2..]);
+        Ok(())
+    }
+
+    #[test]
+    fn preshared_key_and_keepalive() -> anyhow::Result<()> {
+        let mut api = MockApi::new();
+        api.add_device("wg0");
+        let public_key = PrivateKey::generate().public_key();
+        let preshared_key = PresharedKey::generate();
+
+        let request = set::Device::default().peers(vec![set::Peer::from_public_key(public_key)
+            .preshared_key(preshared_key.clone())
+            .persistent_keepalive_interval(25)
+            .endpoint("192.95.5.67:1234".parse()?)]);
+        api.set_device("wg0", &request)?;
+        let peer = &api.get_device("wg0")?.peers[0];
+        assert_eq!(peer.preshared_key, preshared_key);
+        assert_eq!(peer.persistent_keepalive_interval, 25);
+        assert_eq!(peer.endpoint, Some("192.95.5.67:1234".parse()?));
+
+        let request = set::Device::default().peers(vec![set::Peer::from_public_key(public_key)
+            .preshared_key(PresharedKey::zero())
+            .persistent_keepalive_interval(0)]);
+        api.set_device("wg0", &request)?;
+        let peer = &api.get_device("wg0")?.peers[0];
+        assert!(peer.preshared_key.is_zero());
+        assert_eq!(peer.persistent_keepalive_interval, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn allowed_ips() -> anyhow::Result<()> {
+        let mut api = MockApi::new();
+        api.add_device("wg0");
+        let a = PrivateKey::generate().public_key();
+        let b = PrivateKey::generate().public_key();
+
+        let request = set::Device::default().peers(vec![
+            set::Peer::from_public_key(a)
+                .allowed_ips(vec![allowed_ip("10.0.0.1/24")?, allowed_ip("fd00::1/64")?]),
+            set::Peer::from_public_key(b).allowed_ips(vec![allowed_ip("10.0.1.1/32")?]),
+        ]);
+        api.set_device("wg0", &request)?;
+        let device = api.get_device("wg0")?;
+        assert_eq!(
+            device.peers[0].allowed_ips,
+            vec!["10.0.0.0/24".parse()?, "fd00::/64".parse()?]
+        );
+
+        // Adding an allowed IP to b moves it away from a.
+        let request = set::Device::default().peers(vec![
+            set::Peer::from_public_key(b).allowed_ips(vec![allowed_ip("10.0.0.0/24")?])
+        ]);
+        api.set_device("wg0", &request)?;
+        let device = api.get_device("wg0")?;
+        assert_eq!(device.peers[0].allowed_ips, vec!["fd00::/64".parse()?]);
+        assert_eq!(
+            device.peers[1].allowed_ips,
+            vec!["10.0.1.1/32".parse()?, "10.0.0.0/24".parse()?]
+        );
+
+        let request = set::Device::default().peers(vec![set::Peer::from_public_key(b)
+            .replace_allowed_ips(true)
+            .allowed_ips(vec![allowed_ip("10.0.2.0/24")?])]);
+        api.set_device("wg0", &request)?;
+        let device = api.get_device("wg0")?;
+        assert_eq!(device.peers[1].allowed_ips, vec!["10.0.2.0/24".parse()?]);
+        Ok(())
+    }
+}
@@ -0,0 +1,60 @@
+//! A common interface over the Linux kernel and cross-platform userspace
+//! backends.
+//!
+//! [`WgSocket`][crate::WgSocket] and [`xplatform::Client`][crate::xplatform::Client]
+//! both implement [`WireGuardApi`], so code written against the trait can
+//! manage kernel WireGuard on Linux as well as userspace implementations such
+//! as wireguard-go and boringtun.
+//!
+//! ```no_run
+//! use wireguard_uapi::api::{set, ApiError, WireGuardApi};
+//! use wireguard_uapi::PublicKey;
+//!
+//! fn remove_peer(
+//!     api: &mut impl WireGuardApi,
+//!     ifname: &str,
+//!     public_key: PublicKey,
+//! ) -> Result<(), ApiError> {
+//!     let peer = set::Peer::from_public_key(public_key).remove(true);
+//!     api.set_device(ifname, &set::Device::default().peers(vec![peer]))
+//! }
+//! ```
+
+#[cfg(feature = "mock")]
+pub mod mock;
+pub mod set;
+
+use crate::get;
+
+#[cfg(target_os = "linux")]
+use crate::linux;
+#[cfg(feature = "xplatform")]
+use crate::xplatform;
+
+pub trait WireGuardApi {
+    fn get_device(&mut self, ifname: &str) -> Result<get::Device, ApiError>;
+
+    fn set_device(&mut self, ifname: &str, device: &set::Device) -> Result<(), ApiError>;
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ApiError {
+    #[cfg(target_os = "linux")]
+    #[error(transparent)]
+    LinuxGetDevice(#[from] linux::err::GetDeviceError),
+
+    #[cfg(target_os = "linux")]
+    #[error(transparent)]
+    LinuxSetDevice(#[from] linux::err::SetDeviceError),
+
+    #[cfg(feature = "xplatform")]
+    #[error(transparent)]
+    XplatformGetDevice(#[from] xplatform::error::GetDeviceError),
+
+    #[cfg(feature = "xplatform")]
+    #[error(transparent)]
+    XplatformSetDevice(#[from] xplatform::error::SetDeviceError),
+
+    #[error("No such device: `{0}`")]
+    NoSuchDevice(String),
+}
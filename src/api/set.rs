@@ -0,0 +1,296 @@
+//! An owned set model shared by every [`WireGuardApi`][super::WireGuardApi]
+//! backend.
+//!
+//! It can express everything both the kernel and the cross-platform protocol
+//! accept in a set operation, and converts into each backend's own set types.
+
+use crate::key::{PresharedKey, PrivateKey, PublicKey};
+use std::net::{IpAddr, SocketAddr};
+
+#[cfg(target_os = "linux")]
+use crate::linux::{self, DeviceInterface};
+#[cfg(feature = "xplatform")]
+use crate::xplatform;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct Device {
+    /// An all zero key removes the private key.
+    #[cfg_attr(feature = "serde", serde(skip_serializing))]
+    pub private_key: Option<PrivateKey>,
+    /// 0 to choose randomly
+    pub listen_port: Option<u16>,
+    /// 0 to disable
+    pub fwmark: Option<u32>,
+    /// Remove all current peers before adding the peers below.
+    pub replace_peers: bool,
+    pub peers: Vec<Peer>,
+}
+
+impl Device {
+    pub fn private_key(mut self, private_key: PrivateKey) -> Self {
+        self.private_key = Some(private_key);
+        self
+    }
+
+    pub fn listen_port(mut self, listen_port: u16) -> Self {
+        self.listen_port = Some(listen_port);
+        self
+    }
+
+    pub fn fwmark(mut self, fwmark: u32) -> Self {
+        self.fwmark = Some(fwmark);
+        self
+    }
+
+    pub fn replace_peers(mut self, replace_peers: bool) -> Self {
+        self.replace_peers = replace_peers;
+        self
+    }
+
+    pub fn peers(mut self, peers: Vec<Peer>) -> Self {
+        self.peers = peers;
+        self
+    }
+
+    /// Converts into the Linux-specific set model, which borrows from this
+    /// device.
+    #[cfg(target_os = "linux")]
+    pub fn to_linux_set_device<'a>(
+        &'a self,
+        interface: DeviceInterface<'a>,
+    ) -> linux::set::Device<'a> {
+        let mut flags = vec![];
+        if self.replace_peers {
+            flags.push(linux::set::WgDeviceF::ReplacePeers);
+        }
+
+        linux::set::Device {
+            interface,
+            flags,
+            private_key: self.private_key.as_ref(),
+            listen_port: self.listen_port,
+            fwmark: self.fwmark,
+            peers: self.peers.iter().map(Peer::to_linux_set_peer).collect(),
+        }
+    }
+
+    #[cfg(feature = "xplatform")]
+    pub fn to_xplatform_set_device(&self) -> xplatform::set::Device {
+        xplatform::set::Device {
+            private_key: self.private_key.clone(),
+            listen_port: self.listen_port,
+            fwmark: self.fwmark,
+            replace_peers: Some(true).filter(|_| self.replace_peers),
+            peers: self.peers.iter().map(Peer::to_xplatform_set_peer).collect(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Peer {
+    pub public_key: PublicKey,
+    /// Remove this peer instead of adding or updating it.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub remove: bool,
+    /// Only update the peer if it already exists. It won't be created.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub update_only: bool,
+    /// An all zero key removes the preshared key.
+    pub preshared_key: Option<PresharedKey>,
+    pub endpoint: Option<SocketAddr>,
+    /// 0 to disable
+    pub persistent_keepalive_interval: Option<u16>,
+    /// Remove the current allowed IPs of the peer before adding the ones below.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub replace_allowed_ips: bool,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub allowed_ips: Vec<AllowedIp>,
+}
+
+impl Peer {
+    pub fn from_public_key(public_key: PublicKey) -> Self {
+        Self {
+            public_key,
+            remove: false,
+            update_only: false,
+            preshared_key: None,
+            endpoint: None,
+            persistent_keepalive_interval: None,
+            replace_allowed_ips: false,
+            allowed_ips: vec![],
+        }
+    }
+
+    pub fn remove(mut self, remove: bool) -> Self {
+        self.remove = remove;
+        self
+    }
+
+    pub fn update_only(mut self, update_only: bool) -> Self {
+        self.update_only = update_only;
+        self
+    }
+
+    pub fn preshared_key(mut self, preshared_key: PresharedKey) -> Self {
+        self.preshared_key = Some(preshared_key);
+        self
+    }
+
+    pub fn endpoint(mut self, endpoint: SocketAddr) -> Self {
+        self.endpoint = Some(endpoint);
+        self
+    }
+
+    pub fn persistent_keepalive_interval(mut self, persistent_keepalive_interval: u16) -> Self {
+        self.persistent_keepalive_interval = Some(persistent_keepalive_interval);
+        self
+    }
+
+    pub fn replace_allowed_ips(mut self, replace_allowed_ips: bool) -> Self {
+        self.replace_allowed_ips = replace_allowed_ips;
+        self
+    }
+
+    pub fn allowed_ips(mut self, allowed_ips: Vec<AllowedIp>) -> Self {
+        self.allowed_ips = allowed_ips;
+        self
+    }
+
+    #[cfg(target_os = "linux")]
+    fn to_linux_set_peer(&self) -> linux::set::Peer<'_> {
+        let mut flags = vec![];
+        if self.remove {
+            flags.push(linux::set::WgPeerF::RemoveMe);
+        }
+        if self.replace_allowed_ips {
+            flags.push(linux::set::WgPeerF::ReplaceAllowedIps);
+        }
+        if self.update_only {
+            flags.push(linux::set::WgPeerF::UpdateOnly);
+        }
+
+        linux::set::Peer {
+            public_key: &self.public_key,
+            flags,
+            preshared_key: self.preshared_key.as_ref(),
+            endpoint: self.endpoint.as_ref(),
+            persistent_keepalive_interval: self.persistent_keepalive_interval,
+            allowed_ips: self
+                .allowed_ips
+                .iter()
+                .map(|allowed_ip| linux::set::AllowedIp {
+                    ipaddr: &allowed_ip.ipaddr,
+                    cidr_mask: Some(allowed_ip.cidr_mask),
+                })
+                .collect(),
+            protocol_version: None,
+        }
+    }
+
+    #[cfg(feature = "xplatform")]
+    fn to_xplatform_set_peer(&self) -> xplatform::set::Peer {
+        xplatform::set::Peer {
+            public_key: self.public_key,
+            remove: Some(true).filter(|_| self.remove),
+            update_only: Some(true).filter(|_| self.update_only),
+            preshared_key: self.preshared_key.clone(),
+            endpoint: self.endpoint,
+            persistent_keepalive_interval: self.persistent_keepalive_interval,
+            replace_allowed_ips: Some(true).filter(|_| self.replace_allowed_ips),
+            allowed_ips: self
+                .allowed_ips
+                .iter()
+                .map(|allowed_ip| xplatform::set::AllowedIp {
+                    ipaddr: allowed_ip.ipaddr,
+                    cidr_mask: allowed_ip.cidr_mask,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct AllowedIp {
+    pub ipaddr: IpAddr,
+    pub cidr_mask: u8,
+}
+
+impl AllowedIp {
+    /// An allowed IP matching only this address.
+    pub fn from_ipaddr(ipaddr: IpAddr) -> Self {
+        let cidr_mask = match ipaddr {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        Self { ipaddr, cidr_mask }
+    }
+}
+
+impl From<&crate::get::AllowedIp> for AllowedIp {
+    fn from(allowed_ip: &crate::get::AllowedIp) -> Self {
+        Self {
+            ipaddr: allowed_ip.ipaddr,
+            cidr_mask: allowed_ip.cidr_mask,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example_device() -> anyhow::Result<Device> {
+        Ok(Device::default()
+            .private_key("yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk=".parse()?)
+            .replace_peers(true)
+            .peers(vec![
+                Peer::from_public_key("xTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg=".parse()?)
+                    .update_only(true)
+                    .replace_allowed_ips(true)
+                    .allowed_ips(vec![AllowedIp::from_ipaddr("10.192.122.3".parse()?)]),
+                Peer::from_public_key("TrMvSoP4jYQlY6RIzBgbssQqY3vxI2Pi+y71lOWWXX0=".parse()?)
+                    .remove(true),
+            ]))
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn to_linux_set_device() -> anyhow::Result<()> {
+        use linux::set::{WgDeviceF, WgPeerF};
+
+        let device = example_device()?;
+        let linux_device = device.to_linux_set_device(DeviceInterface::from_name("wgtest0"));
+
+        assert_eq!(linux_device.flags, vec![WgDeviceF::ReplacePeers]);
+        assert_eq!(linux_device.private_key, device.private_key.as_ref());
+        assert_eq!(
+            linux_device.peers[0].flags,
+            vec![WgPeerF::ReplaceAllowedIps, WgPeerF::UpdateOnly]
+        );
+        assert_eq!(linux_device.peers[0].allowed_ips[0].cidr_mask, Some(32));
+        assert_eq!(linux_device.peers[1].flags, vec![WgPeerF::RemoveMe]);
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "xplatform")]
+    fn to_xplatform_set_device() -> anyhow::Result<()> {
+        let expected = "\
+            private_key=c809f3e5317e9575c9b5ed78b638b7ce530dabe85ddab614220241801ddf0669\n\
+            replace_peers=true\n\
+            public_key=c53201039adba14be71f886da1d8dbe9eebded08cb111b75340078999aa9f038\n\
+            update_only=true\n\
+            replace_allowed_ips=true\n\
+            allowed_ip=10.192.122.3/32\n\
+            public_key=4eb32f4a83f88d842563a448cc181bb2c42a637bf12363e2fb2ef594e5965d7d\n\
+            remove=true\n";
+        assert_eq!(
+            example_device()?.to_xplatform_set_device().to_string(),
+            expected
+        );
+        Ok(())
+    }
+}
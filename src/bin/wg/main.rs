@@ -0,0 +1,463 @@
+//! A `wg` compatible command line tool. Interfaces with a socket in
+//! `/var/run/wireguard` are managed through the cross-platform userspace
+//! protocol, the same as upstream `wg`, and all others through netlink.
+
+mod set;
+mod show;
+
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
+use wireguard_uapi::api::{self, ApiError, WireGuardApi};
+use wireguard_uapi::config::{self, Config};
+use wireguard_uapi::xplatform::Client;
+use wireguard_uapi::{get, PresharedKey, PrivateKey};
+use zeroize::Zeroizing;
+
+#[cfg(target_os = "linux")]
+use wireguard_uapi::{RouteSocket, WgSocket};
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+const SOCKET_DIR: &str = "/var/run/wireguard";
+
+const SUBCOMMANDS: &[(&str, &str)] = &[
+    (
+        "show",
+        "Shows the current configuration and device information",
+    ),
+    (
+        "showconf",
+        "Shows the current configuration of a given WireGuard interface, for use with `setconf'",
+    ),
+    (
+        "set",
+        "Change the current configuration, add peers, remove peers, or change peers",
+    ),
+    (
+        "setconf",
+        "Applies a configuration file to a WireGuard interface",
+    ),
+    (
+        "addconf",
+        "Appends a configuration file to a WireGuard interface",
+    ),
+    (
+        "syncconf",
+        "Synchronizes a configuration file to a WireGuard interface",
+    ),
+    (
+        "genkey",
+        "Generates a new private key and writes it to stdout",
+    ),
+    (
+        "genpsk",
+        "Generates a new preshared key and writes it to stdout",
+    ),
+    (
+        "pubkey",
+        "Reads a private key from stdin and writes a public key to stdout",
+    ),
+];
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let prog = args.first().map(String::as_str).unwrap_or("wg");
+
+    let (command, rest) = match args.get(1).map(String::as_str) {
+        None => ("show", &[][..]),
+        Some("help" | "--help" | "-h") => {
+            print!("{}", usage(prog));
+            return;
+        }
+        Some("--version" | "-v") => {
+            println!("wireguard-uapi v{}", env!("CARGO_PKG_VERSION"));
+            return;
+        }
+        Some(command) => (command, &args[2..]),
+    };
+
+    let result = match command {
+        "show" => show(prog, rest),
+        "showconf" => showconf(prog, rest),
+        "set" => set(prog, rest),
+        "setconf" | "addconf" | "syncconf" => conf(prog, command, rest),
+        "genkey" | "genpsk" => genkey(prog, command, rest),
+        "pubkey" => pubkey(prog, rest),
+        _ => {
+            eprintln!("Invalid subcommand: `{}'", command);
+            eprint!("{}", usage(prog));
+            process::exit(1);
+        }
+    };
+
+    if let Err(err) = result {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+}
+
+fn usage(prog: &str) -> String {
+    let mut usage = format!("Usage: {} <cmd> [<args>]\n\nAvailable subcommands:\n", prog);
+    for (command, description) in SUBCOMMANDS {
+        usage.push_str(&format!("  {}: {}\n", command, description));
+    }
+    usage.push_str("You may pass `--help' to any of these subcommands to view usage.\n");
+    usage
+}
+
+/// Prints the usage of a subcommand when its arguments don't match.
+fn check_usage(prog: &str, command: &str, valid: bool, usage: &str) -> Result<()> {
+    match valid {
+        true => Ok(()),
+        false => Err(format!("Usage: {} {} {}", prog, command, usage).into()),
+    }
+}
+
+fn show(prog: &str, args: &[String]) -> Result<()> {
+    let usage = format!(
+        "{{ <interface> | all | interfaces }} [{}]",
+        show::FIELDS.join(" | ")
+    );
+    let field = args.get(1).map(String::as_str);
+    let valid = args.len() <= 2
+        && !args.iter().any(|arg| arg == "--help")
+        && field.is_none_or(|field| show::FIELDS.contains(&field));
+    check_usage(prog, "show", valid, &usage)?;
+
+    let mut backends = Backends::default();
+    let mut stdout = io::stdout().lock();
+
+    match args.first().map(String::as_str) {
+        Some("interfaces") => {
+            check_usage(prog, "show", field.is_none(), &usage)?;
+            let interfaces = backends.list()?;
+            if !interfaces.is_empty() {
+                writeln!(stdout, "{}", interfaces.join(" "))?;
+            }
+        }
+        None | Some("all") => {
+            let interfaces = backends.list()?;
+            for (i, ifname) in interfaces.iter().enumerate() {
+                // Like upstream, an interface that can't be read, such as one
+                // deleted since it was listed, doesn't stop the others.
+                let device = match backends.get_device(ifname) {
+                    Ok(device) => device,
+                    Err(err) => {
+                        eprintln!("Unable to access interface {}: {}", ifname, err);
+                        continue;
+                    }
+                };
+                match field {
+                    Some(field) => stdout.write_all(show_field(&device, field, true).as_bytes())?,
+                    None => {
+                        stdout.write_all(pretty(&device).as_bytes())?;
+                        if i + 1 < interfaces.len() {
+                            writeln!(stdout)?;
+                        }
+                    }
+                }
+            }
+        }
+        Some(ifname) => {
+            let device = backends
+                .get_device(ifname)
+                .map_err(|err| format!("Unable to access interface {}: {}", ifname, err))?;
+            let text = match field {
+                Some(field) => show_field(&device, field, false),
+                None => pretty(&device),
+            };
+            stdout.write_all(text.as_bytes())?;
+        }
+    }
+
+    Ok(())
+}
+
+fn show_field(device: &get::Device, field: &str, with_interface: bool) -> String {
+    // The field was checked against the list of valid fields already.
+    show::ugly(device, field, with_interface).unwrap_or_default()
+}
+
+fn pretty(device: &get::Device) -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.as_secs())
+        .unwrap_or(0);
+    let show_keys = std::env::var("WG_HIDE_KEYS").is_ok_and(|hide| hide == "never");
+    let text = show::pretty(device, now, show_keys);
+
+    let color = match std::env::var("WG_COLOR_MODE").as_deref() {
+        Ok("always") => true,
+        Ok("never") => false,
+        _ => io::stdout().is_terminal(),
+    };
+    match color {
+        true => text,
+        false => show::strip_colors(&text),
+    }
+}
+
+fn showconf(prog: &str, args: &[String]) -> Result<()> {
+    check_usage(
+        prog,
+        "showconf",
+        args.len() == 1 && args[0] != "--help",
+        "<interface>",
+    )?;
+
+    let device = Backends::default()
+        .get_device(&args[0])
+        .map_err(|err| format!("Unable to access interface: {}", err))?;
+    print!("{}", Config::from(&device));
+    Ok(())
+}
+
+fn set(prog: &str, args: &[String]) -> Result<()> {
+    check_usage(
+        prog,
+        "set",
+        args.len() >= 2 && args[0] != "--help",
+        set::USAGE,
+    )?;
+
+    let device = set::parse_args(&args[1..], |path| {
+        Ok(Zeroizing::new(fs::read_to_string(path)?))
+    })?;
+    Backends::default()
+        .set_device(&args[0], &device)
+        .map_err(|err| format!("Unable to modify interface: {}", err).into())
+}
+
+fn conf(prog: &str, command: &str, args: &[String]) -> Result<()> {
+    check_usage(
+        prog,
+        command,
+        args.len() == 2 && args[0] != "--help",
+        "<interface> <configuration filename>",
+    )?;
+    let (ifname, path) = (&args[0], &args[1]);
+
+    let config: Config = fs::read_to_string(path)?
+        .parse()
+        .map_err(|err| format!("{}: {}", path, err))?;
+
+    let mut backends = Backends::default();
+    let device = match command {
+        "setconf" => api::set::Device::from(&config),
+        "addconf" => addconf_device(&config),
+        _ => {
+            let current = backends
+                .get_device(ifname)
+                .map_err(|err| format!("Unable to access interface: {}", err))?;
+            let diff = config::diff(&current, &config);
+            if diff.is_empty() {
+                return Ok(());
+            }
+            api::set::Device::from(&diff)
+        }
+    };
+
+    backends
+        .set_device(ifname, &device)
+        .map_err(|err| format!("Unable to modify interface: {}", err).into())
+}
+
+/// Keeps the current peers, but like upstream still replaces the allowed IPs
+/// of each peer in the configuration.
+fn addconf_device(config: &Config) -> api::set::Device {
+    let mut device = api::set::Device::from(config);
+    device.replace_peers = false;
+    device
+}
+
+fn genkey(prog: &str, command: &str, args: &[String]) -> Result<()> {
+    check_usage(prog, command, args.is_empty(), "")?;
+    warn_if_world_accessible();
+
+    match command {
+        "genkey" => println!("{}", PrivateKey::generate()),
+        _ => println!("{}", PresharedKey::generate()),
+    }
+    Ok(())
+}
+
+fn pubkey(prog: &str, args: &[String]) -> Result<()> {
+    check_usage(prog, "pubkey", args.is_empty(), "")?;
+
+    // One more byte than is read, so the buffer never grows and leaves a copy
+    // of the key behind.
+    let mut input = Zeroizing::new(String::with_capacity(1025));
+    io::stdin().take(1024).read_to_string(&mut input)?;
+    let private_key: PrivateKey = input
+        .trim()
+        .parse()
+        .map_err(|_| format!("{}: Key is not the correct length or format", prog))?;
+    println!("{}", private_key.public_key());
+    Ok(())
+}
+
+/// Keys written to a file that others can read aren't secret anymore.
+fn warn_if_world_accessible() {
+    use std::os::fd::AsFd;
+    use std::os::unix::fs::PermissionsExt;
+
+    let metadata = io::stdout()
+        .as_fd()
+        .try_clone_to_owned()
+        .map(File::from)
+        .and_then(|stdout| stdout.metadata());
+    if let Ok(metadata) = metadata {
+        if metadata.is_file() && metadata.permissions().mode() & 0o007 != 0 {
+            eprintln!("Warning: writing to world accessible file.");
+            eprintln!("Consider setting the umask to 077 and trying again.");
+        }
+    }
+}
+
+/// Picks the backend of each interface, opening the netlink socket only once
+/// it's needed.
+#[derive(Default)]
+struct Backends {
+    #[cfg(target_os = "linux")]
+    kernel: Option<WgSocket>,
+}
+
+impl Backends {
+    fn socket_path(ifname: &str) -> PathBuf {
+        Path::new(SOCKET_DIR).join(format!("{}.sock", ifname))
+    }
+
+    /// Like upstream, a socket that refuses connections was left behind by a
+    /// userspace implementation that exited. It's removed so the interface is
+    /// looked up in the kernel instead.
+    fn is_listening(path: &Path) -> bool {
+        use std::os::unix::fs::FileTypeExt;
+        use std::os::unix::net::UnixStream;
+
+        let is_socket = fs::metadata(path).is_ok_and(|metadata| metadata.file_type().is_socket());
+        if !is_socket {
+            return false;
+        }
+        match UnixStream::connect(path) {
+            Ok(_) => true,
+            Err(err) => {
+                if err.kind() == io::ErrorKind::ConnectionRefused {
+                    let _ = fs::remove_file(path);
+                }
+                false
+            }
+        }
+    }
+
+    fn with_api<T>(
+        &mut self,
+        ifname: &str,
+        f: impl FnOnce(&mut dyn WireGuardApi) -> std::result::Result<T, ApiError>,
+    ) -> Result<T> {
+        let path = Self::socket_path(ifname);
+        if Self::is_listening(&path) {
+            return Ok(f(&mut Client::create(path))?);
+        }
+
+        #[cfg(target_os = "linux")]
+        {
+            if self.kernel.is_none() {
+                self.kernel = Some(WgSocket::connect()?);
+            }
+            Ok(f(self.kernel.as_mut().unwrap())?)
+        }
+
+        #[cfg(not(target_os = "linux"))]
+        Err(io::Error::from(io::ErrorKind::NotFound).into())
+    }
+
+    fn get_device(&mut self, ifname: &str) -> Result<get::Device> {
+        let mut device = self.with_api(ifname, |api| api.get_device(ifname))?;
+        // The userspace protocol only reports the private key. Upstream derives
+        // the public key from it, which `show` prints.
+        if device.public_key.is_none() {
+            device.public_key = device.private_key.as_ref().map(PrivateKey::public_key);
+        }
+        Ok(device)
+    }
+
+    fn set_device(&mut self, ifname: &str, device: &api::set::Device) -> Result<()> {
+        self.with_api(ifname, |api| api.set_device(ifname, device))
+    }
+
+    /// Kernel interfaces come first, followed by userspace ones.
+    fn list(&mut self) -> Result<Vec<String>> {
+        #[cfg(target_os = "linux")]
+        let mut interfaces = RouteSocket::connect()?.list_device_names()?;
+        #[cfg(not(target_os = "linux"))]
+        let mut interfaces = vec![];
+
+        let mut userspace = vec![];
+        if let Ok(entries) = fs::read_dir(SOCKET_DIR) {
+            for entry in entries {
+                let path = entry?.path();
+                if path.extension().is_some_and(|ext| ext == "sock") && Self::is_listening(&path) {
+                    if let Some(name) = path.file_stem().and_then(|name| name.to_str()) {
+                        userspace.push(name.to_string());
+                    }
+                }
+            }
+        }
+        userspace.sort();
+
+        for name in userspace {
+            if !interfaces.contains(&name) {
+                interfaces.push(name);
+            }
+        }
+        Ok(interfaces)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn addconf_replaces_allowed_ips_of_listed_peers() -> anyhow::Result<()> {
+        let config: Config = "[Interface]\n\
+             ListenPort = 51820\n\
+             \n\
+             [Peer]\n\
+             PublicKey = xTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg=\n\
+             AllowedIPs = 10.192.122.3/32\n"
+            .parse()?;
+
+        let device = addconf_device(&config);
+        assert!(!device.replace_peers);
+        assert!(device.peers[0].replace_allowed_ips);
+        assert_eq!(
+            device.peers[0].allowed_ips,
+            vec![api::set::AllowedIp::from_ipaddr("10.192.122.3".parse()?)]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn stale_sockets_are_removed() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+
+        let path = dir.path().join("wg0.sock");
+        let listener = std::os::unix::net::UnixListener::bind(&path)?;
+        assert!(Backends::is_listening(&path));
+
+        drop(listener);
+        assert!(!Backends::is_listening(&path));
+        assert!(!path.exists());
+
+        let path = dir.path().join("wg1.sock");
+        fs::write(&path, "")?;
+        assert!(!Backends::is_listening(&path));
+        assert!(path.exists());
+        Ok(())
+    }
+}
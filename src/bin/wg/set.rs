@@ -0,0 +1,209 @@
+//! Arguments of `wg set`.
+
+use std::error::Error;
+use std::net::{SocketAddr, ToSocketAddrs};
+use wireguard_uapi::api::set;
+use wireguard_uapi::get;
+use zeroize::Zeroizing;
+
+pub const USAGE: &str = "<interface> [listen-port <port>] [fwmark <mark>] [private-key <file path>] [peer <base64 public key> [remove] [preshared-key <file path>] [endpoint <ip>:<port>] [persistent-keepalive <interval seconds>] [allowed-ips <ip1>/<cidr1>[,<ip2>/<cidr2>]...] ]...";
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+/// Builds the set request for the arguments following the interface name.
+/// `read_key` returns the contents of a key file.
+pub fn parse_args(
+    args: &[String],
+    read_key: impl Fn(&str) -> Result<Zeroizing<String>>,
+) -> Result<set::Device> {
+    let mut device = set::Device::default();
+    let mut args = args.iter().map(String::as_str);
+
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("Expected a value after `{}'", arg))
+        };
+
+        match (device.peers.last_mut(), arg) {
+            (None, "listen-port") => {
+                let port = value()?;
+                let port = port
+                    .parse()
+                    .map_err(|_| format!("Unable to parse port: `{}'", port))?;
+                device.listen_port = Some(port);
+            }
+            (None, "fwmark") => {
+                let fwmark = value()?;
+                device.fwmark = Some(parse_fwmark(fwmark).ok_or_else(|| {
+                    format!("Fwmark is neither 0/off nor 0x0-0xffffffff: `{}'", fwmark)
+                })?);
+            }
+            (None, "private-key") => {
+                let key = read_key(value()?)?;
+                device.private_key = Some(parse_key(&key)?);
+            }
+            (_, "peer") => {
+                let key = value()?;
+                let public_key = key
+                    .parse()
+                    .map_err(|_| format!("Key is not the correct length or format: `{}'", key))?;
+                device.peers.push(set::Peer::from_public_key(public_key));
+            }
+            (Some(peer), "remove") => peer.remove = true,
+            (Some(peer), "update-only") => peer.update_only = true,
+            (Some(peer), "preshared-key") => {
+                let key = read_key(value()?)?;
+                peer.preshared_key = Some(parse_key(&key)?);
+            }
+            (Some(peer), "endpoint") => peer.endpoint = Some(parse_endpoint(value()?)?),
+            (Some(peer), "persistent-keepalive") => {
+                let interval = value()?;
+                let parsed = match interval {
+                    "off" => Some(0),
+                    interval => interval.parse().ok(),
+                };
+                peer.persistent_keepalive_interval = Some(parsed.ok_or_else(|| {
+                    format!(
+                        "Persistent keepalive interval is neither 0/off nor 1-65535: `{}'",
+                        interval
+                    )
+                })?);
+            }
+            (Some(peer), "allowed-ips") => {
+                peer.replace_allowed_ips = true;
+                peer.allowed_ips = parse_allowed_ips(value()?)?;
+            }
+            (_, arg) => return Err(format!("Invalid argument: {}", arg).into()),
+        }
+    }
+
+    Ok(device)
+}
+
+/// Key files hold a base64 key. An empty file, such as `/dev/null`, stands for
+/// the all zero key, which removes the key.
+pub fn parse_key<K: std::str::FromStr + From<[u8; 32]>>(contents: &str) -> Result<K> {
+    let contents = contents.trim();
+    if contents.is_empty() {
+        return Ok(K::from([0; 32]));
+    }
+    contents
+        .parse()
+        .map_err(|_| "Key is not the correct length or format".into())
+}
+
+fn parse_fwmark(value: &str) -> Option<u32> {
+    if value == "off" {
+        return Some(0);
+    }
+    match value.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => value.parse().ok(),
+    }
+}
+
+fn parse_endpoint(value: &str) -> Result<SocketAddr> {
+    value
+        .to_socket_addrs()
+        .ok()
+        .and_then(|mut addrs| addrs.next())
+        .ok_or_else(|| format!("Unable to parse endpoint: `{}'", value).into())
+}
+
+fn parse_allowed_ips(value: &str) -> Result<Vec<set::AllowedIp>> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|allowed_ip| !allowed_ip.is_empty())
+        .map(|allowed_ip| {
+            match allowed_ip.contains('/') {
+                true => allowed_ip
+                    .parse::<get::AllowedIp>()
+                    .ok()
+                    .map(|ip| (&ip).into()),
+                false => allowed_ip.parse().ok().map(set::AllowedIp::from_ipaddr),
+            }
+            .ok_or_else(|| format!("Unable to parse IP address: `{}'", allowed_ip).into())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wireguard_uapi::{PresharedKey, PrivateKey};
+
+    fn args(args: &str) -> Vec<String> {
+        args.split_whitespace().map(String::from).collect()
+    }
+
+    fn read_key(path: &str) -> Result<Zeroizing<String>> {
+        match path {
+            "private.key" => Ok(Zeroizing::new(
+                "yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk=\n".to_string(),
+            )),
+            "/dev/null" => Ok(Zeroizing::default()),
+            _ => Err("No such file".into()),
+        }
+    }
+
+    #[test]
+    fn parse_device_and_peers() -> anyhow::Result<()> {
+        let device = parse_args(
+            &args(
+                "listen-port 51820 fwmark 0x1234 private-key private.key \
+                 peer xTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg= preshared-key /dev/null \
+                 endpoint 192.95.5.67:1234 persistent-keepalive off allowed-ips 10.0.0.1,fd00::/64 \
+                 peer TrMvSoP4jYQlY6RIzBgbssQqY3vxI2Pi+y71lOWWXX0= remove",
+            ),
+            read_key,
+        )
+        .map_err(|err| anyhow::anyhow!("{}", err))?;
+
+        assert_eq!(device.listen_port, Some(51820));
+        assert_eq!(device.fwmark, Some(0x1234));
+        assert_eq!(
+            device.private_key,
+            Some(PrivateKey::from_base64(
+                "yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk="
+            )?)
+        );
+        assert!(!device.replace_peers);
+
+        let peer = &device.peers[0];
+        assert_eq!(peer.preshared_key, Some(PresharedKey::zero()));
+        assert_eq!(peer.endpoint, Some("192.95.5.67:1234".parse()?));
+        assert_eq!(peer.persistent_keepalive_interval, Some(0));
+        assert!(peer.replace_allowed_ips);
+        assert_eq!(
+            peer.allowed_ips,
+            vec![
+                set::AllowedIp::from_ipaddr("10.0.0.1".parse()?),
+                set::AllowedIp {
+                    ipaddr: "fd00::".parse()?,
+                    cidr_mask: 64
+                },
+            ]
+        );
+        assert!(device.peers[1].remove);
+        Ok(())
+    }
+
+    #[test]
+    fn parse_invalid_args() {
+        let error = |line| parse_args(&args(line), read_key).unwrap_err().to_string();
+
+        assert_eq!(error("remove"), "Invalid argument: remove");
+        assert_eq!(error("listen-port"), "Expected a value after `listen-port'");
+        assert_eq!(error("private-key missing.key"), "No such file");
+        assert_eq!(
+            error("fwmark 0xfffffffff"),
+            "Fwmark is neither 0/off nor 0x0-0xffffffff: `0xfffffffff'"
+        );
+        assert_eq!(
+            error("peer xTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg= listen-port 1"),
+            "Invalid argument: listen-port"
+        );
+    }
+}
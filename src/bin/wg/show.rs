@@ -0,0 +1,400 @@
+//! Output of `wg show`, byte for byte the same as upstream. Colors are written
+//! as escape codes and stripped by the caller when stdout isn't a terminal.
+
+use std::fmt::Write;
+use wireguard_uapi::dump;
+use wireguard_uapi::get::{AllowedIp, Device, Peer};
+use wireguard_uapi::PresharedKey;
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const FG_RED: &str = "\x1b[31m";
+const FG_GREEN: &str = "\x1b[32m";
+const FG_YELLOW: &str = "\x1b[33m";
+const FG_CYAN: &str = "\x1b[36m";
+
+pub const FIELDS: &[&str] = &[
+    "public-key",
+    "private-key",
+    "listen-port",
+    "fwmark",
+    "peers",
+    "preshared-keys",
+    "endpoints",
+    "allowed-ips",
+    "latest-handshakes",
+    "transfer",
+    "persistent-keepalive",
+    "dump",
+];
+
+/// The human readable output of `wg show <interface>`. `now` is the current
+/// Unix time in seconds, which handshake times are shown relative to.
+pub fn pretty(device: &Device, now: u64, show_keys: bool) -> String {
+    let mut out = String::new();
+    let mask = |key: String| match show_keys {
+        true => key,
+        false => "(hidden)".to_string(),
+    };
+
+    out.push_str(RESET);
+    writeln!(
+        out,
+        "{}{}interface{}: {}{}{}",
+        FG_GREEN, BOLD, RESET, FG_GREEN, device.ifname, RESET
+    )
+    .unwrap();
+    if let Some(public_key) = &device.public_key {
+        writeln!(out, "  {}public key{}: {}", BOLD, RESET, public_key).unwrap();
+    }
+    if let Some(private_key) = &device.private_key {
+        let private_key = mask(private_key.to_string());
+        writeln!(out, "  {}private key{}: {}", BOLD, RESET, private_key).unwrap();
+    }
+    if device.listen_port != 0 {
+        let port = device.listen_port;
+        writeln!(out, "  {}listening port{}: {}", BOLD, RESET, port).unwrap();
+    }
+    if device.fwmark != 0 {
+        writeln!(out, "  {}fwmark{}: {:#x}", BOLD, RESET, device.fwmark).unwrap();
+    }
+
+    let peers = sorted_peers(device);
+    if !peers.is_empty() {
+        out.push('\n');
+    }
+
+    for (i, peer) in peers.iter().enumerate() {
+        if i > 0 {
+            out.push('\n');
+        }
+        writeln!(
+            out,
+            "{}{}peer{}: {}{}{}",
+            FG_YELLOW, BOLD, RESET, FG_YELLOW, peer.public_key, RESET
+        )
+        .unwrap();
+        if !peer.preshared_key.is_zero() {
+            let preshared_key = mask(peer.preshared_key.to_string());
+            writeln!(out, "  {}preshared key{}: {}", BOLD, RESET, preshared_key).unwrap();
+        }
+        if let Some(endpoint) = peer.endpoint {
+            writeln!(out, "  {}endpoint{}: {}", BOLD, RESET, endpoint).unwrap();
+        }
+
+        write!(out, "  {}allowed ips{}: ", BOLD, RESET).unwrap();
+        if peer.allowed_ips.is_empty() {
+            out.push_str("(none)");
+        }
+        for (i, allowed_ip) in peer.allowed_ips.iter().enumerate() {
+            if i > 0 {
+                out.push_str(", ");
+            }
+            let (ipaddr, cidr) = (allowed_ip.ipaddr, allowed_ip.cidr_mask);
+            write!(out, "{}{}/{}{}", ipaddr, FG_CYAN, RESET, cidr).unwrap();
+        }
+        out.push('\n');
+
+        let handshake = peer.last_handshake_time.as_secs();
+        if handshake != 0 {
+            let ago = ago(handshake, now);
+            writeln!(out, "  {}latest handshake{}: {}", BOLD, RESET, ago).unwrap();
+        }
+        if peer.rx_bytes != 0 || peer.tx_bytes != 0 {
+            writeln!(
+                out,
+                "  {}transfer{}: {} received, {} sent",
+                BOLD,
+                RESET,
+                bytes(peer.rx_bytes),
+                bytes(peer.tx_bytes)
+            )
+            .unwrap();
+        }
+        if peer.persistent_keepalive_interval != 0 {
+            let every = pretty_time(peer.persistent_keepalive_interval.into());
+            writeln!(
+                out,
+                "  {}persistent keepalive{}: every {}",
+                BOLD, RESET, every
+            )
+            .unwrap();
+        }
+    }
+
+    out
+}
+
+/// The output of `wg show <interface> <field>`, where every line is prefixed
+/// with the interface name for `wg show all <field>`. Returns `None` for an
+/// unknown field.
+pub fn ugly(device: &Device, field: &str, with_interface: bool) -> Option<String> {
+    let mut out = String::new();
+    let prefix = match with_interface {
+        true => format!("{}\t", device.ifname),
+        false => String::new(),
+    };
+
+    let device_line = |value: String| format!("{}{}\n", prefix, value);
+    let peer_lines = |value: &dyn Fn(&Peer) -> String| {
+        device
+            .peers
+            .iter()
+            .map(|peer| format!("{}{}\t{}\n", prefix, peer.public_key, value(peer)))
+            .collect::<String>()
+    };
+
+    match field {
+        "public-key" => out.push_str(&device_line(maybe_key(device.public_key))),
+        "private-key" => out.push_str(&device_line(maybe_key(device.private_key.as_ref()))),
+        "listen-port" => out.push_str(&device_line(device.listen_port.to_string())),
+        "fwmark" => out.push_str(&device_line(off_or_hex(device.fwmark))),
+        "endpoints" => out.push_str(&peer_lines(&|peer| endpoint(peer))),
+        "allowed-ips" => out.push_str(&peer_lines(&|peer| allowed_ips(&peer.allowed_ips, " "))),
+        "latest-handshakes" => out.push_str(&peer_lines(&|peer| {
+            peer.last_handshake_time.as_secs().to_string()
+        })),
+        "transfer" => out.push_str(&peer_lines(&|peer| {
+            format!("{}\t{}", peer.rx_bytes, peer.tx_bytes)
+        })),
+        "persistent-keepalive" => out.push_str(&peer_lines(&|peer| {
+            off_or(peer.persistent_keepalive_interval)
+        })),
+        "preshared-keys" => out.push_str(&peer_lines(&|peer| preshared_key(&peer.preshared_key))),
+        "peers" => {
+            for peer in &device.peers {
+                writeln!(out, "{}{}", prefix, peer.public_key).unwrap();
+            }
+        }
+        "dump" => dump::write_device(&mut out, device, with_interface).unwrap(),
+        _ => return None,
+    }
+
+    Some(out)
+}
+
+/// Removes the escape codes `pretty` adds, for output that isn't a terminal.
+pub fn strip_colors(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('\x1b') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        rest = match rest.find('m') {
+            Some(end) => &rest[end + 1..],
+            None => "",
+        };
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Peers with the most recent handshake come first, and those that never had
+/// one last.
+fn sorted_peers(device: &Device) -> Vec<&Peer> {
+    let mut peers: Vec<_> = device.peers.iter().collect();
+    peers.sort_by(|a, b| {
+        let never = |peer: &Peer| peer.last_handshake_time.as_nanos() == 0;
+        never(a)
+            .cmp(&never(b))
+            .then(b.last_handshake_time.cmp(&a.last_handshake_time))
+    });
+    peers
+}
+
+fn maybe_key<K: ToString>(key: Option<K>) -> String {
+    key.map(|key| key.to_string())
+        .unwrap_or_else(|| "(none)".to_string())
+}
+
+fn preshared_key(key: &PresharedKey) -> String {
+    maybe_key(Some(key).filter(|key| !key.is_zero()))
+}
+
+fn endpoint(peer: &Peer) -> String {
+    maybe_key(peer.endpoint)
+}
+
+fn allowed_ips(allowed_ips: &[AllowedIp], separator: &str) -> String {
+    if allowed_ips.is_empty() {
+        return "(none)".to_string();
+    }
+    allowed_ips
+        .iter()
+        .map(|allowed_ip| format!("{}/{}", allowed_ip.ipaddr, allowed_ip.cidr_mask))
+        .collect::<Vec<_>>()
+        .join(separator)
+}
+
+fn off_or(value: u16) -> String {
+    match value {
+        0 => "off".to_string(),
+        value => value.to_string(),
+    }
+}
+
+fn off_or_hex(value: u32) -> String {
+    match value {
+        0 => "off".to_string(),
+        value => format!("{:#x}", value),
+    }
+}
+
+fn ago(time: u64, now: u64) -> String {
+    if now == time {
+        "Now".to_string()
+    } else if now < time {
+        format!(
+            "({}System clock wound backward; connection problems may ensue.{})",
+            FG_RED, RESET
+        )
+    } else {
+        format!("{} ago", pretty_time(now - time))
+    }
+}
+
+fn pretty_time(seconds: u64) -> String {
+    let units = [
+        ("year", 365 * 24 * 60 * 60),
+        ("day", 24 * 60 * 60),
+        ("hour", 60 * 60),
+        ("minute", 60),
+        ("second", 1),
+    ];
+
+    let mut left = seconds;
+    let mut parts = vec![];
+    for (unit, length) in units.iter() {
+        let count = left / length;
+        left %= length;
+        if count != 0 {
+            let plural = if count == 1 { "" } else { "s" };
+            parts.push(format!("{} {}{}{}{}", count, FG_CYAN, unit, plural, RESET));
+        }
+    }
+    parts.join(", ")
+}
+
+fn bytes(bytes: u64) -> String {
+    const KIB: u64 = 1024;
+    let (value, unit) = match bytes {
+        b if b < KIB => return format!("{} {}B{}", b, FG_CYAN, RESET),
+        b if b < KIB.pow(2) => (b as f64 / KIB as f64, "KiB"),
+        b if b < KIB.pow(3) => (b as f64 / KIB.pow(2) as f64, "MiB"),
+        b if b < KIB.pow(4) => (b as f64 / KIB.pow(3) as f64, "GiB"),
+        b => (b as f64 / KIB.pow(4) as f64, "TiB"),
+    };
+    format!("{:.2} {}{}{}", value, FG_CYAN, unit, RESET)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn example_device() -> anyhow::Result<Device> {
+        Ok(Device {
+            ifindex: 5,
+            ifname: "wg0".to_string(),
+            private_key: Some("yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk=".parse()?),
+            public_key: Some("HIgo9xNzJMWLKASShiTqIybxZ0U3wGLiUeJ1PKf8ykw=".parse()?),
+            listen_port: 51820,
+            fwmark: 0x1234,
+            peers: vec![
+                Peer {
+                    public_key: "TrMvSoP4jYQlY6RIzBgbssQqY3vxI2Pi+y71lOWWXX0=".parse()?,
+                    preshared_key: PresharedKey::zero(),
+                    endpoint: None,
+                    persistent_keepalive_interval: 0,
+                    last_handshake_time: Duration::from_secs(0),
+                    rx_bytes: 0,
+                    tx_bytes: 0,
+                    allowed_ips: vec![],
+                    protocol_version: 1,
+                },
+                Peer {
+                    public_key: "xTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg=".parse()?,
+                    preshared_key: "/UwcSPg38hW/D9Y3tcS1FOV0K1wuURMbS0sesJEP5ak=".parse()?,
+                    endpoint: Some("192.95.5.67:1234".parse()?),
+                    persistent_keepalive_interval: 25,
+                    last_handshake_time: Duration::new(1_600_000_000, 5),
+                    rx_bytes: 2148,
+                    tx_bytes: 5_000_000,
+                    allowed_ips: vec!["10.192.122.3/32".parse()?, "fd00::/64".parse()?],
+                    protocol_version: 1,
+                },
+            ],
+        })
+    }
+
+    #[test]
+    fn pretty_sorts_peers_by_latest_handshake() -> anyhow::Result<()> {
+        let text = strip_colors(&pretty(&example_device()?, 1_600_003_723, false));
+        let expected = "\
+interface: wg0
+  public key: HIgo9xNzJMWLKASShiTqIybxZ0U3wGLiUeJ1PKf8ykw=
+  private key: (hidden)
+  listening port: 51820
+  fwmark: 0x1234
+
+peer: xTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg=
+  preshared key: (hidden)
+  endpoint: 192.95.5.67:1234
+  allowed ips: 10.192.122.3/32, fd00::/64
+  latest handshake: 1 hour, 2 minutes, 3 seconds ago
+  transfer: 2.10 KiB received, 4.77 MiB sent
+  persistent keepalive: every 25 seconds
+
+peer: TrMvSoP4jYQlY6RIzBgbssQqY3vxI2Pi+y71lOWWXX0=
+  allowed ips: (none)
+";
+        assert_eq!(text, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn pretty_colors() -> anyhow::Result<()> {
+        let text = pretty(&example_device()?, 1_600_000_000, true);
+        assert!(text.starts_with("\x1b[0m\x1b[32m\x1b[1minterface\x1b[0m: \x1b[32mwg0\x1b[0m\n"));
+        assert!(text.contains("  \x1b[1mprivate key\x1b[0m: yAnz5TF+"));
+        assert!(text.contains("  \x1b[1mlatest handshake\x1b[0m: Now\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn ugly_fields() -> anyhow::Result<()> {
+        let device = example_device()?;
+        let field = |field| ugly(&device, field, false).unwrap();
+
+        assert_eq!(field("listen-port"), "51820\n");
+        assert_eq!(field("fwmark"), "0x1234\n");
+        assert_eq!(
+            field("endpoints"),
+            "TrMvSoP4jYQlY6RIzBgbssQqY3vxI2Pi+y71lOWWXX0=\t(none)\n\
+             xTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg=\t192.95.5.67:1234\n"
+        );
+        assert_eq!(
+            field("allowed-ips"),
+            "TrMvSoP4jYQlY6RIzBgbssQqY3vxI2Pi+y71lOWWXX0=\t(none)\n\
+             xTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg=\t10.192.122.3/32 fd00::/64\n"
+        );
+        assert_eq!(
+            ugly(&device, "persistent-keepalive", true).unwrap(),
+            "wg0\tTrMvSoP4jYQlY6RIzBgbssQqY3vxI2Pi+y71lOWWXX0=\toff\n\
+             wg0\txTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg=\t25\n"
+        );
+        assert_eq!(ugly(&device, "mtu", false), None);
+        Ok(())
+    }
+
+    #[test]
+    fn pretty_time_units() {
+        assert_eq!(strip_colors(&pretty_time(1)), "1 second");
+        assert_eq!(
+            strip_colors(&pretty_time(366 * 24 * 60 * 60 + 60)),
+            "1 year, 1 day, 1 minute"
+        );
+        assert_eq!(strip_colors(&bytes(1023)), "1023 B");
+        assert_eq!(strip_colors(&bytes(1024 * 1024 * 1024 * 1536)), "1.50 TiB");
+    }
+}
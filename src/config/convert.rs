@@ -0,0 +1,155 @@
+use super::Config;
+use crate::api;
+
+#[cfg(target_os = "linux")]
+use crate::linux::{set, DeviceInterface};
+#[cfg(feature = "xplatform")]
+use crate::xplatform;
+
+impl Config {
+    /// Builds the request `wg setconf` would send for this config. All existing
+    /// peers on the interface are replaced, as are the allowed IPs of each peer.
+    #[cfg(target_os = "linux")]
+    pub fn to_linux_set_device<'a>(&'a self, interface: DeviceInterface<'a>) -> set::Device<'a> {
+        set::Device {
+            interface,
+            flags: vec![set::WgDeviceF::ReplacePeers],
+            private_key: self.interface.private_key.as_ref(),
+            listen_port: self.interface.listen_port,
+            fwmark: self.interface.fwmark,
+            peers: self
+                .peers
+                .iter()
+                .map(|peer| set::Peer {
+                    public_key: &peer.public_key,
+                    flags: vec![set::WgPeerF::ReplaceAllowedIps],
+                    preshared_key: peer.preshared_key.as_ref(),
+                    endpoint: peer.endpoint.as_ref(),
+                    persistent_keepalive_interval: peer.persistent_keepalive_interval,
+                    allowed_ips: peer
+                        .allowed_ips
+                        .iter()
+                        .map(|allowed_ip| set::AllowedIp {
+                            ipaddr: &allowed_ip.ipaddr,
+                            cidr_mask: Some(allowed_ip.cidr_mask),
+                        })
+                        .collect(),
+                    protocol_version: None,
+                })
+                .collect(),
+        }
+    }
+
+    /// Builds the request `wg setconf` would send for this config. All existing
+    /// peers on the interface are replaced, as are the allowed IPs of each peer.
+    #[cfg(feature = "xplatform")]
+    pub fn to_xplatform_set_device(&self) -> xplatform::set::Device {
+        xplatform::set::Device {
+            private_key: self.interface.private_key.clone(),
+            listen_port: self.interface.listen_port,
+            fwmark: self.interface.fwmark,
+            replace_peers: Some(true),
+            peers: self
+                .peers
+                .iter()
+                .map(|peer| xplatform::set::Peer {
+                    preshared_key: peer.preshared_key.clone(),
+                    endpoint: peer.endpoint,
+                    persistent_keepalive_interval: peer.persistent_keepalive_interval,
+                    replace_allowed_ips: Some(true),
+                    allowed_ips: peer
+                        .allowed_ips
+                        .iter()
+                        .map(|allowed_ip| xplatform::set::AllowedIp {
+                            ipaddr: allowed_ip.ipaddr,
+                            cidr_mask: allowed_ip.cidr_mask,
+                        })
+                        .collect(),
+                    ..xplatform::set::Peer::from_public_key(peer.public_key)
+                })
+                .collect(),
+        }
+    }
+}
+
+/// The same replace-everything request as [`Config::to_linux_set_device`], for
+/// any [`WireGuardApi`][api::WireGuardApi] backend.
+impl From<&Config> for api::set::Device {
+    fn from(config: &Config) -> Self {
+        Self {
+            private_key: config.interface.private_key.clone(),
+            listen_port: config.interface.listen_port,
+            fwmark: config.interface.fwmark,
+            replace_peers: true,
+            peers: config
+                .peers
+                .iter()
+                .map(|peer| api::set::Peer {
+                    preshared_key: peer.preshared_key.clone(),
+                    endpoint: peer.endpoint,
+                    persistent_keepalive_interval: peer.persistent_keepalive_interval,
+                    replace_allowed_ips: true,
+                    allowed_ips: peer.allowed_ips.iter().map(Into::into).collect(),
+                    ..api::set::Peer::from_public_key(peer.public_key)
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = "
+        [Interface]
+        PrivateKey = yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk=
+        ListenPort = 51820
+
+        [Peer]
+        PublicKey = xTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg=
+        PresharedKey = /UwcSPg38hW/D9Y3tcS1FOV0K1wuURMbS0sesJEP5ak=
+        Endpoint = 192.95.5.67:1234
+        AllowedIPs = 10.192.122.3/32, 10.192.124.1/24
+    ";
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn to_linux_set_device() -> anyhow::Result<()> {
+        let config: Config = CONFIG.parse()?;
+        let device = config.to_linux_set_device(DeviceInterface::from_name("wgtest0"));
+
+        assert_eq!(device.flags, vec![set::WgDeviceF::ReplacePeers]);
+        assert_eq!(device.private_key, config.interface.private_key.as_ref());
+        assert_eq!(device.listen_port, Some(51820));
+        assert_eq!(device.fwmark, None);
+
+        let peer = &device.peers[0];
+        assert_eq!(peer.flags, vec![set::WgPeerF::ReplaceAllowedIps]);
+        assert_eq!(peer.public_key, &config.peers[0].public_key);
+        assert_eq!(peer.preshared_key, config.peers[0].preshared_key.as_ref());
+        assert_eq!(peer.endpoint, Some(&"192.95.5.67:1234".parse()?));
+        assert_eq!(peer.allowed_ips.len(), 2);
+        assert_eq!(peer.allowed_ips[1].cidr_mask, Some(24));
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "xplatform")]
+    fn to_xplatform_set_device() -> anyhow::Result<()> {
+        let config: Config = CONFIG.parse()?;
+
+        let expected = "\
+            private_key=c809f3e5317e9575c9b5ed78b638b7ce530dabe85ddab614220241801ddf0669\n\
+            listen_port=51820\n\
+            replace_peers=true\n\
+            public_key=c53201039adba14be71f886da1d8dbe9eebded08cb111b75340078999aa9f038\n\
+            preshared_key=fd4c1c48f837f215bf0fd637b5c4b514e5742b5c2e51131b4b4b1eb0910fe5a9\n\
+            endpoint=192.95.5.67:1234\n\
+            replace_allowed_ips=true\n\
+            allowed_ip=10.192.122.3/32\n\
+            allowed_ip=10.192.124.1/24\n";
+        assert_eq!(config.to_xplatform_set_device().to_string(), expected);
+        Ok(())
+    }
+}
@@ -0,0 +1,451 @@
+use super::Config;
+use crate::api;
+use crate::get;
+use crate::key::{PresharedKey, PrivateKey, PublicKey};
+use std::collections::{BTreeSet, HashMap};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+#[cfg(target_os = "linux")]
+use crate::linux::{set, DeviceInterface};
+#[cfg(feature = "xplatform")]
+use crate::xplatform;
+
+// Sent to clear the preshared key of a peer that shouldn't have one anymore.
+static ZERO_PRESHARED_KEY: PresharedKey = PresharedKey::zero();
+
+/// The changes needed to bring a running interface in line with a config. Use
+/// [`diff`] to compute one.
+///
+/// Fields are `None` when they're already up to date. Peers that don't need any
+/// changes are left out entirely so their sessions aren't disturbed.
+#[derive(Debug, Default, PartialEq)]
+pub struct DeviceDiff<'a> {
+    pub private_key: Option<&'a PrivateKey>,
+    pub listen_port: Option<u16>,
+    pub fwmark: Option<u32>,
+    pub peers: Vec<PeerDiff<'a>>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct PeerDiff<'a> {
+    pub public_key: &'a PublicKey,
+    /// The peer is on the interface but not in the config.
+    pub remove: bool,
+    pub preshared_key: Option<&'a PresharedKey>,
+    pub endpoint: Option<&'a SocketAddr>,
+    pub persistent_keepalive_interval: Option<u16>,
+    /// When set, the allowed IPs of the peer should be replaced with this list.
+    pub allowed_ips: Option<&'a [get::AllowedIp]>,
+}
+
+impl<'a> PeerDiff<'a> {
+    fn from_public_key(public_key: &'a PublicKey) -> Self {
+        Self {
+            public_key,
+            remove: false,
+            preshared_key: None,
+            endpoint: None,
+            persistent_keepalive_interval: None,
+            allowed_ips: None,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        !self.remove
+            && self.preshared_key.is_none()
+            && self.endpoint.is_none()
+            && self.persistent_keepalive_interval.is_none()
+            && self.allowed_ips.is_none()
+    }
+}
+
+/// Computes the smallest change that makes `current` match `desired`, similar
+/// to `wg syncconf`.
+///
+/// Interface fields missing from the config are left as they are, as are peer
+/// endpoints, since the kernel learns those from incoming traffic. A missing
+/// preshared key or persistent keepalive means the peer shouldn't have one.
+/// Allowed IPs are compared as sets after masking off host bits, the same way
+/// the kernel stores them.
+pub fn diff<'a>(current: &'a get::Device, desired: &'a Config) -> DeviceDiff<'a> {
+    let interface = &desired.interface;
+    let mut device_diff = DeviceDiff {
+        private_key: interface
+            .private_key
+            .as_ref()
+            .filter(|&key| current.private_key.as_ref() != Some(key)),
+        listen_port: interface
+            .listen_port
+            .filter(|&port| port != current.listen_port),
+        fwmark: interface.fwmark.filter(|&fwmark| fwmark != current.fwmark),
+        peers: vec![],
+    };
+
+    let mut current_peers: HashMap<&PublicKey, &get::Peer> = current
+        .peers
+        .iter()
+        .map(|peer| (&peer.public_key, peer))
+        .collect();
+
+    for peer in &desired.peers {
+        let current_peer = current_peers.remove(&peer.public_key);
+        let mut peer_diff = PeerDiff::from_public_key(&peer.public_key);
+
+        let preshared_key = peer.preshared_key.as_ref().unwrap_or(&ZERO_PRESHARED_KEY);
+        let keepalive = peer.persistent_keepalive_interval.unwrap_or(0);
+
+        match current_peer {
+            Some(current_peer) => {
+                peer_diff.preshared_key =
+                    Some(preshared_key).filter(|&key| key != &current_peer.preshared_key);
+                peer_diff.endpoint = peer
+                    .endpoint
+                    .as_ref()
+                    .filter(|&endpoint| Some(*endpoint) != current_peer.endpoint);
+                peer_diff.persistent_keepalive_interval =
+                    Some(keepalive).filter(|&k| k != current_peer.persistent_keepalive_interval);
+                peer_diff.allowed_ips = Some(&peer.allowed_ips[..]).filter(|allowed_ips| {
+                    allowed_ip_set(allowed_ips) != allowed_ip_set(&current_peer.allowed_ips)
+                });
+            }
+            None => {
+                // New peers only need the fields that differ from the defaults.
+                peer_diff.preshared_key = Some(preshared_key).filter(|key| !key.is_zero());
+                peer_diff.endpoint = peer.endpoint.as_ref();
+                peer_diff.persistent_keepalive_interval = Some(keepalive).filter(|&k| k != 0);
+                peer_diff.allowed_ips =
+                    Some(&peer.allowed_ips[..]).filter(|allowed_ips| !allowed_ips.is_empty());
+
+                // Make sure the peer is created even if every field is a default.
+                if peer_diff.is_empty() {
+                    peer_diff.persistent_keepalive_interval = Some(keepalive);
+                }
+            }
+        }
+
+        if !peer_diff.is_empty() {
+            device_diff.peers.push(peer_diff);
+        }
+    }
+
+    // Keep removals in the order the interface reports peers.
+    for peer in &current.peers {
+        if current_peers.contains_key(&peer.public_key) {
+            let mut peer_diff = PeerDiff::from_public_key(&peer.public_key);
+            peer_diff.remove = true;
+            device_diff.peers.push(peer_diff);
+        }
+    }
+
+    device_diff
+}
+
+fn allowed_ip_set(allowed_ips: &[get::AllowedIp]) -> BTreeSet<(IpAddr, u8)> {
+    allowed_ips
+        .iter()
+        .map(|allowed_ip| mask_host_bits(allowed_ip.ipaddr, allowed_ip.cidr_mask))
+        .collect()
+}
+
+fn mask_host_bits(ipaddr: IpAddr, cidr_mask: u8) -> (IpAddr, u8) {
+    match ipaddr {
+        IpAddr::V4(addr) => {
+            let cidr_mask = cidr_mask.min(32);
+            let mask = u32::MAX.checked_shl(32 - u32::from(cidr_mask)).unwrap_or(0);
+            (Ipv4Addr::from(u32::from(addr) & mask).into(), cidr_mask)
+        }
+        IpAddr::V6(addr) => {
+            let cidr_mask = cidr_mask.min(128);
+            let mask = u128::MAX
+                .checked_shl(128 - u32::from(cidr_mask))
+                .unwrap_or(0);
+            (Ipv6Addr::from(u128::from(addr) & mask).into(), cidr_mask)
+        }
+    }
+}
+
+impl<'a> DeviceDiff<'a> {
+    /// True if the interface already matches the config.
+    pub fn is_empty(&self) -> bool {
+        self.private_key.is_none()
+            && self.listen_port.is_none()
+            && self.fwmark.is_none()
+            && self.peers.is_empty()
+    }
+
+    #[cfg(target_os = "linux")]
+    pub fn to_linux_set_device(&self, interface: DeviceInterface<'a>) -> set::Device<'a> {
+        set::Device {
+            interface,
+            flags: vec![],
+            private_key: self.private_key,
+            listen_port: self.listen_port,
+            fwmark: self.fwmark,
+            peers: self
+                .peers
+                .iter()
+                .map(|peer| {
+                    let mut flags = vec![];
+                    if peer.remove {
+                        flags.push(set::WgPeerF::RemoveMe);
+                    }
+                    if peer.allowed_ips.is_some() {
+                        flags.push(set::WgPeerF::ReplaceAllowedIps);
+                    }
+
+                    set::Peer {
+                        public_key: peer.public_key,
+                        flags,
+                        preshared_key: peer.preshared_key,
+                        endpoint: peer.endpoint,
+                        persistent_keepalive_interval: peer.persistent_keepalive_interval,
+                        allowed_ips: peer
+                            .allowed_ips
+                            .unwrap_or_default()
+                            .iter()
+                            .map(|allowed_ip| set::AllowedIp {
+                                ipaddr: &allowed_ip.ipaddr,
+                                cidr_mask: Some(allowed_ip.cidr_mask),
+                            })
+                            .collect(),
+                        protocol_version: None,
+                    }
+                })
+                .collect(),
+        }
+    }
+
+    #[cfg(feature = "xplatform")]
+    pub fn to_xplatform_set_device(&self) -> xplatform::set::Device {
+        xplatform::set::Device {
+            private_key: self.private_key.cloned(),
+            listen_port: self.listen_port,
+            fwmark: self.fwmark,
+            replace_peers: None,
+            peers: self
+                .peers
+                .iter()
+                .map(|peer| xplatform::set::Peer {
+                    remove: Some(true).filter(|_| peer.remove),
+                    preshared_key: peer.preshared_key.cloned(),
+                    endpoint: peer.endpoint.copied(),
+                    persistent_keepalive_interval: peer.persistent_keepalive_interval,
+                    replace_allowed_ips: peer.allowed_ips.map(|_| true),
+                    allowed_ips: peer
+                        .allowed_ips
+                        .unwrap_or_default()
+                        .iter()
+                        .map(|allowed_ip| xplatform::set::AllowedIp {
+                            ipaddr: allowed_ip.ipaddr,
+                            cidr_mask: allowed_ip.cidr_mask,
+                        })
+                        .collect(),
+                    ..xplatform::set::Peer::from_public_key(*peer.public_key)
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<&DeviceDiff<'_>> for api::set::Device {
+    fn from(device_diff: &DeviceDiff<'_>) -> Self {
+        Self {
+            private_key: device_diff.private_key.cloned(),
+            listen_port: device_diff.listen_port,
+            fwmark: device_diff.fwmark,
+            replace_peers: false,
+            peers: device_diff
+                .peers
+                .iter()
+                .map(|peer| api::set::Peer {
+                    remove: peer.remove,
+                    preshared_key: peer.preshared_key.cloned(),
+                    endpoint: peer.endpoint.copied(),
+                    persistent_keepalive_interval: peer.persistent_keepalive_interval,
+                    replace_allowed_ips: peer.allowed_ips.is_some(),
+                    allowed_ips: peer
+                        .allowed_ips
+                        .unwrap_or_default()
+                        .iter()
+                        .map(Into::into)
+                        .collect(),
+                    ..api::set::Peer::from_public_key(*peer.public_key)
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    const PEER_A: &str = "xTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg=";
+    const PEER_B: &str = "TrMvSoP4jYQlY6RIzBgbssQqY3vxI2Pi+y71lOWWXX0=";
+    const PEER_C: &str = "gN65BkIKy1eCE9pP1wdc8ROUtkHLF2PfAqYdyYBz6EA=";
+
+    fn current_device() -> anyhow::Result<get::Device> {
+        let peer = |public_key: &str, allowed_ips: &[&str]| -> anyhow::Result<get::Peer> {
+            Ok(get::Peer {
+                public_key: public_key.parse()?,
+                preshared_key: PresharedKey::zero(),
+                endpoint: Some("192.95.5.67:1234".parse()?),
+                persistent_keepalive_interval: 0,
+                last_handshake_time: Duration::from_secs(0),
+                rx_bytes: 0,
+                tx_bytes: 0,
+                allowed_ips: allowed_ips
+                    .iter()
+                    .map(|allowed_ip| allowed_ip.parse())
+                    .collect::<Result<_, _>>()?,
+                protocol_version: 1,
+            })
+        };
+
+        Ok(get::Device {
+            ifindex: 6,
+            ifname: "wgtest0".to_string(),
+            private_key: Some("yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk=".parse()?),
+            public_key: Some("HIgo9xNzJMWLKASShiTqIybxZ0U3wGLiUeJ1PKf8ykw=".parse()?),
+            listen_port: 51820,
+            fwmark: 0,
+            peers: vec![
+                peer(PEER_A, &["10.192.122.3/32", "10.192.124.0/24"])?,
+                peer(PEER_B, &["10.192.122.4/32"])?,
+            ],
+        })
+    }
+
+    #[test]
+    fn diff_of_matching_config_is_empty() -> anyhow::Result<()> {
+        let current = current_device()?;
+        // Allowed IPs are reordered and have host bits set, but are equivalent.
+        let desired: Config = format!(
+            "[Interface]
+            PrivateKey = yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk=
+            ListenPort = 51820
+            [Peer]
+            PublicKey = {}
+            AllowedIPs = 10.192.124.1/24, 10.192.122.3/32
+            [Peer]
+            PublicKey = {}
+            AllowedIPs = 10.192.122.4",
+            PEER_A, PEER_B
+        )
+        .parse()?;
+
+        assert_eq!(diff(&current, &desired), DeviceDiff::default());
+        Ok(())
+    }
+
+    #[test]
+    fn diff_changed_peers() -> anyhow::Result<()> {
+        let current = current_device()?;
+        let desired: Config = format!(
+            "[Interface]
+            ListenPort = 51821
+            [Peer]
+            PublicKey = {}
+            AllowedIPs = 10.192.122.3/32, 10.192.124.0/24
+            PersistentKeepalive = 25
+            [Peer]
+            PublicKey = {}
+            AllowedIPs = 10.192.122.5/32",
+            PEER_A, PEER_C
+        )
+        .parse()?;
+
+        let device_diff = diff(&current, &desired);
+        let expected = DeviceDiff {
+            private_key: None,
+            listen_port: Some(51821),
+            fwmark: None,
+            peers: vec![
+                PeerDiff {
+                    persistent_keepalive_interval: Some(25),
+                    ..PeerDiff::from_public_key(&desired.peers[0].public_key)
+                },
+                PeerDiff {
+                    allowed_ips: Some(&desired.peers[1].allowed_ips),
+                    ..PeerDiff::from_public_key(&desired.peers[1].public_key)
+                },
+                PeerDiff {
+                    remove: true,
+                    ..PeerDiff::from_public_key(&current.peers[1].public_key)
+                },
+            ],
+        };
+        assert_eq!(device_diff, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn diff_removes_preshared_key() -> anyhow::Result<()> {
+        let mut current = current_device()?;
+        current.peers[0].preshared_key = PresharedKey::generate();
+        current.peers.truncate(1);
+
+        let desired: Config = format!(
+            "[Interface]
+            [Peer]
+            PublicKey = {}
+            AllowedIPs = 10.192.122.3/32, 10.192.124.0/24",
+            PEER_A
+        )
+        .parse()?;
+
+        let device_diff = diff(&current, &desired);
+        assert_eq!(device_diff.peers.len(), 1);
+        assert!(device_diff.peers[0].preshared_key.unwrap().is_zero());
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn diff_to_linux_set_device() -> anyhow::Result<()> {
+        let current = current_device()?;
+        let desired: Config = format!(
+            "[Interface]
+            [Peer]
+            PublicKey = {}
+            AllowedIPs = 10.192.122.3/32",
+            PEER_A
+        )
+        .parse()?;
+
+        let device_diff = diff(&current, &desired);
+        let device = device_diff.to_linux_set_device(DeviceInterface::from_name("wgtest0"));
+        assert!(device.flags.is_empty());
+        assert_eq!(device.peers.len(), 2);
+        assert_eq!(device.peers[0].flags, vec![set::WgPeerF::ReplaceAllowedIps]);
+        assert_eq!(device.peers[0].allowed_ips.len(), 1);
+        assert_eq!(device.peers[1].flags, vec![set::WgPeerF::RemoveMe]);
+        assert!(device.peers[1].allowed_ips.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "xplatform")]
+    fn diff_to_xplatform_set_device() -> anyhow::Result<()> {
+        let current = current_device()?;
+        let desired: Config = format!(
+            "[Interface]
+            [Peer]
+            PublicKey = {}
+            AllowedIPs = 10.192.122.3/32",
+            PEER_A
+        )
+        .parse()?;
+
+        let expected = "\
+            public_key=c53201039adba14be71f886da1d8dbe9eebded08cb111b75340078999aa9f038\n\
+            replace_allowed_ips=true\n\
+            allowed_ip=10.192.122.3/32\n\
+            public_key=4eb32f4a83f88d842563a448cc181bb2c42a637bf12363e2fb2ef594e5965d7d\n\
+            remove=true\n";
+        let device = diff(&current, &desired).to_xplatform_set_device();
+        assert_eq!(device.to_string(), expected);
+        Ok(())
+    }
+}
@@ -0,0 +1,258 @@
+//! Configuration files in the format read by `wg setconf` and written by
+//! `wg showconf`.
+//!
+//! The format is INI-like. An `[Interface]` section holds device-level keys and
+//! each `[Peer]` section describes one peer. See the *CONFIGURATION FILE FORMAT*
+//! section of `man wg` for the full specification.
+//!
+//! ```
+//! use wireguard_uapi::config::Config;
+//!
+//! let config: Config = "
+//! [Interface]
+//! PrivateKey = yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk=
+//! ListenPort = 51820
+//!
+//! [Peer]
+//! PublicKey = xTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg=
+//! Endpoint = 192.95.5.67:1234
+//! AllowedIPs = 10.192.122.3/32, 10.192.124.1/24
+//! "
+//! .parse()
+//! .unwrap();
+//!
+//! assert_eq!(config.interface.listen_port, Some(51820));
+//! assert_eq!(config.peers[0].allowed_ips.len(), 2);
+//! ```
+//!
+//! Formatting a [`Config`] produces the same output as `wg showconf`. A
+//! [`get::Device`] can be converted into a [`Config`] to render the current
+//! state of an interface.
+//!
+//! Files written for wg-quick contain additional keys such as `Address` and
+//! `DNS`. Parse those as a [`QuickConfig`] instead.
+//!
+//! To apply a config to a running interface without resetting every peer, use
+//! [`diff`] to compute only the changes that are needed, like `wg syncconf`.
+
+mod convert;
+mod diff;
+mod parse;
+mod quick;
+
+pub use diff::{diff, DeviceDiff, PeerDiff};
+pub use parse::{ParseConfigError, ParseConfigErrorKind};
+pub use quick::{NetworkSettings, QuickConfig, Table};
+
+use crate::get;
+use crate::key::{PresharedKey, PrivateKey, PublicKey};
+use std::fmt;
+use std::net::SocketAddr;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Config {
+    pub interface: Interface,
+    pub peers: Vec<Peer>,
+}
+
+/// The `[Interface]` section. Keys that are missing from the file are `None`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Interface {
+    pub private_key: Option<PrivateKey>,
+    pub listen_port: Option<u16>,
+    /// `FwMark = off` is parsed as 0.
+    pub fwmark: Option<u32>,
+}
+
+/// A `[Peer]` section.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Peer {
+    pub public_key: PublicKey,
+    pub preshared_key: Option<PresharedKey>,
+    pub allowed_ips: Vec<get::AllowedIp>,
+    /// Hostnames are resolved while parsing, the same way `wg setconf` does.
+    pub endpoint: Option<SocketAddr>,
+    /// `PersistentKeepalive = off` is parsed as 0.
+    pub persistent_keepalive_interval: Option<u16>,
+}
+
+impl Peer {
+    pub fn from_public_key(public_key: PublicKey) -> Self {
+        Self {
+            public_key,
+            preshared_key: None,
+            allowed_ips: vec![],
+            endpoint: None,
+            persistent_keepalive_interval: None,
+        }
+    }
+}
+
+/// Converts the current state of a device into a config. Fields that the
+/// kernel reports as zero are left out, the same as `wg showconf`.
+impl From<&get::Device> for Config {
+    fn from(device: &get::Device) -> Self {
+        Self {
+            interface: Interface {
+                private_key: device.private_key.clone(),
+                listen_port: Some(device.listen_port).filter(|&port| port != 0),
+                fwmark: Some(device.fwmark).filter(|&fwmark| fwmark != 0),
+            },
+            peers: device.peers.iter().map(Peer::from).collect(),
+        }
+    }
+}
+
+impl From<&get::Peer> for Peer {
+    fn from(peer: &get::Peer) -> Self {
+        Self {
+            public_key: peer.public_key,
+            preshared_key: Some(peer.preshared_key.clone()).filter(|key| !key.is_zero()),
+            allowed_ips: peer.allowed_ips.clone(),
+            endpoint: peer.endpoint,
+            persistent_keepalive_interval: Some(peer.persistent_keepalive_interval)
+                .filter(|&interval| interval != 0),
+        }
+    }
+}
+
+/// Formats the config the same way as `wg showconf`.
+impl fmt::Display for Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.interface.fmt(f)?;
+
+        for (i, peer) in self.peers.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            peer.fmt(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for Interface {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "[Interface]")?;
+
+        if let Some(listen_port) = self.listen_port {
+            writeln!(f, "ListenPort = {}", listen_port)?;
+        }
+
+        if let Some(fwmark) = self.fwmark {
+            writeln!(f, "FwMark = {:#x}", fwmark)?;
+        }
+
+        if let Some(private_key) = &self.private_key {
+            writeln!(f, "PrivateKey = {}", private_key)?;
+        }
+
+        writeln!(f)
+    }
+}
+
+impl fmt::Display for Peer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "[Peer]")?;
+        writeln!(f, "PublicKey = {}", self.public_key)?;
+
+        if let Some(preshared_key) = &self.preshared_key {
+            writeln!(f, "PresharedKey = {}", preshared_key)?;
+        }
+
+        if !self.allowed_ips.is_empty() {
+            write!(f, "AllowedIPs = ")?;
+            for (i, allowed_ip) in self.allowed_ips.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{}/{}", allowed_ip.ipaddr, allowed_ip.cidr_mask)?;
+            }
+            writeln!(f)?;
+        }
+
+        if let Some(endpoint) = self.endpoint {
+            writeln!(f, "Endpoint = {}", endpoint)?;
+        }
+
+        if let Some(interval) = self.persistent_keepalive_interval {
+            writeln!(f, "PersistentKeepalive = {}", interval)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    const SHOWCONF: &str = "\
+[Interface]
+ListenPort = 51820
+FwMark = 0x1234
+PrivateKey = yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk=
+
+[Peer]
+PublicKey = xTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg=
+PresharedKey = /UwcSPg38hW/D9Y3tcS1FOV0K1wuURMbS0sesJEP5ak=
+AllowedIPs = 10.192.122.3/32, 10.192.124.0/24
+Endpoint = 192.95.5.67:1234
+PersistentKeepalive = 25
+
+[Peer]
+PublicKey = TrMvSoP4jYQlY6RIzBgbssQqY3vxI2Pi+y71lOWWXX0=
+AllowedIPs = 10.192.122.4/32, fd00::/64
+Endpoint = [2607:5300:60:6b0::c05f:543]:2468
+";
+
+    #[test]
+    fn display_round_trips() -> anyhow::Result<()> {
+        let config: Config = SHOWCONF.parse()?;
+        assert_eq!(config.to_string(), SHOWCONF);
+        Ok(())
+    }
+
+    #[test]
+    fn display_empty_interface() {
+        assert_eq!(Config::default().to_string(), "[Interface]\n\n");
+    }
+
+    #[test]
+    fn from_get_device() -> anyhow::Result<()> {
+        let device = get::Device {
+            ifindex: 6,
+            ifname: "wgtest0".to_string(),
+            private_key: Some("yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk=".parse()?),
+            public_key: Some("HIgo9xNzJMWLKASShiTqIybxZ0U3wGLiUeJ1PKf8ykw=".parse()?),
+            listen_port: 51820,
+            fwmark: 0,
+            peers: vec![get::Peer {
+                public_key: "xTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg=".parse()?,
+                preshared_key: PresharedKey::zero(),
+                endpoint: Some("192.95.5.67:1234".parse()?),
+                persistent_keepalive_interval: 0,
+                last_handshake_time: Duration::from_secs(1_600_000_000),
+                rx_bytes: 2148,
+                tx_bytes: 2200,
+                allowed_ips: vec!["10.192.122.3/32".parse()?],
+                protocol_version: 1,
+            }],
+        };
+
+        let expected = "\
+[Interface]
+ListenPort = 51820
+PrivateKey = yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk=
+
+[Peer]
+PublicKey = xTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg=
+AllowedIPs = 10.192.122.3/32
+Endpoint = 192.95.5.67:1234
+";
+        assert_eq!(Config::from(&device).to_string(), expected);
+        Ok(())
+    }
+}
@@ -0,0 +1,485 @@
+use super::{Config, Interface, Peer};
+use crate::get::AllowedIp;
+use crate::key::{PresharedKey, PrivateKey, PublicKey};
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::str::FromStr;
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq)]
+#[error("Line {line}: {kind}")]
+pub struct ParseConfigError {
+    /// The 1-based line number the error was found on.
+    pub line: usize,
+    #[source]
+    pub kind: ParseConfigErrorKind,
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum ParseConfigErrorKind {
+    #[error("Expected a `[Section]` header or a `Key = Value` pair")]
+    InvalidLine,
+    #[error("Unknown section: `[{0}]`")]
+    UnknownSection(String),
+    #[error("Key `{0}` appears before any section")]
+    KeyOutsideSection(String),
+    #[error("Unknown key: `{0}`")]
+    UnknownKey(String),
+    #[error("Peer is missing a PublicKey")]
+    MissingPublicKey,
+
+    #[error("Invalid PrivateKey")]
+    InvalidPrivateKey,
+    #[error("Invalid PublicKey: `{0}`")]
+    InvalidPublicKey(String),
+    #[error("Invalid PresharedKey")]
+    InvalidPresharedKey,
+    #[error("Invalid ListenPort: `{0}`")]
+    InvalidListenPort(String),
+    #[error("Invalid FwMark: `{0}`")]
+    InvalidFwmark(String),
+    #[error("Invalid AllowedIPs entry: `{0}`")]
+    InvalidAllowedIp(String),
+    #[error("Invalid Endpoint: `{0}`")]
+    InvalidEndpoint(String),
+    #[error("Invalid PersistentKeepalive: `{0}`")]
+    InvalidPersistentKeepalive(String),
+
+    #[error("Invalid Address: `{0}`")]
+    InvalidAddress(String),
+    #[error("Invalid MTU: `{0}`")]
+    InvalidMtu(String),
+    #[error("Invalid Table: `{0}`")]
+    InvalidTable(String),
+    #[error("Invalid SaveConfig, expected `true` or `false`: `{0}`")]
+    InvalidSaveConfig(String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Section {
+    Interface,
+    Peer,
+}
+
+enum Line<'a> {
+    Section(&'a str),
+    Pair(&'a str, &'a str),
+}
+
+fn tokenize(line: &str) -> Result<Option<Line<'_>>, ParseConfigErrorKind> {
+    // Everything after a # is a comment.
+    let line = line.split('#').next().unwrap_or_default().trim();
+    if line.is_empty() {
+        return Ok(None);
+    }
+
+    if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+        return Ok(Some(Line::Section(name.trim())));
+    }
+
+    match line.split_once('=') {
+        Some((key, value)) => Ok(Some(Line::Pair(key.trim(), value.trim()))),
+        None => Err(ParseConfigErrorKind::InvalidLine),
+    }
+}
+
+/// A peer whose section hasn't ended yet. The public key may appear anywhere in
+/// the section, so it can't be required until the next section starts.
+struct PartialPeer {
+    line: usize,
+    public_key: Option<PublicKey>,
+    preshared_key: Option<PresharedKey>,
+    allowed_ips: Vec<AllowedIp>,
+    endpoint: Option<SocketAddr>,
+    persistent_keepalive_interval: Option<u16>,
+}
+
+impl PartialPeer {
+    fn new(line: usize) -> Self {
+        Self {
+            line,
+            public_key: None,
+            preshared_key: None,
+            allowed_ips: vec![],
+            endpoint: None,
+            persistent_keepalive_interval: None,
+        }
+    }
+
+    fn finish(self) -> Result<Peer, ParseConfigError> {
+        let public_key = self.public_key.ok_or(ParseConfigError {
+            line: self.line,
+            kind: ParseConfigErrorKind::MissingPublicKey,
+        })?;
+
+        Ok(Peer {
+            public_key,
+            preshared_key: self.preshared_key,
+            allowed_ips: self.allowed_ips,
+            endpoint: self.endpoint,
+            persistent_keepalive_interval: self.persistent_keepalive_interval,
+        })
+    }
+}
+
+/// Reads a config one line at a time. Formats that extend the `wg` config
+/// format can hook into [`ConfigParser::parse_line_with`] to handle their own
+/// keys.
+#[derive(Default)]
+pub(crate) struct ConfigParser {
+    interface: Interface,
+    peers: Vec<Peer>,
+    section: Option<Section>,
+    peer: Option<PartialPeer>,
+}
+
+impl ConfigParser {
+    pub fn parse_line(&mut self, number: usize, line: &str) -> Result<(), ParseConfigError> {
+        self.parse_line_with(number, line, |_, _, _| Ok(false))
+    }
+
+    /// The extension is called for every key before the standard keys are
+    /// checked. It returns true if it handled the key.
+    pub fn parse_line_with<F>(
+        &mut self,
+        number: usize,
+        line: &str,
+        extension: F,
+    ) -> Result<(), ParseConfigError>
+    where
+        F: FnOnce(Section, &str, &str) -> Result<bool, ParseConfigErrorKind>,
+    {
+        let with_line = |kind| ParseConfigError { line: number, kind };
+
+        match tokenize(line).map_err(with_line)? {
+            None => Ok(()),
+            Some(Line::Section(name)) => self.start_section(number, name),
+            Some(Line::Pair(key, value)) => {
+                let section = self.section.ok_or_else(|| {
+                    with_line(ParseConfigErrorKind::KeyOutsideSection(key.into()))
+                })?;
+
+                if extension(section, key, value).map_err(with_line)? {
+                    return Ok(());
+                }
+
+                match section {
+                    Section::Interface => self.set_interface_key(key, value),
+                    Section::Peer => self.set_peer_key(key, value),
+                }
+                .map_err(with_line)
+            }
+        }
+    }
+
+    pub fn finish(mut self) -> Result<Config, ParseConfigError> {
+        self.finish_peer()?;
+
+        Ok(Config {
+            interface: self.interface,
+            peers: self.peers,
+        })
+    }
+
+    fn start_section(&mut self, number: usize, name: &str) -> Result<(), ParseConfigError> {
+        self.finish_peer()?;
+
+        let section = if name.eq_ignore_ascii_case("interface") {
+            Section::Interface
+        } else if name.eq_ignore_ascii_case("peer") {
+            self.peer = Some(PartialPeer::new(number));
+            Section::Peer
+        } else {
+            return Err(ParseConfigError {
+                line: number,
+                kind: ParseConfigErrorKind::UnknownSection(name.to_string()),
+            });
+        };
+
+        self.section = Some(section);
+        Ok(())
+    }
+
+    fn finish_peer(&mut self) -> Result<(), ParseConfigError> {
+        if let Some(peer) = self.peer.take() {
+            self.peers.push(peer.finish()?);
+        }
+        Ok(())
+    }
+
+    fn set_interface_key(&mut self, key: &str, value: &str) -> Result<(), ParseConfigErrorKind> {
+        type Error = ParseConfigErrorKind;
+        let interface = &mut self.interface;
+
+        match key.to_ascii_lowercase().as_str() {
+            "privatekey" => {
+                let private_key =
+                    PrivateKey::from_base64(value).map_err(|_| Error::InvalidPrivateKey)?;
+                interface.private_key = Some(private_key);
+            }
+            "listenport" => {
+                let listen_port = value
+                    .parse()
+                    .map_err(|_| Error::InvalidListenPort(value.to_string()))?;
+                interface.listen_port = Some(listen_port);
+            }
+            "fwmark" => {
+                let fwmark =
+                    parse_fwmark(value).ok_or_else(|| Error::InvalidFwmark(value.to_string()))?;
+                interface.fwmark = Some(fwmark);
+            }
+            _ => return Err(Error::UnknownKey(key.to_string())),
+        }
+
+        Ok(())
+    }
+
+    fn set_peer_key(&mut self, key: &str, value: &str) -> Result<(), ParseConfigErrorKind> {
+        type Error = ParseConfigErrorKind;
+        // A peer is always started along with the peer section.
+        let peer = self.peer.as_mut().unwrap();
+
+        match key.to_ascii_lowercase().as_str() {
+            "publickey" => {
+                let public_key = PublicKey::from_base64(value)
+                    .map_err(|_| Error::InvalidPublicKey(value.to_string()))?;
+                peer.public_key = Some(public_key);
+            }
+            "presharedkey" => {
+                let preshared_key =
+                    PresharedKey::from_base64(value).map_err(|_| Error::InvalidPresharedKey)?;
+                peer.preshared_key = Some(preshared_key);
+            }
+            "allowedips" => {
+                // AllowedIPs may be given more than once. Each occurrence adds to the list.
+                for allowed_ip in value.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+                    let allowed_ip = parse_allowed_ip(allowed_ip)
+                        .ok_or_else(|| Error::InvalidAllowedIp(allowed_ip.to_string()))?;
+                    peer.allowed_ips.push(allowed_ip);
+                }
+            }
+            "endpoint" => {
+                let endpoint = parse_endpoint(value)
+                    .ok_or_else(|| Error::InvalidEndpoint(value.to_string()))?;
+                peer.endpoint = Some(endpoint);
+            }
+            "persistentkeepalive" => {
+                let interval = parse_off_or(value)
+                    .ok_or_else(|| Error::InvalidPersistentKeepalive(value.to_string()))?;
+                peer.persistent_keepalive_interval = Some(interval);
+            }
+            _ => return Err(Error::UnknownKey(key.to_string())),
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Config {
+    type Err = ParseConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = ConfigParser::default();
+        for (i, line) in s.lines().enumerate() {
+            parser.parse_line(i + 1, line)?;
+        }
+        parser.finish()
+    }
+}
+
+/// Parses a number, treating "off" as 0.
+fn parse_off_or<T: FromStr + Default>(value: &str) -> Option<T> {
+    if value.eq_ignore_ascii_case("off") {
+        return Some(T::default());
+    }
+    value.parse().ok()
+}
+
+/// Parses a fwmark in decimal or 0x-prefixed hex. "off" is treated as 0.
+fn parse_fwmark(value: &str) -> Option<u32> {
+    match value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => parse_off_or(value),
+    }
+}
+
+/// Unlike [`AllowedIp`]'s `FromStr` implementation, the CIDR mask is optional
+/// and defaults to a single address.
+pub(crate) fn parse_allowed_ip(value: &str) -> Option<AllowedIp> {
+    let (ipaddr, cidr_mask) = match value.split_once('/') {
+        Some((ipaddr, cidr_mask)) => (ipaddr, Some(cidr_mask)),
+        None => (value, None),
+    };
+    let ipaddr: IpAddr = ipaddr.parse().ok()?;
+    let max_cidr_mask = match ipaddr {
+        IpAddr::V4(_) => 32,
+        IpAddr::V6(_) => 128,
+    };
+    let cidr_mask = match cidr_mask {
+        Some(cidr_mask) => cidr_mask
+            .parse()
+            .ok()
+            .filter(|&mask| mask <= max_cidr_mask)?,
+        None => max_cidr_mask,
+    };
+
+    Some(AllowedIp {
+        family: match ipaddr {
+            IpAddr::V4(_) => 2,  // libc::AF_INET
+            IpAddr::V6(_) => 10, // libc::AF_INET6
+        },
+        ipaddr,
+        cidr_mask,
+    })
+}
+
+/// Accepts `IP:port`, `[IPv6]:port` or `hostname:port`. Hostnames are resolved
+/// to their first address.
+fn parse_endpoint(value: &str) -> Option<SocketAddr> {
+    value
+        .parse()
+        .ok()
+        .or_else(|| value.to_socket_addrs().ok()?.next())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    #[test]
+    fn parse_man_page_example() -> anyhow::Result<()> {
+        // The example from "man wg", minus the peer with a hostname endpoint
+        // since resolving it requires network access.
+        let config: Config = "
+            [Interface]
+            PrivateKey = yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk=
+            ListenPort = 51820
+
+            [Peer]
+            PublicKey = xTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg=
+            Endpoint = 192.95.5.67:1234
+            AllowedIPs = 10.192.122.3/32, 10.192.124.1/24
+
+            [Peer]
+            PublicKey = TrMvSoP4jYQlY6RIzBgbssQqY3vxI2Pi+y71lOWWXX0=
+            Endpoint = [2607:5300:60:6b0::c05f:543]:2468
+            AllowedIPs = 10.192.122.4/32, 192.168.0.0/16
+        "
+        .parse()?;
+
+        let expected = Config {
+            interface: Interface {
+                private_key: Some("yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk=".parse()?),
+                listen_port: Some(51820),
+                fwmark: None,
+            },
+            peers: vec![
+                Peer {
+                    public_key: "xTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg=".parse()?,
+                    preshared_key: None,
+                    allowed_ips: vec!["10.192.122.3/32".parse()?, "10.192.124.1/24".parse()?],
+                    endpoint: Some(SocketAddr::new(Ipv4Addr::new(192, 95, 5, 67).into(), 1234)),
+                    persistent_keepalive_interval: None,
+                },
+                Peer {
+                    public_key: "TrMvSoP4jYQlY6RIzBgbssQqY3vxI2Pi+y71lOWWXX0=".parse()?,
+                    preshared_key: None,
+                    allowed_ips: vec!["10.192.122.4/32".parse()?, "192.168.0.0/16".parse()?],
+                    endpoint: Some(SocketAddr::new(
+                        Ipv6Addr::new(0x2607, 0x5300, 0x60, 0x6b0, 0, 0, 0xc05f, 0x543).into(),
+                        2468,
+                    )),
+                    persistent_keepalive_interval: None,
+                },
+            ],
+        };
+
+        assert_eq!(config, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn parse_is_case_insensitive_and_ignores_comments() -> anyhow::Result<()> {
+        let config: Config = "
+            # A comment
+            [interface]
+            FWMARK = 0x10 # Trailing comment
+
+            [PEER]
+            publickey=xTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg=
+            allowedips = 10.0.0.1
+            AllowedIPs = fd00::/64,
+            PersistentKeepalive = off
+        "
+        .parse()?;
+
+        assert_eq!(config.interface.fwmark, Some(16));
+        let peer = &config.peers[0];
+        assert_eq!(
+            peer.allowed_ips,
+            vec!["10.0.0.1/32".parse()?, "fd00::/64".parse()?]
+        );
+        assert_eq!(peer.persistent_keepalive_interval, Some(0));
+        Ok(())
+    }
+
+    #[test]
+    fn parse_fwmark_values() {
+        assert_eq!(parse_fwmark("off"), Some(0));
+        assert_eq!(parse_fwmark("51820"), Some(51820));
+        assert_eq!(parse_fwmark("0xca6c"), Some(51820));
+        assert_eq!(parse_fwmark("0xzz"), None);
+        assert_eq!(parse_fwmark("-1"), None);
+    }
+
+    #[test]
+    fn parse_invalid_configs() {
+        let error = |line, kind| Err(ParseConfigError { line, kind });
+
+        assert_eq!(
+            "ListenPort = 1".parse::<Config>(),
+            error(
+                1,
+                ParseConfigErrorKind::KeyOutsideSection("ListenPort".into())
+            )
+        );
+        assert_eq!(
+            "[Interface]\nListenPort".parse::<Config>(),
+            error(2, ParseConfigErrorKind::InvalidLine)
+        );
+        assert_eq!(
+            "[Interface]\n[Bogus]".parse::<Config>(),
+            error(2, ParseConfigErrorKind::UnknownSection("Bogus".into()))
+        );
+        assert_eq!(
+            "[Interface]\nAddress = 10.0.0.1/24".parse::<Config>(),
+            error(2, ParseConfigErrorKind::UnknownKey("Address".into()))
+        );
+        assert_eq!(
+            "[Interface]\nListenPort = 65536".parse::<Config>(),
+            error(2, ParseConfigErrorKind::InvalidListenPort("65536".into()))
+        );
+        assert_eq!(
+            "[Interface]\nPrivateKey = abc".parse::<Config>(),
+            error(2, ParseConfigErrorKind::InvalidPrivateKey)
+        );
+        assert_eq!(
+            "[Peer]\nAllowedIPs = 10.0.0.1/24\n[Interface]".parse::<Config>(),
+            error(1, ParseConfigErrorKind::MissingPublicKey)
+        );
+        assert_eq!(
+            "[Peer]\nAllowedIPs = 10.0.0.1/33".parse::<Config>(),
+            error(
+                2,
+                ParseConfigErrorKind::InvalidAllowedIp("10.0.0.1/33".into())
+            )
+        );
+        assert_eq!(
+            "[Peer]\nEndpoint = 10.0.0.1".parse::<Config>(),
+            error(2, ParseConfigErrorKind::InvalidEndpoint("10.0.0.1".into()))
+        );
+    }
+}
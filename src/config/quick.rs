@@ -0,0 +1,329 @@
+use super::parse::{parse_allowed_ip, ConfigParser, Section};
+use super::{Config, ParseConfigError, ParseConfigErrorKind};
+use crate::get::AllowedIp;
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+/// A wg-quick configuration file. This is the `wg` format with a few extra
+/// `[Interface]` keys that wg-quick applies itself instead of passing to
+/// `wg setconf`. See `man wg-quick` for their meaning.
+///
+/// ```
+/// use wireguard_uapi::config::QuickConfig;
+///
+/// let quick: QuickConfig = "
+/// [Interface]
+/// Address = 10.200.100.8/24
+/// DNS = 10.200.100.1
+/// PrivateKey = oK56DE9Ue9zK76rAc8pBl6opph+1v36lm7cXXsQKrQM=
+///
+/// [Peer]
+/// PublicKey = GtL7fZc/bLnqZldpVofMCD6hDjrK28SsdLxevJ+qtKU=
+/// AllowedIPs = 0.0.0.0/0
+/// Endpoint = 198.51.100.8:51820
+/// "
+/// .parse()
+/// .unwrap();
+///
+/// // The part of the file that WgSocket::set_device understands.
+/// assert_eq!(quick.config.peers.len(), 1);
+/// // The part wg-quick configures on the network interface.
+/// assert_eq!(quick.network.address[0].cidr_mask, 24);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct QuickConfig {
+    pub config: Config,
+    pub network: NetworkSettings,
+}
+
+/// The `[Interface]` keys specific to wg-quick.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct NetworkSettings {
+    /// `Address`. Addresses without a prefix length are a single address.
+    pub address: Vec<AllowedIp>,
+    /// The `DNS` entries that are IP addresses.
+    pub dns: Vec<IpAddr>,
+    /// The `DNS` entries that aren't IP addresses are search domains.
+    pub dns_search: Vec<String>,
+    /// `MTU`. wg-quick picks one automatically when this is unset.
+    pub mtu: Option<u32>,
+    /// `Table`. wg-quick uses `auto` when this is unset.
+    pub table: Option<Table>,
+    /// `SaveConfig`
+    pub save_config: bool,
+    /// `PreUp` commands, in order. These are kept verbatim.
+    pub pre_up: Vec<String>,
+    /// `PostUp` commands, in order. These are kept verbatim.
+    pub post_up: Vec<String>,
+    /// `PreDown` commands, in order. These are kept verbatim.
+    pub pre_down: Vec<String>,
+    /// `PostDown` commands, in order. These are kept verbatim.
+    pub post_down: Vec<String>,
+}
+
+/// The routing table wg-quick adds routes for allowed IPs to.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Table {
+    /// Routes aren't created at all.
+    Off,
+    /// The main table, or a dedicated table and fwmark for default routes.
+    Auto,
+    Id(u32),
+    /// A table name from `/etc/iproute2/rt_tables`.
+    Named(String),
+}
+
+impl FromStr for Table {
+    type Err = ParseConfigErrorKind;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            return Err(ParseConfigErrorKind::InvalidTable(s.to_string()));
+        }
+
+        Ok(if s.eq_ignore_ascii_case("off") {
+            Table::Off
+        } else if s.eq_ignore_ascii_case("auto") {
+            Table::Auto
+        } else if let Ok(id) = s.parse() {
+            Table::Id(id)
+        } else {
+            Table::Named(s.to_string())
+        })
+    }
+}
+
+impl fmt::Display for Table {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Table::Off => f.write_str("off"),
+            Table::Auto => f.write_str("auto"),
+            Table::Id(id) => write!(f, "{}", id),
+            Table::Named(name) => f.write_str(name),
+        }
+    }
+}
+
+impl NetworkSettings {
+    fn set_key(&mut self, key: &str, value: &str) -> Result<bool, ParseConfigErrorKind> {
+        type Error = ParseConfigErrorKind;
+
+        match key.to_ascii_lowercase().as_str() {
+            "address" => {
+                for address in value.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+                    let address = parse_allowed_ip(address)
+                        .ok_or_else(|| Error::InvalidAddress(address.to_string()))?;
+                    self.address.push(address);
+                }
+            }
+            "dns" => {
+                for dns in value.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+                    match dns.parse() {
+                        Ok(ipaddr) => self.dns.push(ipaddr),
+                        Err(_) => self.dns_search.push(dns.to_string()),
+                    }
+                }
+            }
+            "mtu" => {
+                let mtu = value
+                    .parse()
+                    .map_err(|_| Error::InvalidMtu(value.to_string()))?;
+                self.mtu = Some(mtu);
+            }
+            "table" => self.table = Some(value.parse()?),
+            "saveconfig" => {
+                self.save_config = match value {
+                    "true" => true,
+                    "false" => false,
+                    _ => return Err(Error::InvalidSaveConfig(value.to_string())),
+                };
+            }
+            "preup" => self.pre_up.push(value.to_string()),
+            "postup" => self.post_up.push(value.to_string()),
+            "predown" => self.pre_down.push(value.to_string()),
+            "postdown" => self.post_down.push(value.to_string()),
+            _ => return Ok(false),
+        }
+
+        Ok(true)
+    }
+}
+
+impl FromStr for QuickConfig {
+    type Err = ParseConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = ConfigParser::default();
+        let mut network = NetworkSettings::default();
+
+        for (i, line) in s.lines().enumerate() {
+            parser.parse_line_with(i + 1, line, |section, key, value| match section {
+                Section::Interface => network.set_key(key, value),
+                Section::Peer => Ok(false),
+            })?;
+        }
+
+        Ok(QuickConfig {
+            config: parser.finish()?,
+            network,
+        })
+    }
+}
+
+/// Formats the config the same way wg-quick saves it when `SaveConfig` is
+/// enabled.
+impl fmt::Display for QuickConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let network = &self.network;
+        writeln!(f, "[Interface]")?;
+
+        for address in &network.address {
+            writeln!(f, "Address = {}/{}", address.ipaddr, address.cidr_mask)?;
+        }
+
+        for dns in &network.dns {
+            writeln!(f, "DNS = {}", dns)?;
+        }
+
+        for dns_search in &network.dns_search {
+            writeln!(f, "DNS = {}", dns_search)?;
+        }
+
+        if let Some(mtu) = network.mtu {
+            writeln!(f, "MTU = {}", mtu)?;
+        }
+
+        if let Some(table) = &network.table {
+            writeln!(f, "Table = {}", table)?;
+        }
+
+        if network.save_config {
+            writeln!(f, "SaveConfig = true")?;
+        }
+
+        let hooks = [
+            ("PreUp", &network.pre_up),
+            ("PostUp", &network.post_up),
+            ("PreDown", &network.pre_down),
+            ("PostDown", &network.post_down),
+        ];
+        for (key, commands) in hooks.iter() {
+            for command in commands.iter() {
+                writeln!(f, "{} = {}", key, command)?;
+            }
+        }
+
+        // The rest is identical to wg showconf, minus its [Interface] header.
+        let config = self.config.to_string();
+        f.write_str(config.strip_prefix("[Interface]\n").unwrap_or(&config))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WG_QUICK: &str = "\
+[Interface]
+Address = 10.200.100.8/24
+Address = fd00::8/64
+DNS = 10.200.100.1
+DNS = example.com
+MTU = 1420
+Table = 1234
+SaveConfig = true
+PostUp = iptables -A FORWARD -i %i -j ACCEPT; iptables -t nat -A POSTROUTING -o eth0 -j MASQUERADE
+PostDown = iptables -D FORWARD -i %i -j ACCEPT
+ListenPort = 51820
+PrivateKey = oK56DE9Ue9zK76rAc8pBl6opph+1v36lm7cXXsQKrQM=
+
+[Peer]
+PublicKey = GtL7fZc/bLnqZldpVofMCD6hDjrK28SsdLxevJ+qtKU=
+AllowedIPs = 0.0.0.0/0
+Endpoint = 198.51.100.8:51820
+";
+
+    #[test]
+    fn parse_wg_quick_config() -> anyhow::Result<()> {
+        let quick: QuickConfig = WG_QUICK.parse()?;
+        let network = &quick.network;
+
+        assert_eq!(
+            network.address,
+            vec!["10.200.100.8/24".parse()?, "fd00::8/64".parse()?]
+        );
+        assert_eq!(network.dns, vec!["10.200.100.1".parse::<IpAddr>()?]);
+        assert_eq!(network.dns_search, vec!["example.com"]);
+        assert_eq!(network.mtu, Some(1420));
+        assert_eq!(network.table, Some(Table::Id(1234)));
+        assert!(network.save_config);
+        assert_eq!(
+            network.post_up,
+            vec!["iptables -A FORWARD -i %i -j ACCEPT; iptables -t nat -A POSTROUTING -o eth0 -j MASQUERADE"]
+        );
+        assert_eq!(network.post_down.len(), 1);
+        assert!(network.pre_up.is_empty());
+
+        assert_eq!(quick.config.interface.listen_port, Some(51820));
+        assert_eq!(quick.config.peers.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn display_round_trips() -> anyhow::Result<()> {
+        let quick: QuickConfig = WG_QUICK.parse()?;
+        assert_eq!(quick.to_string(), WG_QUICK);
+        Ok(())
+    }
+
+    #[test]
+    fn parse_comma_separated_dns() -> anyhow::Result<()> {
+        let quick: QuickConfig = "[Interface]\nDNS = 10.200.100.1, example.com\n".parse()?;
+        assert_eq!(quick.network.dns, vec!["10.200.100.1".parse::<IpAddr>()?]);
+        assert_eq!(quick.network.dns_search, vec!["example.com"]);
+        Ok(())
+    }
+
+    #[test]
+    fn parse_table() {
+        assert_eq!("off".parse(), Ok(Table::Off));
+        assert_eq!("Auto".parse(), Ok(Table::Auto));
+        assert_eq!("51820".parse(), Ok(Table::Id(51820)));
+        assert_eq!("main".parse(), Ok(Table::Named("main".to_string())));
+    }
+
+    #[test]
+    fn plain_config_rejects_wg_quick_keys() {
+        assert!(matches!(
+            WG_QUICK.parse::<Config>(),
+            Err(ParseConfigError {
+                line: 2,
+                kind: ParseConfigErrorKind::UnknownKey(_)
+            })
+        ));
+    }
+
+    #[test]
+    fn parse_invalid_wg_quick_keys() {
+        let error = |kind| Err(ParseConfigError { line: 2, kind });
+
+        assert_eq!(
+            "[Interface]\nAddress = 10.0.0.1/40".parse::<QuickConfig>(),
+            error(ParseConfigErrorKind::InvalidAddress("10.0.0.1/40".into()))
+        );
+        assert_eq!(
+            "[Interface]\nMTU = big".parse::<QuickConfig>(),
+            error(ParseConfigErrorKind::InvalidMtu("big".into()))
+        );
+        assert_eq!(
+            "[Interface]\nSaveConfig = yes".parse::<QuickConfig>(),
+            error(ParseConfigErrorKind::InvalidSaveConfig("yes".into()))
+        );
+        // wg-quick keys are only valid in the [Interface] section.
+        assert_eq!(
+            "[Peer]\nMTU = 1420".parse::<QuickConfig>(),
+            error(ParseConfigErrorKind::UnknownKey("MTU".into()))
+        );
+    }
+}
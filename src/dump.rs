@@ -0,0 +1,436 @@
+//! The tab-separated format printed by `wg show <interface> dump` and
+//! `wg show all dump`.
+//!
+//! The first line of an interface describes the interface itself:
+//!
+//! ```text
+//! private-key  public-key  listen-port  fwmark
+//! ```
+//!
+//! Every following line describes one of its peers:
+//!
+//! ```text
+//! public-key  preshared-key  endpoint  allowed-ips  latest-handshake  transfer-rx  transfer-tx  persistent-keepalive
+//! ```
+//!
+//! Missing values are written as `(none)`, and a disabled fwmark or persistent
+//! keepalive as `off`. `wg show all dump` prefixes every line with the
+//! interface name.
+//!
+//! ```
+//! use wireguard_uapi::dump;
+//!
+//! let text = "\
+//! wg0\tyAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk=\tHIgo9xNzJMWLKASShiTqIybxZ0U3wGLiUeJ1PKf8ykw=\t51820\toff
+//! wg0\txTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg=\t(none)\t192.95.5.67:1234\t10.192.122.3/32\t1600000000\t2148\t2200\t25
+//! ";
+//!
+//! let devices = dump::parse_devices(text).unwrap();
+//! assert_eq!(devices[0].ifname, "wg0");
+//! assert_eq!(devices[0].peers[0].rx_bytes, 2148);
+//! assert_eq!(dump::devices_to_string(&devices), text);
+//! ```
+
+use crate::get::{AllowedIp, Device, Peer};
+use crate::key::{PresharedKey, PrivateKey, PublicKey};
+use std::fmt::{self, Write};
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::time::Duration;
+use thiserror::Error;
+
+const NONE: &str = "(none)";
+const OFF: &str = "off";
+
+const DEVICE_FIELDS: usize = 4;
+const PEER_FIELDS: usize = 8;
+
+#[derive(Debug, Error, PartialEq)]
+#[error("Line {line}: {kind}")]
+pub struct ParseDumpError {
+    /// The 1-based line number the error was found on.
+    pub line: usize,
+    #[source]
+    pub kind: ParseDumpErrorKind,
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum ParseDumpErrorKind {
+    #[error("Expected 4 fields for an interface or 8 for a peer, found {0}")]
+    InvalidFieldCount(usize),
+    #[error("Peer of interface `{0}` appears before the interface")]
+    PeerBeforeInterface(String),
+
+    #[error("Invalid private key")]
+    InvalidPrivateKey,
+    #[error("Invalid public key: `{0}`")]
+    InvalidPublicKey(String),
+    #[error("Invalid preshared key")]
+    InvalidPresharedKey,
+    #[error("Invalid listen port: `{0}`")]
+    InvalidListenPort(String),
+    #[error("Invalid fwmark: `{0}`")]
+    InvalidFwmark(String),
+    #[error("Invalid endpoint: `{0}`")]
+    InvalidEndpoint(String),
+    #[error("Invalid allowed IP: `{0}`")]
+    InvalidAllowedIp(String),
+    #[error("Invalid latest handshake: `{0}`")]
+    InvalidLatestHandshake(String),
+    #[error("Invalid transfer: `{0}`")]
+    InvalidTransfer(String),
+    #[error("Invalid persistent keepalive: `{0}`")]
+    InvalidPersistentKeepalive(String),
+}
+
+/// Formats a device the same way as `wg show <interface> dump`.
+pub fn device_to_string(device: &Device) -> String {
+    let mut text = String::new();
+    // Writing to a String can't fail.
+    write_device(&mut text, device, false).unwrap();
+    text
+}
+
+/// Formats devices the same way as `wg show all dump`, with the interface name
+/// at the start of every line.
+pub fn devices_to_string(devices: &[Device]) -> String {
+    let mut text = String::new();
+    for device in devices {
+        write_device(&mut text, device, true).unwrap();
+    }
+    text
+}
+
+pub fn write_device(out: &mut impl Write, device: &Device, with_interface: bool) -> fmt::Result {
+    let prefix = |out: &mut dyn Write| match with_interface {
+        true => write!(out, "{}\t", device.ifname),
+        false => Ok(()),
+    };
+
+    prefix(out)?;
+    writeln!(
+        out,
+        "{}\t{}\t{}\t{}",
+        OrNone(device.private_key.as_ref()),
+        OrNone(device.public_key.as_ref()),
+        device.listen_port,
+        OrOff(
+            Some(device.fwmark)
+                .filter(|&fwmark| fwmark != 0)
+                .map(|fwmark| format!("{:#x}", fwmark))
+        ),
+    )?;
+
+    for peer in &device.peers {
+        prefix(out)?;
+        write!(
+            out,
+            "{}\t{}\t{}\t",
+            peer.public_key,
+            OrNone(Some(&peer.preshared_key).filter(|key| !key.is_zero())),
+            OrNone(peer.endpoint.as_ref()),
+        )?;
+        if peer.allowed_ips.is_empty() {
+            write!(out, "{}", NONE)?;
+        }
+        for (i, allowed_ip) in peer.allowed_ips.iter().enumerate() {
+            if i > 0 {
+                write!(out, ",")?;
+            }
+            write!(out, "{}/{}", allowed_ip.ipaddr, allowed_ip.cidr_mask)?;
+        }
+        writeln!(
+            out,
+            "\t{}\t{}\t{}\t{}",
+            peer.last_handshake_time.as_secs(),
+            peer.rx_bytes,
+            peer.tx_bytes,
+            OrOff(Some(peer.persistent_keepalive_interval).filter(|&interval| interval != 0)),
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Parses the output of `wg show <interface> dump`. The dump doesn't contain
+/// the interface name, so it's passed in. The `ifindex` isn't part of the
+/// dump either and is left as 0.
+pub fn parse_device(s: &str, ifname: &str) -> Result<Device, ParseDumpError> {
+    let mut device: Option<Device> = None;
+
+    for (i, line) in lines(s) {
+        let fields: Vec<_> = line.split('\t').collect();
+        match &mut device {
+            None => device = Some(parse_device_line(i, ifname, &fields)?),
+            Some(device) => device.peers.push(parse_peer_line(i, &fields)?),
+        }
+    }
+
+    Ok(device.unwrap_or_else(|| empty_device(ifname)))
+}
+
+/// Parses the output of `wg show all dump`.
+pub fn parse_devices(s: &str) -> Result<Vec<Device>, ParseDumpError> {
+    let mut devices: Vec<Device> = vec![];
+
+    for (i, line) in lines(s) {
+        let (ifname, rest) = line.split_once('\t').ok_or(ParseDumpError {
+            line: i,
+            kind: ParseDumpErrorKind::InvalidFieldCount(1),
+        })?;
+        let fields: Vec<_> = rest.split('\t').collect();
+
+        match devices.last_mut() {
+            Some(device) if device.ifname == ifname && fields.len() != DEVICE_FIELDS => {
+                device.peers.push(parse_peer_line(i, &fields)?)
+            }
+            _ if fields.len() == PEER_FIELDS => {
+                return Err(ParseDumpError {
+                    line: i,
+                    kind: ParseDumpErrorKind::PeerBeforeInterface(ifname.to_string()),
+                })
+            }
+            _ => devices.push(parse_device_line(i, ifname, &fields)?),
+        }
+    }
+
+    Ok(devices)
+}
+
+fn lines(s: &str) -> impl Iterator<Item = (usize, &str)> {
+    s.lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line))
+        .filter(|(_, line)| !line.is_empty())
+}
+
+fn empty_device(ifname: &str) -> Device {
+    Device {
+        ifindex: 0,
+        ifname: ifname.to_string(),
+        private_key: None,
+        public_key: None,
+        listen_port: 0,
+        fwmark: 0,
+        peers: vec![],
+    }
+}
+
+fn parse_device_line(line: usize, ifname: &str, fields: &[&str]) -> Result<Device, ParseDumpError> {
+    type Error = ParseDumpErrorKind;
+    let with_line = |kind| ParseDumpError { line, kind };
+
+    if fields.len() != DEVICE_FIELDS {
+        return Err(with_line(Error::InvalidFieldCount(fields.len())));
+    }
+
+    let private_key = or_none(fields[0], PrivateKey::from_base64)
+        .map_err(|_| with_line(Error::InvalidPrivateKey))?;
+    let public_key = or_none(fields[1], PublicKey::from_base64)
+        .map_err(|_| with_line(Error::InvalidPublicKey(fields[1].to_string())))?;
+    let listen_port = fields[2]
+        .parse()
+        .map_err(|_| with_line(Error::InvalidListenPort(fields[2].to_string())))?;
+    let fwmark = off_or(fields[3], |fwmark| {
+        fwmark
+            .strip_prefix("0x")
+            .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+    })
+    .ok_or_else(|| with_line(Error::InvalidFwmark(fields[3].to_string())))?;
+
+    Ok(Device {
+        private_key,
+        public_key,
+        listen_port,
+        fwmark,
+        ..empty_device(ifname)
+    })
+}
+
+fn parse_peer_line(line: usize, fields: &[&str]) -> Result<Peer, ParseDumpError> {
+    type Error = ParseDumpErrorKind;
+    let with_line = |kind| ParseDumpError { line, kind };
+
+    if fields.len() != PEER_FIELDS {
+        return Err(with_line(Error::InvalidFieldCount(fields.len())));
+    }
+
+    let public_key = PublicKey::from_base64(fields[0])
+        .map_err(|_| with_line(Error::InvalidPublicKey(fields[0].to_string())))?;
+    let preshared_key = or_none(fields[1], PresharedKey::from_base64)
+        .map_err(|_| with_line(Error::InvalidPresharedKey))?
+        .unwrap_or_else(PresharedKey::zero);
+    let endpoint = or_none(fields[2], SocketAddr::from_str)
+        .map_err(|_| with_line(Error::InvalidEndpoint(fields[2].to_string())))?;
+    let allowed_ips = match fields[3] {
+        NONE => vec![],
+        allowed_ips => allowed_ips
+            .split(',')
+            .map(|allowed_ip| {
+                AllowedIp::from_str(allowed_ip)
+                    .map_err(|_| with_line(Error::InvalidAllowedIp(allowed_ip.to_string())))
+            })
+            .collect::<Result<_, _>>()?,
+    };
+    let last_handshake_time = fields[4]
+        .parse()
+        .map(Duration::from_secs)
+        .map_err(|_| with_line(Error::InvalidLatestHandshake(fields[4].to_string())))?;
+    let transfer = |field: &str| {
+        field
+            .parse()
+            .map_err(|_| with_line(Error::InvalidTransfer(field.to_string())))
+    };
+    let persistent_keepalive_interval = off_or(fields[7], |interval| interval.parse().ok())
+        .ok_or_else(|| with_line(Error::InvalidPersistentKeepalive(fields[7].to_string())))?;
+
+    Ok(Peer {
+        public_key,
+        preshared_key,
+        endpoint,
+        persistent_keepalive_interval,
+        last_handshake_time,
+        rx_bytes: transfer(fields[5])?,
+        tx_bytes: transfer(fields[6])?,
+        allowed_ips,
+        // The only protocol version there is.
+        protocol_version: 1,
+    })
+}
+
+fn or_none<T, E>(field: &str, parse: impl FnOnce(&str) -> Result<T, E>) -> Result<Option<T>, E> {
+    match field {
+        NONE => Ok(None),
+        field => parse(field).map(Some),
+    }
+}
+
+fn off_or<T: Default>(field: &str, parse: impl FnOnce(&str) -> Option<T>) -> Option<T> {
+    match field {
+        OFF => Some(T::default()),
+        field => parse(field),
+    }
+}
+
+struct OrNone<T>(Option<T>);
+
+impl<T: fmt::Display> fmt::Display for OrNone<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.0 {
+            Some(value) => value.fmt(f),
+            None => f.write_str(NONE),
+        }
+    }
+}
+
+struct OrOff<T>(Option<T>);
+
+impl<T: fmt::Display> fmt::Display for OrOff<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.0 {
+            Some(value) => value.fmt(f),
+            None => f.write_str(OFF),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DUMP: &str = "\
+yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk=\tHIgo9xNzJMWLKASShiTqIybxZ0U3wGLiUeJ1PKf8ykw=\t51820\t0x1234
+TrMvSoP4jYQlY6RIzBgbssQqY3vxI2Pi+y71lOWWXX0=\t(none)\t(none)\t(none)\t0\t0\t0\toff
+xTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg=\t/UwcSPg38hW/D9Y3tcS1FOV0K1wuURMbS0sesJEP5ak=\t[2607:5300:60:6b0::c05f:543]:2468\t10.192.122.3/32,fd00::/64\t1600000000\t2148\t5000000\t25
+";
+
+    #[test]
+    fn parse_device_round_trips() -> anyhow::Result<()> {
+        let device = parse_device(DUMP, "wg0")?;
+
+        assert_eq!(device.ifname, "wg0");
+        assert_eq!(
+            device.public_key,
+            Some("HIgo9xNzJMWLKASShiTqIybxZ0U3wGLiUeJ1PKf8ykw=".parse()?)
+        );
+        assert_eq!(device.fwmark, 0x1234);
+        assert_eq!(device.peers.len(), 2);
+
+        let unused = &device.peers[0];
+        assert!(unused.preshared_key.is_zero());
+        assert_eq!(unused.endpoint, None);
+        assert_eq!(unused.allowed_ips, vec![]);
+        assert_eq!(unused.persistent_keepalive_interval, 0);
+
+        let peer = &device.peers[1];
+        assert_eq!(
+            peer.endpoint,
+            Some("[2607:5300:60:6b0::c05f:543]:2468".parse()?)
+        );
+        assert_eq!(peer.allowed_ips[1], "fd00::/64".parse()?);
+        assert_eq!(peer.last_handshake_time, Duration::from_secs(1_600_000_000));
+        assert_eq!((peer.rx_bytes, peer.tx_bytes), (2148, 5_000_000));
+        assert_eq!(peer.persistent_keepalive_interval, 25);
+
+        assert_eq!(device_to_string(&device), DUMP);
+        Ok(())
+    }
+
+    #[test]
+    fn parse_devices_round_trips() -> anyhow::Result<()> {
+        let mut dump = String::new();
+        for ifname in &["wg0", "wg1"] {
+            for line in DUMP.lines() {
+                dump.push_str(&format!("{}\t{}\n", ifname, line));
+            }
+        }
+        dump.push_str("wg2\t(none)\t(none)\t0\toff\n");
+
+        let devices = parse_devices(&dump)?;
+        assert_eq!(devices.len(), 3);
+        assert_eq!(devices[1].ifname, "wg1");
+        assert_eq!(devices[1].peers.len(), 2);
+        assert_eq!(devices[2].private_key, None);
+        assert_eq!(devices[2].peers, vec![]);
+
+        assert_eq!(devices_to_string(&devices), dump);
+        Ok(())
+    }
+
+    #[test]
+    fn parse_invalid_dumps() {
+        let peer = DUMP.lines().nth(1).unwrap();
+
+        assert_eq!(
+            parse_devices(&format!("wg0\t{}\n", peer)),
+            Err(ParseDumpError {
+                line: 1,
+                kind: ParseDumpErrorKind::PeerBeforeInterface("wg0".to_string()),
+            })
+        );
+        assert_eq!(
+            parse_device("(none)\t(none)\t0\n", "wg0"),
+            Err(ParseDumpError {
+                line: 1,
+                kind: ParseDumpErrorKind::InvalidFieldCount(3),
+            })
+        );
+        assert_eq!(
+            parse_device(
+                &format!("(none)\t(none)\t0\toff\n{}", peer.replace("off", "never")),
+                "wg0"
+            ),
+            Err(ParseDumpError {
+                line: 2,
+                kind: ParseDumpErrorKind::InvalidPersistentKeepalive("never".to_string()),
+            })
+        );
+        assert_eq!(
+            parse_device("(none)\t(none)\t0\t4660\n", "wg0"),
+            Err(ParseDumpError {
+                line: 1,
+                kind: ParseDumpErrorKind::InvalidFwmark("4660".to_string()),
+            })
+        );
+    }
+}
@@ -1,16 +1,19 @@
+use crate::key::{PresharedKey, PrivateKey, PublicKey};
 use derive_builder::Builder;
 use std::net::{IpAddr, SocketAddr};
 use std::str::FromStr;
 use std::time::Duration;
 
-#[derive(Builder, Debug, PartialEq)]
+#[derive(Builder, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Device {
     pub ifindex: u32,
     pub ifname: String,
     #[builder(default)]
-    pub private_key: Option<[u8; 32]>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing, default))]
+    pub private_key: Option<PrivateKey>,
     #[builder(default)]
-    pub public_key: Option<[u8; 32]>,
+    pub public_key: Option<PublicKey>,
     pub listen_port: u16,
     pub fwmark: u32,
     #[builder(default)]
@@ -18,15 +21,22 @@ pub struct Device {
 }
 
 #[derive(Builder, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Peer {
     // The public_key and allowed_ips fields are public to
     // make peer coalescing easier.
     #[builder(field(public))]
-    pub public_key: [u8; 32],
-    pub preshared_key: [u8; 32],
+    pub public_key: PublicKey,
+    /// All zeros if the peer has no preshared key.
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "crate::serialize::optional_preshared_key")
+    )]
+    pub preshared_key: PresharedKey,
     #[builder(default)]
     pub endpoint: Option<SocketAddr>,
     pub persistent_keepalive_interval: u16,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialize::unix_timestamp"))]
     pub last_handshake_time: Duration,
     pub rx_bytes: u64,
     pub tx_bytes: u64,
@@ -35,6 +45,14 @@ pub struct Peer {
     pub protocol_version: u32,
 }
 
+/// A part of a device received while streaming it rather than retrieving it whole. The device
+/// comes first with its `peers` left empty, followed by each of its peers.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DeviceEntry {
+    Device(Device),
+    Peer(Peer),
+}
+
 #[derive(Builder, Clone, Debug, PartialEq)]
 pub struct AllowedIp {
     pub family: u16,
@@ -0,0 +1,375 @@
+//! Strongly typed WireGuard keys.
+//!
+//! WireGuard uses three kinds of 32 byte keys. Keeping them as distinct types
+//! prevents a private key from accidentally being sent where a public key was
+//! expected. All three can be parsed from and formatted as base64 (the format
+//! used by the `wg` command line tool) or lowercase hex (the format used by the
+//! cross-platform userspace protocol).
+//!
+//! New keys can be created without a WireGuard interface or kernel module,
+//! similar to `wg genkey`, `wg pubkey` and `wg genpsk`.
+//!
+//! ```
+//! use wireguard_uapi::key::{PresharedKey, PrivateKey};
+//!
+//! let private_key = PrivateKey::generate();
+//! let public_key = private_key.public_key();
+//! let preshared_key = PresharedKey::generate();
+//! ```
+//!
+//! ```
+//! use wireguard_uapi::key::PublicKey;
+//!
+//! let key: PublicKey = "HIgo9xNzJMWLKASShiTqIybxZ0U3wGLiUeJ1PKf8ykw=".parse().unwrap();
+//! assert_eq!(key.to_string(), "HIgo9xNzJMWLKASShiTqIybxZ0U3wGLiUeJ1PKf8ykw=");
+//! assert_eq!(
+//!     format!("{:x}", key),
+//!     "1c8828f7137324c58b2804928624ea2326f1674537c062e251e2753ca7fcca4c"
+//! );
+//! ```
+
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+use subtle::ConstantTimeEq;
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+pub const KEY_LEN: usize = 32;
+
+// Hex encoded keys always have this length. Anything else is decoded as base64.
+const HEX_KEY_LEN: usize = 2 * KEY_LEN;
+
+// Length of a base64 encoded key, including the trailing padding character.
+const BASE64_KEY_LEN: usize = 44;
+
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum ParseKeyError {
+    #[error("Keys must be {} bytes. Found {0}.", KEY_LEN)]
+    InvalidLength(usize),
+    #[error("Key is not valid base64")]
+    InvalidBase64,
+    #[error("Key is not valid hex")]
+    InvalidHex,
+}
+
+macro_rules! impl_key {
+    ($(#[$meta:meta])* $name:ident) => {
+        $(#[$meta])*
+        pub struct $name([u8; KEY_LEN]);
+
+        impl $name {
+            pub const fn from_bytes(bytes: [u8; KEY_LEN]) -> Self {
+                Self(bytes)
+            }
+
+            /// The all zero key. When used in a set operation, this
+            /// indicates that the key should be removed.
+            pub const fn zero() -> Self {
+                Self([0u8; KEY_LEN])
+            }
+
+            pub fn is_zero(&self) -> bool {
+                self.ct_eq(&Self::zero()).into()
+            }
+
+            pub fn as_bytes(&self) -> &[u8; KEY_LEN] {
+                &self.0
+            }
+
+            pub fn from_base64(s: &str) -> Result<Self, ParseKeyError> {
+                let buf = base64::decode(s).map_err(|_| ParseKeyError::InvalidBase64)?;
+                Self::from_slice(&Zeroizing::new(buf))
+            }
+
+            pub fn from_hex(s: &str) -> Result<Self, ParseKeyError> {
+                let buf = hex::decode(s).map_err(|_| ParseKeyError::InvalidHex)?;
+                Self::from_slice(&Zeroizing::new(buf))
+            }
+
+            pub fn from_slice(buf: &[u8]) -> Result<Self, ParseKeyError> {
+                if buf.len() != KEY_LEN {
+                    return Err(ParseKeyError::InvalidLength(buf.len()));
+                }
+
+                let mut key = Self::zero();
+                key.0.copy_from_slice(buf);
+                Ok(key)
+            }
+
+            /// The returned string is not wiped when dropped. Prefer formatting
+            /// the key with `Display` to avoid the extra copy of secret keys.
+            pub fn to_base64(&self) -> String {
+                base64::encode(&self.0)
+            }
+
+            /// The returned string is not wiped when dropped. Prefer formatting
+            /// the key with `{:x}` to avoid the extra copy of secret keys.
+            pub fn to_hex(&self) -> String {
+                hex::encode(&self.0)
+            }
+        }
+
+        impl From<[u8; KEY_LEN]> for $name {
+            fn from(bytes: [u8; KEY_LEN]) -> Self {
+                Self(bytes)
+            }
+        }
+
+        impl AsRef<[u8]> for $name {
+            fn as_ref(&self) -> &[u8] {
+                &self.0
+            }
+        }
+
+        impl ConstantTimeEq for $name {
+            fn ct_eq(&self, other: &Self) -> subtle::Choice {
+                self.0.ct_eq(&other.0)
+            }
+        }
+
+        // Comparisons run in constant time so that checking a secret key
+        // against a known value doesn't leak how many bytes matched.
+        impl PartialEq for $name {
+            fn eq(&self, other: &Self) -> bool {
+                self.ct_eq(other).into()
+            }
+        }
+
+        impl Eq for $name {}
+
+        /// Formats the key as base64.
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                // Encode on the stack so no copy of the key outlives this call.
+                let mut buf = Zeroizing::new([0u8; BASE64_KEY_LEN]);
+                let len = base64::encode_config_slice(&self.0, base64::STANDARD, &mut buf[..]);
+                f.write_str(std::str::from_utf8(&buf[..len]).map_err(|_| fmt::Error)?)
+            }
+        }
+
+        /// Formats the key as lowercase hex.
+        impl fmt::LowerHex for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                for byte in &self.0 {
+                    write!(f, "{:02x}", byte)?;
+                }
+                Ok(())
+            }
+        }
+
+        /// Parses either a base64 or hex encoded key.
+        impl FromStr for $name {
+            type Err = ParseKeyError;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                if s.len() == HEX_KEY_LEN {
+                    Self::from_hex(s)
+                } else {
+                    Self::from_base64(s)
+                }
+            }
+        }
+    };
+}
+
+impl_key!(
+    /// The private key of a WireGuard interface.
+    #[derive(Clone)]
+    PrivateKey
+);
+
+impl_key!(
+    /// The public key of a WireGuard interface or peer.
+    #[derive(Clone, Copy)]
+    PublicKey
+);
+
+impl_key!(
+    /// An optional symmetric key shared between two peers. An all zero
+    /// preshared key means none is in use.
+    #[derive(Clone)]
+    PresharedKey
+);
+
+fn random_key_bytes() -> [u8; KEY_LEN] {
+    let mut bytes = [0u8; KEY_LEN];
+    getrandom::getrandom(&mut bytes)
+        .expect("Unable to read from the system random number generator");
+    bytes
+}
+
+impl PrivateKey {
+    /// Generates a new Curve25519 private key from the system random number
+    /// generator. This is the equivalent of `wg genkey`.
+    pub fn generate() -> Self {
+        let mut key = Self(random_key_bytes());
+
+        // Clamp the key the same way the wg tool does.
+        // https://git.zx2c4.com/wireguard-tools/tree/src/curve25519.h
+        key.0[0] &= 248;
+        key.0[31] &= 127;
+        key.0[31] |= 64;
+
+        key
+    }
+
+    /// Derives the X25519 public key of this private key. This is the
+    /// equivalent of `wg pubkey`.
+    pub fn public_key(&self) -> PublicKey {
+        let secret = x25519_dalek::StaticSecret::from(self.0);
+        PublicKey(x25519_dalek::PublicKey::from(&secret).to_bytes())
+    }
+}
+
+impl From<&PrivateKey> for PublicKey {
+    fn from(private_key: &PrivateKey) -> Self {
+        private_key.public_key()
+    }
+}
+
+impl PresharedKey {
+    /// Generates a new random preshared key. This is the equivalent of
+    /// `wg genpsk`.
+    pub fn generate() -> Self {
+        Self(random_key_bytes())
+    }
+}
+
+// Hash is implemented by hand since PartialEq is. Only public keys are hashable
+// as they're commonly used to look up peers.
+impl Hash for PublicKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state);
+    }
+}
+
+// Secret keys are wiped from memory when dropped and redacted from debug output
+// so they don't end up in logs.
+macro_rules! impl_secret_key {
+    ($name:ident) => {
+        impl Zeroize for $name {
+            fn zeroize(&mut self) {
+                self.0.zeroize();
+            }
+        }
+
+        impl Drop for $name {
+            fn drop(&mut self) {
+                self.zeroize();
+            }
+        }
+
+        impl ZeroizeOnDrop for $name {}
+
+        impl fmt::Debug for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(concat!(stringify!($name), "(<redacted>)"))
+            }
+        }
+    };
+}
+
+impl_secret_key!(PrivateKey);
+impl_secret_key!(PresharedKey);
+
+impl fmt::Debug for PublicKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("PublicKey").field(&self.to_base64()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE64: &str = "yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk=";
+    const HEX: &str = "c809f3e5317e9575c9b5ed78b638b7ce530dabe85ddab614220241801ddf0669";
+
+    #[test]
+    fn parse_base64_and_hex() -> anyhow::Result<()> {
+        let from_base64: PrivateKey = BASE64.parse()?;
+        let from_hex: PrivateKey = HEX.parse()?;
+        assert_eq!(from_base64, from_hex);
+        assert_eq!(from_base64.to_base64(), BASE64);
+        assert_eq!(from_base64.to_hex(), HEX);
+        assert_eq!(format!("{:x}", from_base64), HEX);
+        assert_eq!(from_base64.to_string(), BASE64);
+        Ok(())
+    }
+
+    #[test]
+    fn parse_invalid_keys() {
+        assert_eq!(
+            "".parse::<PublicKey>(),
+            Err(ParseKeyError::InvalidLength(0))
+        );
+        assert_eq!(
+            "not base64!".parse::<PublicKey>(),
+            Err(ParseKeyError::InvalidBase64)
+        );
+        assert_eq!(
+            "zz".repeat(32).parse::<PublicKey>(),
+            Err(ParseKeyError::InvalidHex)
+        );
+        assert_eq!(
+            "AAAA".parse::<PublicKey>(),
+            Err(ParseKeyError::InvalidLength(3))
+        );
+    }
+
+    #[test]
+    fn debug_redacts_secrets() -> anyhow::Result<()> {
+        let private_key: PrivateKey = BASE64.parse()?;
+        let preshared_key: PresharedKey = BASE64.parse()?;
+        let public_key: PublicKey = BASE64.parse()?;
+
+        assert_eq!(format!("{:?}", private_key), "PrivateKey(<redacted>)");
+        assert_eq!(format!("{:?}", preshared_key), "PresharedKey(<redacted>)");
+        assert_eq!(
+            format!("{:?}", public_key),
+            format!("PublicKey({:?})", BASE64)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn derive_public_key() -> anyhow::Result<()> {
+        // The key pair from the configuration example in "man wg".
+        let private_key: PrivateKey = BASE64.parse()?;
+        let public_key: PublicKey = "HIgo9xNzJMWLKASShiTqIybxZ0U3wGLiUeJ1PKf8ykw=".parse()?;
+        assert_eq!(private_key.public_key(), public_key);
+        assert_eq!(PublicKey::from(&private_key), public_key);
+        Ok(())
+    }
+
+    #[test]
+    fn generate_keys() {
+        let private_key = PrivateKey::generate();
+        let bytes = private_key.as_bytes();
+        assert_eq!(bytes[0] & 7, 0);
+        assert_eq!(bytes[31] & 128, 0);
+        assert_eq!(bytes[31] & 64, 64);
+        assert_ne!(private_key, PrivateKey::generate());
+
+        assert_ne!(PresharedKey::generate(), PresharedKey::generate());
+        assert!(!PresharedKey::generate().is_zero());
+    }
+
+    #[test]
+    fn zeroize_secret_keys() -> anyhow::Result<()> {
+        let mut private_key: PrivateKey = BASE64.parse()?;
+        private_key.zeroize();
+        assert_eq!(private_key.as_bytes(), &[0u8; KEY_LEN]);
+
+        let mut preshared_key: PresharedKey = BASE64.parse()?;
+        preshared_key.zeroize();
+        assert!(preshared_key.is_zero());
+        Ok(())
+    }
+
+    #[test]
+    fn zero_key() {
+        assert!(PresharedKey::zero().is_zero());
+        assert!(!PresharedKey::from_bytes([1u8; KEY_LEN]).is_zero());
+    }
+}
@@ -1,9 +1,23 @@
 #[cfg(target_os = "linux")]
 pub mod linux;
 #[cfg(target_os = "linux")]
-pub use linux::{err, set, DeviceInterface, RouteSocket, WgSocket};
+pub use linux::{
+    address, err, link, netns, route, set, DeviceInterface, LinkEventSocket, RouteSocket, WgSocket,
+};
+#[cfg(all(target_os = "linux", feature = "tokio"))]
+pub use linux::{AsyncLinkEventSocket, AsyncRouteSocket, AsyncWgSocket};
 
+pub mod api;
+pub mod config;
+pub mod dump;
 pub mod get;
+pub mod key;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+#[cfg(feature = "serde")]
+pub mod serialize;
+pub mod stats;
+pub use key::{PresharedKey, PrivateKey, PublicKey};
 
 #[cfg(feature = "xplatform")]
 pub mod xplatform;
@@ -0,0 +1,38 @@
+use crate::get::ParseAllowedIpError;
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+/// An IP address assigned to a device interface along with the prefix length of its subnet.
+/// Adding one is the equivalent of:
+///
+/// ```sh
+///  sudo ip address add 10.24.0.1/16 dev wgtest0
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Address {
+    pub ipaddr: IpAddr,
+    pub prefix_len: u8,
+}
+
+impl Address {
+    pub fn new(ipaddr: IpAddr, prefix_len: u8) -> Self {
+        Self { ipaddr, prefix_len }
+    }
+}
+
+/// Parses the same `10.24.0.1/16` notation as allowed IPs, keeping the host bits.
+impl FromStr for Address {
+    type Err = ParseAllowedIpError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let allowed_ip: crate::get::AllowedIp = s.parse()?;
+        Ok(Self::new(allowed_ip.ipaddr, allowed_ip.cidr_mask))
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.ipaddr, self.prefix_len)
+    }
+}
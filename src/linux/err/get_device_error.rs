@@ -1,4 +1,4 @@
-use super::ParseDeviceError;
+use super::{KernelError, ParseDeviceError};
 use neli::err::{NlError, SerError};
 use thiserror::Error;
 
@@ -7,6 +7,9 @@ pub enum GetDeviceError {
     #[error(transparent)]
     NlError(NlError),
 
+    #[error(transparent)]
+    KernelError(KernelError),
+
     #[error(transparent)]
     NlSerError(SerError),
 
@@ -39,3 +42,9 @@ impl From<ParseDeviceError> for GetDeviceError {
         GetDeviceError::ParseDeviceError(error)
     }
 }
+
+impl From<KernelError> for GetDeviceError {
+    fn from(error: KernelError) -> Self {
+        GetDeviceError::KernelError(error)
+    }
+}
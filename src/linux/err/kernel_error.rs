@@ -0,0 +1,43 @@
+use std::fmt;
+use std::io;
+
+/// The error the kernel replied to a netlink request with.
+#[derive(Clone, Debug, PartialEq)]
+pub struct KernelError {
+    /// A positive errno value, such as `libc::ENODEV` for a device that doesn't exist.
+    pub errno: i32,
+    /// The extended acknowledgement message explaining the error, if the kernel gave one.
+    pub message: Option<String>,
+}
+
+impl KernelError {
+    pub fn kind(&self) -> io::ErrorKind {
+        io::Error::from(self.clone()).kind()
+    }
+}
+
+impl fmt::Display for KernelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", io::Error::from_raw_os_error(self.errno))?;
+        if let Some(message) = &self.message {
+            write!(f, ": {}", message)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for KernelError {}
+
+/// For errors that are reported as a neli error, such as while connecting.
+impl From<KernelError> for neli::err::NlError {
+    fn from(error: KernelError) -> Self {
+        neli::err::NlError::Msg(error.to_string())
+    }
+}
+
+/// Keeps the errno but drops the message.
+impl From<KernelError> for io::Error {
+    fn from(error: KernelError) -> Self {
+        io::Error::from_raw_os_error(error.errno)
+    }
+}
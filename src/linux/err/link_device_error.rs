@@ -1,3 +1,4 @@
+use super::KernelError;
 use neli::err::{NlError, SerError};
 use thiserror::Error;
 
@@ -6,6 +7,9 @@ pub enum LinkDeviceError {
     #[error(transparent)]
     NlError(NlError),
 
+    #[error(transparent)]
+    KernelError(KernelError),
+
     #[error(transparent)]
     NlSerError(SerError),
 
@@ -35,3 +39,9 @@ impl From<std::io::Error> for LinkDeviceError {
         LinkDeviceError::NlError(error.into())
     }
 }
+
+impl From<KernelError> for LinkDeviceError {
+    fn from(error: KernelError) -> Self {
+        LinkDeviceError::KernelError(error)
+    }
+}
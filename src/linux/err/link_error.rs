@@ -0,0 +1,51 @@
+use super::{KernelError, ParseAttributeError};
+use neli::err::{DeError, NlError, SerError};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum LinkError {
+    #[error(transparent)]
+    NlError(NlError),
+
+    #[error(transparent)]
+    KernelError(KernelError),
+
+    #[error(transparent)]
+    NlDeError(DeError),
+
+    #[error(transparent)]
+    NlSerError(SerError),
+
+    #[error(transparent)]
+    ParseAttributeError(ParseAttributeError),
+}
+
+impl From<NlError> for LinkError {
+    fn from(error: NlError) -> Self {
+        Self::NlError(error)
+    }
+}
+
+impl From<DeError> for LinkError {
+    fn from(error: DeError) -> Self {
+        Self::NlDeError(error)
+    }
+}
+
+impl From<SerError> for LinkError {
+    fn from(error: SerError) -> Self {
+        Self::NlSerError(error)
+    }
+}
+
+impl From<ParseAttributeError> for LinkError {
+    fn from(error: ParseAttributeError) -> Self {
+        Self::ParseAttributeError(error)
+    }
+}
+
+impl From<KernelError> for LinkError {
+    fn from(error: KernelError) -> Self {
+        Self::KernelError(error)
+    }
+}
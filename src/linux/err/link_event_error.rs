@@ -0,0 +1,57 @@
+use super::{ConnectError, ListDevicesError, ParseAttributeError};
+use neli::err::{DeError, NlError};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum LinkEventError {
+    #[error(transparent)]
+    NlError(NlError),
+
+    #[error(transparent)]
+    NlDeError(DeError),
+
+    #[error(transparent)]
+    ParseAttributeError(ParseAttributeError),
+
+    #[error(transparent)]
+    ConnectError(ConnectError),
+
+    #[error(transparent)]
+    ListDevicesError(ListDevicesError),
+}
+
+impl From<NlError> for LinkEventError {
+    fn from(error: NlError) -> Self {
+        Self::NlError(error)
+    }
+}
+
+impl From<DeError> for LinkEventError {
+    fn from(error: DeError) -> Self {
+        Self::NlDeError(error)
+    }
+}
+
+impl From<ParseAttributeError> for LinkEventError {
+    fn from(error: ParseAttributeError) -> Self {
+        Self::ParseAttributeError(error)
+    }
+}
+
+impl From<ConnectError> for LinkEventError {
+    fn from(error: ConnectError) -> Self {
+        Self::ConnectError(error)
+    }
+}
+
+impl From<ListDevicesError> for LinkEventError {
+    fn from(error: ListDevicesError) -> Self {
+        Self::ListDevicesError(error)
+    }
+}
+
+impl From<std::io::Error> for LinkEventError {
+    fn from(error: std::io::Error) -> Self {
+        Self::NlError(crate::linux::socket::io_error(error))
+    }
+}
@@ -1,4 +1,4 @@
-use super::ParseAttributeError;
+use super::{KernelError, ParseAttributeError};
 use neli::err::{DeError, NlError, SerError};
 use thiserror::Error;
 
@@ -7,6 +7,9 @@ pub enum ListDevicesError {
     #[error(transparent)]
     NlError(NlError),
 
+    #[error(transparent)]
+    KernelError(KernelError),
+
     #[error(transparent)]
     NlDeError(DeError),
 
@@ -16,7 +19,6 @@ pub enum ListDevicesError {
     #[error(transparent)]
     ParseAttributeError(ParseAttributeError),
 
-    // TODO: Print netlink error message when neli exposes it.
     #[error("Unknown netlink error while reading devices.")]
     Unknown,
 }
@@ -44,3 +46,9 @@ impl From<ParseAttributeError> for ListDevicesError {
         Self::ParseAttributeError(error)
     }
 }
+
+impl From<KernelError> for ListDevicesError {
+    fn from(error: KernelError) -> Self {
+        Self::KernelError(error)
+    }
+}
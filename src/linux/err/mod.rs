@@ -1,15 +1,30 @@
+mod address_error;
+pub use address_error::AddressError;
+
 mod connect_error;
 pub use connect_error::ConnectError;
 
 mod get_device_error;
 pub use get_device_error::GetDeviceError;
 
+mod kernel_error;
+pub use kernel_error::KernelError;
+
+mod link_event_error;
+pub use link_event_error::LinkEventError;
+
+mod link_error;
+pub use link_error::LinkError;
+
 mod link_device_error;
 pub use link_device_error::LinkDeviceError;
 
 mod list_devices_error;
 pub use list_devices_error::ListDevicesError;
 
+mod route_error;
+pub use route_error::RouteError;
+
 mod set_device_error;
 pub use set_device_error::SetDeviceError;
 
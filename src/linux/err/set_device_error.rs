@@ -1,3 +1,4 @@
+use super::KernelError;
 use neli::err::{NlError, SerError};
 use thiserror::Error;
 
@@ -6,6 +7,9 @@ pub enum SetDeviceError {
     #[error(transparent)]
     NlError(NlError),
 
+    #[error(transparent)]
+    KernelError(KernelError),
+
     #[error(transparent)]
     NlSerError(SerError),
 }
@@ -21,3 +25,9 @@ impl From<SerError> for SetDeviceError {
         SetDeviceError::NlSerError(error)
     }
 }
+
+impl From<KernelError> for SetDeviceError {
+    fn from(error: KernelError) -> Self {
+        SetDeviceError::KernelError(error)
+    }
+}
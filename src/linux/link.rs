@@ -0,0 +1,80 @@
+/// The state of a device interface as reported by
+/// [`RouteSocket::get_link`](crate::RouteSocket::get_link) and
+/// [`RouteSocket::list_devices`](crate::RouteSocket::list_devices).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Link {
+    pub ifindex: u32,
+    pub ifname: String,
+    /// The `IFF_*` flags of the interface. See [`Link::is_up`].
+    pub flags: u32,
+    pub mtu: u32,
+    pub txqueuelen: u32,
+    pub operstate: OperState,
+    /// The ifindex of the device this interface is enslaved to, such as a VRF.
+    pub master: Option<u32>,
+    /// The ID the link's own network namespace assigned to the namespace its UDP socket lives in,
+    /// when the two differ.
+    pub link_netnsid: Option<i32>,
+    pub stats: Option<LinkStats>,
+}
+
+impl Link {
+    /// Whether the interface was administratively brought up, like with `ip link set up`.
+    pub fn is_up(&self) -> bool {
+        self.flags & libc::IFF_UP as u32 != 0
+    }
+}
+
+/// A change to a WireGuard device interface, received through
+/// [`LinkEventSocket`](crate::LinkEventSocket).
+#[derive(Clone, Debug, PartialEq)]
+pub enum LinkEvent {
+    Added(Link),
+    /// The link's state, name or settings changed. Reported for every change, including ones
+    /// like traffic counters that aren't interesting to most.
+    Changed(Link),
+    /// The link was deleted or moved to another network namespace.
+    Removed(Link),
+}
+
+/// Traffic counters of an interface, the first fields of `struct rtnl_link_stats64`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LinkStats {
+    pub rx_packets: u64,
+    pub tx_packets: u64,
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+    pub rx_errors: u64,
+    pub tx_errors: u64,
+    pub rx_dropped: u64,
+    pub tx_dropped: u64,
+    pub multicast: u64,
+    pub collisions: u64,
+}
+
+/// The operational state of an interface as defined in RFC 2863. WireGuard interfaces report
+/// `Unknown` while they're up since they have no carrier.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OperState {
+    Unknown,
+    NotPresent,
+    Down,
+    LowerLayerDown,
+    Testing,
+    Dormant,
+    Up,
+}
+
+impl From<u8> for OperState {
+    fn from(operstate: u8) -> Self {
+        match operstate {
+            1 => OperState::NotPresent,
+            2 => OperState::Down,
+            3 => OperState::LowerLayerDown,
+            4 => OperState::Testing,
+            5 => OperState::Dormant,
+            6 => OperState::Up,
+            _ => OperState::Unknown,
+        }
+    }
+}
@@ -1,10 +1,16 @@
+pub mod address;
 mod attr;
 mod cmd;
 mod consts;
 pub mod err;
 mod interface;
+pub mod link;
+pub mod netns;
+pub mod route;
 pub mod set;
-mod socket;
+pub(crate) mod socket;
 
 pub use interface::DeviceInterface;
-pub use socket::{RouteSocket, WgSocket};
+#[cfg(feature = "tokio")]
+pub use socket::{AsyncLinkEventSocket, AsyncRouteSocket, AsyncWgSocket};
+pub use socket::{LinkEventSocket, RouteSocket, WgSocket};
@@ -0,0 +1,80 @@
+use std::fs::File;
+use std::io;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::path::Path;
+use std::thread;
+
+/// A handle to a network namespace, used to open sockets inside of it or to move device
+/// interfaces into it.
+///
+/// ```no_run
+/// use wireguard_uapi::netns::NetNs;
+/// use wireguard_uapi::RouteSocket;
+///
+/// # fn main() -> anyhow::Result<()> {
+/// // Created with `ip netns add tenant`.
+/// let tenant = NetNs::from_name("tenant")?;
+///
+/// // The device's UDP socket stays in the current namespace while the interface is only visible
+/// // inside the tenant's.
+/// RouteSocket::connect()?.add_device_in_netns("wgtenant0", &tenant)?;
+/// let mut route_socket = RouteSocket::connect_netns(&tenant)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct NetNs {
+    file: File,
+}
+
+impl NetNs {
+    /// Opens a namespace file such as `/proc/<pid>/ns/net` or a bind mount of one.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(Self {
+            file: File::open(path)?,
+        })
+    }
+
+    /// Opens a namespace created with `ip netns add`, which are bind mounted under `/run/netns`.
+    pub fn from_name(name: &str) -> io::Result<Self> {
+        Self::open(Path::new("/run/netns").join(name))
+    }
+
+    /// The namespace of the calling thread.
+    pub fn current() -> io::Result<Self> {
+        Self::open("/proc/thread-self/ns/net")
+    }
+
+    /// Runs the function on a new thread that joined the namespace. Sockets keep the namespace
+    /// they were created in, so this leaves the namespace of the calling thread alone.
+    pub(crate) fn run<T, F>(&self, f: F) -> io::Result<T>
+    where
+        T: Send,
+        F: FnOnce() -> T + Send,
+    {
+        let fd = self.as_raw_fd();
+        thread::scope(|scope| {
+            scope
+                .spawn(|| {
+                    if unsafe { libc::setns(fd, libc::CLONE_NEWNET) } == -1 {
+                        return Err(io::Error::last_os_error());
+                    }
+                    Ok(f())
+                })
+                .join()
+                .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+        })
+    }
+}
+
+impl From<File> for NetNs {
+    fn from(file: File) -> Self {
+        Self { file }
+    }
+}
+
+impl AsRawFd for NetNs {
+    fn as_raw_fd(&self) -> RawFd {
+        self.file.as_raw_fd()
+    }
+}
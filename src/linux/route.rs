@@ -0,0 +1,159 @@
+use crate::get;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// A route that sends traffic for a destination out of a device interface. Adding one is the
+/// equivalent of:
+///
+/// ```sh
+///  sudo ip route add 10.24.0.0/16 dev wgtest0
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Route {
+    pub ifindex: u32,
+    pub destination: IpAddr,
+    pub prefix_len: u8,
+    /// The routing table. Defaults to the main table (`RT_TABLE_MAIN`).
+    pub table: u32,
+    /// The route priority. `None` leaves it to the kernel, which uses 0 for IPv4 and 1024 for
+    /// IPv6.
+    pub metric: Option<u32>,
+    /// A `RTPROT_*` value recording what installed the route. Defaults to `RTPROT_BOOT`, same as
+    /// the `ip` command.
+    pub protocol: u8,
+}
+
+impl Route {
+    pub fn new(ifindex: u32, destination: IpAddr, prefix_len: u8) -> Self {
+        Self {
+            ifindex,
+            destination,
+            prefix_len,
+            table: libc::RT_TABLE_MAIN as u32,
+            metric: None,
+            protocol: libc::RTPROT_BOOT,
+        }
+    }
+
+    /// The kernel rejects routes with host bits set, so they're cleared from the allowed IP.
+    pub fn from_allowed_ip(ifindex: u32, allowed_ip: &get::AllowedIp) -> Self {
+        let (destination, prefix_len) = match allowed_ip.ipaddr {
+            IpAddr::V4(addr) => {
+                let prefix_len = allowed_ip.cidr_mask.min(32);
+                let mask = u32::MAX
+                    .checked_shl(32 - u32::from(prefix_len))
+                    .unwrap_or(0);
+                (Ipv4Addr::from(u32::from(addr) & mask).into(), prefix_len)
+            }
+            IpAddr::V6(addr) => {
+                let prefix_len = allowed_ip.cidr_mask.min(128);
+                let mask = u128::MAX
+                    .checked_shl(128 - u32::from(prefix_len))
+                    .unwrap_or(0);
+                (Ipv6Addr::from(u128::from(addr) & mask).into(), prefix_len)
+            }
+        };
+        Self::new(ifindex, destination, prefix_len)
+    }
+
+    /// One route per allowed IP of every peer on the device.
+    pub fn for_allowed_ips(device: &get::Device) -> Vec<Self> {
+        device
+            .peers
+            .iter()
+            .flat_map(|peer| &peer.allowed_ips)
+            .map(|allowed_ip| Self::from_allowed_ip(device.ifindex, allowed_ip))
+            .collect()
+    }
+
+    /// The routes added by
+    /// [`RouteSocket::add_allowed_ip_routes`](crate::RouteSocket::add_allowed_ip_routes). Default
+    /// routes are left out when the table is the main table.
+    pub(crate) fn for_allowed_ips_in_table(
+        device: &get::Device,
+        table: u32,
+        metric: Option<u32>,
+        protocol: u8,
+    ) -> Vec<Self> {
+        Self::for_allowed_ips(device)
+            .into_iter()
+            .filter(|route| route.prefix_len > 0 || table != libc::RT_TABLE_MAIN as u32)
+            .map(|route| Self {
+                table,
+                metric,
+                protocol,
+                ..route
+            })
+            .collect()
+    }
+
+    pub fn table(mut self, table: u32) -> Self {
+        self.table = table;
+        self
+    }
+
+    pub fn metric(mut self, metric: u32) -> Self {
+        self.metric = Some(metric);
+        self
+    }
+
+    pub fn protocol(mut self, protocol: u8) -> Self {
+        self.protocol = protocol;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_allowed_ip_clears_host_bits() -> anyhow::Result<()> {
+        let route = Route::from_allowed_ip(3, &"10.24.3.7/16".parse()?);
+        assert_eq!(route, Route::new(3, "10.24.0.0".parse()?, 16));
+
+        let route = Route::from_allowed_ip(3, &"fd00:1:2::3/48".parse()?);
+        assert_eq!(route, Route::new(3, "fd00:1:2::".parse()?, 48));
+
+        let route = Route::from_allowed_ip(3, &"0.0.0.0/0".parse()?);
+        assert_eq!(route.prefix_len, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn allowed_ip_routes_skip_default_routes_in_main_table() -> anyhow::Result<()> {
+        let device = get::Device {
+            ifindex: 3,
+            ifname: "wgtest0".to_string(),
+            private_key: None,
+            public_key: None,
+            listen_port: 0,
+            fwmark: 0,
+            peers: vec![get::Peer {
+                public_key: "xTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg=".parse()?,
+                preshared_key: crate::PresharedKey::zero(),
+                endpoint: None,
+                persistent_keepalive_interval: 0,
+                last_handshake_time: std::time::Duration::ZERO,
+                rx_bytes: 0,
+                tx_bytes: 0,
+                allowed_ips: vec![
+                    "10.24.0.0/16".parse()?,
+                    "0.0.0.0/0".parse()?,
+                    "::/0".parse()?,
+                ],
+                protocol_version: 1,
+            }],
+        };
+
+        let main = libc::RT_TABLE_MAIN as u32;
+        let routes = Route::for_allowed_ips_in_table(&device, main, None, libc::RTPROT_BOOT);
+        assert_eq!(routes, vec![Route::new(3, "10.24.0.0".parse()?, 16)]);
+
+        let routes = Route::for_allowed_ips_in_table(&device, 51820, Some(10), libc::RTPROT_STATIC);
+        assert_eq!(routes.len(), 3);
+        assert!(routes.iter().all(|route| route.table == 51820
+            && route.metric == Some(10)
+            && route.protocol == libc::RTPROT_STATIC));
+        Ok(())
+    }
+}
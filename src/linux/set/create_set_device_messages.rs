@@ -1,25 +1,95 @@
 use super::{AllowedIp, Device, Peer};
+use crate::key::PublicKey;
 use crate::linux::attr::NLA_F_NESTED;
 use crate::linux::attr::{NlaNested, WgDeviceAttribute, WgPeerAttribute};
 use crate::linux::cmd::WgCmd;
 use crate::linux::consts::WG_GENL_VERSION;
 use crate::linux::socket::NlWgMsgType;
 use crate::linux::DeviceInterface;
-use neli::consts::NlmF;
-use neli::err::SerError;
-use neli::genl::Genlmsghdr;
+use neli::consts::{NlAttrType, NlmF};
+use neli::err::{DeError, SerError};
 use neli::nl::Nlmsghdr;
 use neli::nlattr::Nlattr;
-use neli::Nl;
+use neli::{Nl, StreamReadBuffer, StreamWriteBuffer};
 use std::convert::TryInto;
 use std::net::SocketAddr;
+use zeroize::Zeroize;
 
 // TODO: Remove these constants and use something from libc.
 const NETLINK_HEADER_SIZE: usize = 16;
 const GENL_HEADER_SIZE: usize = 4;
 const NETLINK_MSG_LIMIT: usize = 65_536; // 2^16
 
-type NlWgMessage = Nlmsghdr<NlWgMsgType, Genlmsghdr<WgCmd, WgDeviceAttribute>>;
+pub(crate) type NlWgMessage = Nlmsghdr<NlWgMsgType, SetDevicePayload>;
+
+/// The generic netlink payload of a set_device message. This stands in for neli's `Genlmsghdr`,
+/// which doesn't expose its attributes, so that the serialized private and preshared keys can be
+/// wiped once the message is dropped.
+pub(crate) struct SetDevicePayload {
+    attrs: Vec<Nlattr<WgDeviceAttribute, Vec<u8>>>,
+}
+
+impl Nl for SetDevicePayload {
+    fn serialize(&self, mem: &mut StreamWriteBuffer) -> Result<(), SerError> {
+        WgCmd::SetDevice.serialize(mem)?;
+        WG_GENL_VERSION.serialize(mem)?;
+        // Reserved
+        0u16.serialize(mem)?;
+        self.attrs.serialize(mem)?;
+        self.pad(mem)?;
+        Ok(())
+    }
+
+    fn deserialize<B>(_mem: &mut StreamReadBuffer<B>) -> Result<Self, DeError>
+    where
+        B: AsRef<[u8]>,
+    {
+        Err(DeError::new("set_device messages are never received"))
+    }
+
+    fn size(&self) -> usize {
+        GENL_HEADER_SIZE + self.attrs.asize()
+    }
+}
+
+impl Drop for SetDevicePayload {
+    fn drop(&mut self) {
+        for attr in &mut self.attrs {
+            attr.payload.zeroize();
+        }
+    }
+}
+
+/// Moves the attribute out, leaving an empty payload behind for the fragment's drop impl.
+fn take_attr<T: Clone>(attr: &mut Nlattr<T, Vec<u8>>) -> Nlattr<T, Vec<u8>> {
+    Nlattr {
+        nla_len: attr.nla_len,
+        nla_type: attr.nla_type.clone(),
+        payload: std::mem::take(&mut attr.payload),
+    }
+}
+
+/// Like `Nlattr::add_nested_attribute`, but grows the parent's payload into a new buffer and wipes
+/// the old one. Letting the Vec reallocate would leave stale copies of any keys on the heap.
+fn add_nested_attribute<T, TT, P>(
+    parent: &mut Nlattr<T, Vec<u8>>,
+    attr: &Nlattr<TT, P>,
+) -> Result<(), SerError>
+where
+    T: NlAttrType,
+    TT: NlAttrType,
+    P: Nl,
+{
+    let required = parent.payload.len() + attr.asize();
+    if parent.payload.capacity() < required {
+        let capacity = std::cmp::max(required, 2 * parent.payload.capacity());
+        let mut grown = Vec::with_capacity(capacity);
+        grown.extend_from_slice(&parent.payload);
+        std::mem::replace(&mut parent.payload, grown).zeroize();
+    }
+
+    parent.add_nested_attribute(attr)
+}
 
 /// A struct containing information necessary to build a set_device message fragment. It keeps
 /// track of an initial bag of partial_device but keeps peers separate until they're ready to be
@@ -31,51 +101,42 @@ struct IncubatingDeviceFragment {
 
 impl IncubatingDeviceFragment {
     fn split_off_peers(device: Device<'_>) -> Result<(Self, Vec<Peer<'_>>), SerError> {
-        let incubating_device = IncubatingDeviceFragment {
-            partial_device: {
-                let mut attrs = vec![];
-
-                let interface_attr = (&device.interface).try_into()?;
-                attrs.push(interface_attr);
-
-                if !device.flags.is_empty() {
-                    let mut unique = device.flags.clone();
-                    unique.dedup();
-
-                    attrs.push(Nlattr::new(
-                        None,
-                        WgDeviceAttribute::Flags,
-                        unique.drain(..).map(|flag| flag as u32).sum::<u32>(),
-                    )?);
-                }
+        let mut incubating_device = Self::from_interface(&device.interface)?;
+        let attrs = &mut incubating_device.partial_device;
 
-                if let Some(private_key) = device.private_key {
-                    attrs.push(Nlattr::new(
-                        None,
-                        WgDeviceAttribute::PrivateKey,
-                        &private_key[..],
-                    )?);
-                }
+        if !device.flags.is_empty() {
+            let mut unique = device.flags.clone();
+            unique.dedup();
 
-                if let Some(listen_port) = device.listen_port {
-                    attrs.push(Nlattr::new(
-                        None,
-                        WgDeviceAttribute::ListenPort,
-                        &listen_port.to_ne_bytes()[..],
-                    )?);
-                }
+            attrs.push(Nlattr::new(
+                None,
+                WgDeviceAttribute::Flags,
+                unique.drain(..).map(|flag| flag as u32).sum::<u32>(),
+            )?);
+        }
 
-                if let Some(fwmark) = device.fwmark {
-                    attrs.push(Nlattr::new(None, WgDeviceAttribute::Fwmark, fwmark)?);
-                }
+        if let Some(private_key) = device.private_key {
+            attrs.push(Nlattr::new(
+                None,
+                WgDeviceAttribute::PrivateKey,
+                &private_key.as_bytes()[..],
+            )?);
+        }
 
-                // This covers all attributes except peers. Avoid parsing peers here purposefully
-                // since they may not all fit into the first device message.
+        if let Some(listen_port) = device.listen_port {
+            attrs.push(Nlattr::new(
+                None,
+                WgDeviceAttribute::ListenPort,
+                &listen_port.to_ne_bytes()[..],
+            )?);
+        }
 
-                attrs
-            },
-            peers: Nlattr::new::<Vec<u8>>(None, WgDeviceAttribute::Peers | NLA_F_NESTED, vec![])?,
-        };
+        if let Some(fwmark) = device.fwmark {
+            attrs.push(Nlattr::new(None, WgDeviceAttribute::Fwmark, fwmark)?);
+        }
+
+        // This covers all attributes except peers. Avoid parsing peers here purposefully
+        // since they may not all fit into the first device message.
 
         Ok((incubating_device, device.peers))
     }
@@ -95,18 +156,23 @@ impl IncubatingDeviceFragment {
         NETLINK_HEADER_SIZE + GENL_HEADER_SIZE + attrs_size + self.peers.asize()
     }
 
-    fn finalize(self, family_id: NlWgMsgType) -> Result<NlWgMessage, SerError> {
-        let mut device_attrs = self.partial_device;
+    fn add_peer(&mut self, peer: IncubatingPeerFragment) -> Result<(), SerError> {
+        let mut peer_attr = peer.finalize()?;
+        let result = add_nested_attribute(&mut self.peers, &peer_attr);
+        peer_attr.payload.zeroize();
+        result
+    }
+
+    fn finalize(mut self, family_id: NlWgMsgType) -> Result<NlWgMessage, SerError> {
+        let mut device_attrs = std::mem::take(&mut self.partial_device);
 
         // TODO: Condition this behavior on whether peers have ever been added.
         if self.peers.size() > GENL_HEADER_SIZE {
-            device_attrs.push(self.peers);
+            device_attrs.push(take_attr(&mut self.peers));
         }
 
-        let genlhdr = {
-            let cmd = WgCmd::SetDevice;
-            let version = WG_GENL_VERSION;
-            Genlmsghdr::new(cmd, version, device_attrs)?
+        let payload = SetDevicePayload {
+            attrs: device_attrs,
         };
         let nlhdr: NlWgMessage = {
             let size = None;
@@ -114,7 +180,6 @@ impl IncubatingDeviceFragment {
             let flags = vec![NlmF::Request, NlmF::Ack];
             let seq = None;
             let pid = None;
-            let payload = genlhdr;
             Nlmsghdr::new(size, nl_type, flags, seq, pid, payload)
         };
 
@@ -122,6 +187,15 @@ impl IncubatingDeviceFragment {
     }
 }
 
+impl Drop for IncubatingDeviceFragment {
+    fn drop(&mut self) {
+        for attr in &mut self.partial_device {
+            attr.payload.zeroize();
+        }
+        self.peers.payload.zeroize();
+    }
+}
+
 struct IncubatingPeerFragment {
     pub partial_peer: Nlattr<NlaNested, Vec<u8>>,
     pub allowed_ips: Nlattr<WgPeerAttribute, Vec<u8>>,
@@ -129,29 +203,32 @@ struct IncubatingPeerFragment {
 
 impl IncubatingPeerFragment {
     fn split_off_allowed_ips(peer: Peer<'_>) -> Result<(Self, Vec<AllowedIp<'_>>), SerError> {
-        let mut partial_peer =
-            Nlattr::new::<Vec<u8>>(None, NlaNested::Unspec | NLA_F_NESTED, vec![])?;
-
-        let public_key = Nlattr::new(None, WgPeerAttribute::PublicKey, peer.public_key.to_vec())?;
-        partial_peer.add_nested_attribute(&public_key)?;
+        let mut incubating_peer_fragment = Self::from_public_key(peer.public_key)?;
+        let partial_peer = &mut incubating_peer_fragment.partial_peer;
 
         if !peer.flags.is_empty() {
             let mut unique = peer.flags.clone();
             unique.dedup();
 
-            partial_peer.add_nested_attribute(&Nlattr::new(
-                None,
-                WgPeerAttribute::Flags,
-                unique.drain(..).map(|flag| flag as u32).sum::<u32>(),
-            )?)?;
+            add_nested_attribute(
+                partial_peer,
+                &Nlattr::new(
+                    None,
+                    WgPeerAttribute::Flags,
+                    unique.drain(..).map(|flag| flag as u32).sum::<u32>(),
+                )?,
+            )?;
         }
 
         if let Some(preshared_key) = peer.preshared_key {
-            partial_peer.add_nested_attribute(&Nlattr::new(
+            let mut preshared_key = Nlattr::new(
                 None,
                 WgPeerAttribute::PresharedKey,
-                &preshared_key[..],
-            )?)?;
+                &preshared_key.as_bytes()[..],
+            )?;
+            let result = add_nested_attribute(partial_peer, &preshared_key);
+            preshared_key.payload.zeroize();
+            result?;
         }
 
         if let Some(endpoint) = peer.endpoint {
@@ -179,51 +256,43 @@ impl IncubatingPeerFragment {
                 }
             };
 
-            partial_peer.add_nested_attribute(&Nlattr::new(
-                None,
-                WgPeerAttribute::Endpoint,
-                payload,
-            )?)?;
+            add_nested_attribute(
+                partial_peer,
+                &Nlattr::new(None, WgPeerAttribute::Endpoint, payload)?,
+            )?;
         }
 
         if let Some(persistent_keepalive_interval) = peer.persistent_keepalive_interval {
-            partial_peer.add_nested_attribute(&Nlattr::new(
-                None,
-                WgPeerAttribute::PersistentKeepaliveInterval,
-                &persistent_keepalive_interval.to_ne_bytes()[..],
-            )?)?;
+            add_nested_attribute(
+                partial_peer,
+                &Nlattr::new(
+                    None,
+                    WgPeerAttribute::PersistentKeepaliveInterval,
+                    &persistent_keepalive_interval.to_ne_bytes()[..],
+                )?,
+            )?;
         }
 
         if let Some(protocol_version) = peer.protocol_version {
-            partial_peer.add_nested_attribute(&Nlattr::new(
-                None,
-                WgPeerAttribute::ProtocolVersion,
-                protocol_version,
-            )?)?;
+            add_nested_attribute(
+                partial_peer,
+                &Nlattr::new(None, WgPeerAttribute::ProtocolVersion, protocol_version)?,
+            )?;
         }
 
         // This covers all attributes except allowed ips. Avoid parsing allowed ips here
         // purposefully since they may not all fit into the current device message.
 
-        let incubating_peer_fragment = IncubatingPeerFragment {
-            partial_peer,
-            allowed_ips: Nlattr::new::<Vec<u8>>(
-                None,
-                WgPeerAttribute::AllowedIps | NLA_F_NESTED,
-                vec![],
-            )?,
-        };
-
         Ok((incubating_peer_fragment, peer.allowed_ips))
     }
 
-    fn from_public_key(public_key: &[u8; 32]) -> Result<Self, SerError> {
+    fn from_public_key(public_key: &PublicKey) -> Result<Self, SerError> {
         let mut partial_peer =
             Nlattr::new::<Vec<u8>>(None, NlaNested::Unspec | NLA_F_NESTED, vec![])?;
         let allowed_ips =
             Nlattr::new::<Vec<u8>>(None, WgPeerAttribute::AllowedIps | NLA_F_NESTED, vec![])?;
 
-        let public_key = Nlattr::new(None, WgPeerAttribute::PublicKey, public_key.to_vec())?;
+        let public_key = Nlattr::new(None, WgPeerAttribute::PublicKey, &public_key.as_bytes()[..])?;
         partial_peer.add_nested_attribute(&public_key)?;
 
         Ok(IncubatingPeerFragment {
@@ -236,12 +305,17 @@ impl IncubatingPeerFragment {
         self.partial_peer.asize() + self.allowed_ips.asize()
     }
 
-    fn finalize(self) -> Result<Nlattr<NlaNested, Vec<u8>>, SerError> {
-        let mut partial_peer = self.partial_peer;
+    fn finalize(mut self) -> Result<Nlattr<NlaNested, Vec<u8>>, SerError> {
         if self.allowed_ips.size() > GENL_HEADER_SIZE {
-            partial_peer.add_nested_attribute(&self.allowed_ips)?;
+            add_nested_attribute(&mut self.partial_peer, &self.allowed_ips)?;
         }
-        Ok(partial_peer)
+        Ok(take_attr(&mut self.partial_peer))
+    }
+}
+
+impl Drop for IncubatingPeerFragment {
+    fn drop(&mut self) {
+        self.partial_peer.payload.zeroize();
     }
 }
 
@@ -278,10 +352,7 @@ pub fn create_set_device_messages(
                 + incubating_peer_fragment.incubating_size()
                 + allowed_ip_attr.asize();
             if next_size > NETLINK_MSG_LIMIT {
-                let peer_fragment = incubating_peer_fragment.finalize()?;
-                incubating_device_fragment
-                    .peers
-                    .add_nested_attribute(&peer_fragment)?;
+                incubating_device_fragment.add_peer(incubating_peer_fragment)?;
 
                 let device_message = incubating_device_fragment.finalize(family_id)?;
                 messages.push(device_message);
@@ -295,10 +366,7 @@ pub fn create_set_device_messages(
                 .add_nested_attribute(&allowed_ip_attr)?;
         }
 
-        let peer_attr = incubating_peer_fragment.finalize()?;
-        incubating_device_fragment
-            .peers
-            .add_nested_attribute(&peer_attr)?;
+        incubating_device_fragment.add_peer(incubating_peer_fragment)?;
     }
 
     let device_message = incubating_device_fragment.finalize(family_id)?;
@@ -306,3 +374,59 @@ pub fn create_set_device_messages(
 
     Ok(messages)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key::{PresharedKey, PrivateKey};
+    use crate::linux::attr::NLA_TYPE_MASK;
+    use neli::genl::Genlmsghdr;
+
+    #[test]
+    fn set_device_message_round_trips() -> anyhow::Result<()> {
+        let private_key = PrivateKey::generate();
+        let public_key = private_key.public_key();
+        let preshared_key = PresharedKey::generate();
+        let device = Device::from_ifname("wgtest0")
+            .private_key(&private_key)
+            .peers(vec![
+                Peer::from_public_key(&public_key).preshared_key(&preshared_key)
+            ]);
+
+        let messages = create_set_device_messages(device, 1)?;
+        assert_eq!(messages.len(), 1);
+
+        let mut mem = StreamWriteBuffer::new_growable(None);
+        messages[0].serialize(&mut mem)?;
+        let mut mem = StreamReadBuffer::new(mem.as_ref());
+        let message =
+            Nlmsghdr::<NlWgMsgType, Genlmsghdr<WgCmd, WgDeviceAttribute>>::deserialize(&mut mem)?;
+        assert_eq!(message.nl_payload.cmd, WgCmd::SetDevice);
+
+        let mut found_private_key = false;
+        let mut found_preshared_key = false;
+        for attr in message.nl_payload.get_attr_handle().iter() {
+            match attr.nla_type.clone() & NLA_TYPE_MASK {
+                WgDeviceAttribute::PrivateKey => {
+                    assert_eq!(&attr.payload[..], &private_key.as_bytes()[..]);
+                    found_private_key = true;
+                }
+                WgDeviceAttribute::Peers => {
+                    for peer in attr.get_nested_attributes::<NlaNested>()?.iter() {
+                        for peer_attr in peer.get_nested_attributes::<WgPeerAttribute>()?.iter() {
+                            if peer_attr.nla_type == WgPeerAttribute::PresharedKey {
+                                assert_eq!(&peer_attr.payload[..], &preshared_key.as_bytes()[..]);
+                                found_preshared_key = true;
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        assert!(found_private_key);
+        assert!(found_preshared_key);
+        Ok(())
+    }
+}
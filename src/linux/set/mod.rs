@@ -6,4 +6,4 @@ mod peer;
 pub use peer::{Peer, WgPeerF};
 
 mod create_set_device_messages;
-pub(crate) use create_set_device_messages::create_set_device_messages;
+pub(crate) use create_set_device_messages::{create_set_device_messages, NlWgMessage};
@@ -0,0 +1,133 @@
+use super::link_message::create_rtattr;
+use super::parse::{parse_in6_addr, parse_in_addr};
+use crate::address::Address;
+use crate::err::ParseAttributeError;
+use neli::consts::rtnl::RtAddrFamily;
+use neli::consts::{Ifa, NlmF, Rtm};
+use neli::nl::Nlmsghdr;
+use neli::rtnl::Ifaddrmsg;
+use neli::Nl;
+use std::net::IpAddr;
+
+const NLMSG_HDRLEN: usize = 16;
+
+pub enum AddressOperation {
+    Add,
+    Delete,
+}
+
+pub fn address_message(
+    ifindex: u32,
+    address: &Address,
+    operation: AddressOperation,
+) -> Nlmsghdr<Rtm, Ifaddrmsg<Ifa>> {
+    let (family, ipaddr) = match address.ipaddr {
+        IpAddr::V4(addr) => (libc::AF_INET, addr.octets().to_vec()),
+        IpAddr::V6(addr) => (libc::AF_INET6, addr.octets().to_vec()),
+    };
+
+    let addrmsg = Ifaddrmsg {
+        ifa_family: RtAddrFamily::UnrecognizedVariant(family as u8),
+        ifa_prefixlen: address.prefix_len,
+        ifa_flags: vec![],
+        ifa_scope: libc::RT_SCOPE_UNIVERSE,
+        ifa_index: ifindex as libc::c_int,
+        // Same as the `ip` command, which sets both for addresses without a peer.
+        rtattrs: vec![
+            create_rtattr(Ifa::Local, ipaddr.clone()),
+            create_rtattr(Ifa::Address, ipaddr),
+        ],
+    };
+
+    let nl_type = match operation {
+        AddressOperation::Add => Rtm::Newaddr,
+        AddressOperation::Delete => Rtm::Deladdr,
+    };
+    let flags = match operation {
+        AddressOperation::Add => vec![NlmF::Request, NlmF::Ack, NlmF::Create, NlmF::Excl],
+        AddressOperation::Delete => vec![NlmF::Request, NlmF::Ack],
+    };
+    // neli leaves the attributes out of Ifaddrmsg's size, so the length is computed here.
+    let len = Some((NLMSG_HDRLEN + addrmsg.asize() + addrmsg.rtattrs.asize()) as u32);
+    let seq = None;
+    let pid = None;
+    Nlmsghdr::new(len, nl_type, flags, seq, pid, addrmsg)
+}
+
+pub fn get_addresses_msg() -> Nlmsghdr<Rtm, Ifaddrmsg<Ifa>> {
+    let addrmsg = Ifaddrmsg {
+        // Dumps both IPv4 and IPv6 addresses.
+        ifa_family: RtAddrFamily::UnrecognizedVariant(libc::AF_UNSPEC as u8),
+        ifa_prefixlen: 0,
+        ifa_flags: vec![],
+        ifa_scope: 0,
+        ifa_index: 0,
+        rtattrs: vec![],
+    };
+
+    let len = None;
+    let nl_type = Rtm::Getaddr;
+    let flags = vec![NlmF::Request, NlmF::Dump];
+    let seq = None;
+    let pid = None;
+    Nlmsghdr::new(len, nl_type, flags, seq, pid, addrmsg)
+}
+
+/// Returns the interface index along with the address.
+pub fn parse_ifaddrmsg(
+    addrmsg: Ifaddrmsg<Ifa>,
+) -> Result<Option<(u32, Address)>, ParseAttributeError> {
+    let family = i32::from(u8::from(addrmsg.ifa_family));
+    let parse_ipaddr = |payload: &[u8]| -> Result<IpAddr, ParseAttributeError> {
+        match family {
+            libc::AF_INET => Ok(parse_in_addr(payload)?.into()),
+            _ => Ok(parse_in6_addr(payload)?.into()),
+        }
+    };
+
+    if family != libc::AF_INET && family != libc::AF_INET6 {
+        return Ok(None);
+    }
+
+    // IFA_LOCAL is the address itself. IFA_ADDRESS is the peer address on point-to-point
+    // interfaces, and the only one present for IPv6.
+    let mut local = None;
+    let mut address = None;
+    for attr in &addrmsg.rtattrs {
+        match attr.rta_type {
+            Ifa::Local => local = Some(parse_ipaddr(&attr.rta_payload)?),
+            Ifa::Address => address = Some(parse_ipaddr(&attr.rta_payload)?),
+            _ => {}
+        }
+    }
+
+    let ifindex = addrmsg.ifa_index as u32;
+    let prefix_len = addrmsg.ifa_prefixlen;
+    Ok(local
+        .or(address)
+        .map(|ipaddr| (ifindex, Address::new(ipaddr, prefix_len))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use neli::consts::Nlmsg;
+    use neli::{StreamReadBuffer, StreamWriteBuffer};
+
+    #[test]
+    fn address_message_round_trips() -> anyhow::Result<()> {
+        for address in &["10.24.0.1/16", "fd00::1/64"] {
+            let address: Address = address.parse()?;
+
+            let mut buf = StreamWriteBuffer::new_growable(None);
+            address_message(7, &address, AddressOperation::Add).serialize(&mut buf)?;
+            let message = Nlmsghdr::<Nlmsg, Ifaddrmsg<Ifa>>::deserialize(
+                &mut StreamReadBuffer::new(buf.as_ref()),
+            )?;
+
+            assert_eq!(message.nl_len as usize, buf.as_ref().len());
+            assert_eq!(parse_ifaddrmsg(message.nl_payload)?, Some((7, address)));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,43 @@
+use super::async_nl_socket::{AsyncNlSocket, NlResponse};
+use super::link_event::LinkEventFilter;
+use crate::err::LinkEventError;
+use crate::link::LinkEvent;
+use crate::AsyncRouteSocket;
+use neli::consts::{Ifla, NlFamily};
+use neli::rtnl::Ifinfomsg;
+
+/// The async counterpart of [`LinkEventSocket`](crate::LinkEventSocket). Requires the `tokio`
+/// feature.
+///
+/// Receiving can be cancelled by dropping its future without losing events.
+pub struct AsyncLinkEventSocket {
+    sock: AsyncNlSocket,
+    filter: LinkEventFilter,
+}
+
+impl AsyncLinkEventSocket {
+    pub async fn connect() -> Result<Self, LinkEventError> {
+        // See LinkEventSocket::connect.
+        let groups = Some(vec![libc::RTNLGRP_LINK]);
+        let sock = AsyncNlSocket::connect_multicast(NlFamily::Route, groups)?;
+        let links = AsyncRouteSocket::connect().await?.list_devices().await?;
+
+        Ok(Self {
+            sock,
+            filter: LinkEventFilter::new(&links),
+        })
+    }
+
+    /// Waits for the next event.
+    pub async fn recv_event(&mut self) -> Result<LinkEvent, LinkEventError> {
+        loop {
+            let message = match self.sock.recv_multicast::<u16, Ifinfomsg<Ifla>>().await? {
+                NlResponse::Message(message) => message,
+                NlResponse::Done | NlResponse::Ack | NlResponse::Error(_) => continue,
+            };
+            if let Some(event) = self.filter.filter(message.nl_type, message.nl_payload)? {
+                return Ok(event);
+            }
+        }
+    }
+}
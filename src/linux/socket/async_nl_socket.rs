@@ -0,0 +1,280 @@
+use super::io_error;
+use super::nlmsgerr::{enable_ext_ack, parse_nlmsgerr};
+use crate::err::KernelError;
+use crate::netns::NetNs;
+use neli::consts::{NlFamily, NlType, NlmF};
+use neli::err::NlError;
+use neli::nl::Nlmsghdr;
+use neli::socket::NlSocket;
+use neli::{Nl, StreamReadBuffer, StreamWriteBuffer, MAX_NL_LENGTH};
+use std::convert::TryInto;
+use std::io;
+use std::ops::Range;
+use tokio::io::unix::AsyncFd;
+use zeroize::Zeroizing;
+
+const NLMSG_HDRLEN: usize = 16;
+const NLMSG_ALIGNTO: usize = 4;
+
+pub(crate) enum NlResponse<T, P> {
+    Message(Nlmsghdr<T, P>),
+    Done,
+    Ack,
+    Error(KernelError),
+}
+
+/// A non-blocking netlink socket driven by tokio.
+///
+/// Every request gets a new sequence number and responses with any other
+/// sequence number are skipped. A request whose future was dropped before its
+/// response was read therefore can't confuse the next request on the socket.
+///
+/// The kernel only runs one dump per socket at a time, and refuses new dumps
+/// with `EBUSY` until the last one was read to its end. The rest of a dump
+/// whose future was dropped is read before the next request is sent.
+pub(crate) struct AsyncNlSocket {
+    fd: AsyncFd<NlSocket>,
+    seq: u32,
+    /// A dump was requested and its end wasn't received yet.
+    dump: bool,
+    // Responses may contain private and preshared keys.
+    buf: Zeroizing<Vec<u8>>,
+    len: usize,
+    pos: usize,
+}
+
+impl AsyncNlSocket {
+    pub fn connect(family: NlFamily) -> io::Result<Self> {
+        Self::connect_multicast(family, None)
+    }
+
+    /// Joins the multicast groups as well. See [`AsyncNlSocket::recv_multicast`].
+    pub fn connect_multicast(family: NlFamily, groups: Option<Vec<u32>>) -> io::Result<Self> {
+        Self::from_socket(Self::open(family, groups)?)
+    }
+
+    /// Opens the socket inside the network namespace. This briefly blocks on
+    /// a thread that joins the namespace.
+    pub fn connect_netns(family: NlFamily, netns: &NetNs) -> io::Result<Self> {
+        Self::from_socket(netns.run(|| Self::open(family, None))??)
+    }
+
+    fn open(family: NlFamily, groups: Option<Vec<u32>>) -> io::Result<NlSocket> {
+        // Sequence numbers are tracked here instead.
+        let track_seq = false;
+        let mut sock = NlSocket::new(family, track_seq)?;
+
+        // Autoselect a PID
+        let pid = None;
+        sock.bind(pid, groups)?;
+        enable_ext_ack(&sock);
+        Ok(sock)
+    }
+
+    fn from_socket(mut sock: NlSocket) -> io::Result<Self> {
+        sock.nonblock()?;
+
+        // AsyncFd::register replaces this in newer tokio versions. NlSocket
+        // owns its file descriptor and closes it on drop, so it stays valid for
+        // as long as the AsyncFd exists either way.
+        #[allow(deprecated)]
+        let fd = AsyncFd::new(sock)?;
+
+        Ok(Self {
+            fd,
+            seq: 0,
+            dump: false,
+            buf: Zeroizing::new(vec![0; MAX_NL_LENGTH]),
+            len: 0,
+            pos: 0,
+        })
+    }
+
+    /// Sends a new request. Responses still buffered from an earlier request
+    /// are discarded.
+    pub async fn send_nl<T, P>(&mut self, mut message: Nlmsghdr<T, P>) -> Result<(), NlError>
+    where
+        T: Nl + NlType,
+        P: Nl,
+    {
+        self.finish_dump().await?;
+        self.seq = self.seq.wrapping_add(1);
+        self.pos = self.len;
+        message.nl_seq = self.seq;
+        self.dump = message.nl_flags.contains(&NlmF::Dump);
+
+        // Set device messages may contain private and preshared keys.
+        let mut buf = Zeroizing::new(Vec::with_capacity(message.asize()));
+        message.serialize(&mut StreamWriteBuffer::new_growable_ref(&mut buf))?;
+
+        loop {
+            let mut guard = self.fd.writable_mut().await.map_err(io_error)?;
+            match guard.try_io(|fd| fd.get_mut().send(&buf[..], 0)) {
+                Ok(result) => {
+                    result.map_err(io_error)?;
+                    return Ok(());
+                }
+                Err(_would_block) => continue,
+            }
+        }
+    }
+
+    /// Receives the next response to the last request sent.
+    pub async fn recv_nl<T, P>(&mut self) -> Result<NlResponse<T, P>, NlError>
+    where
+        T: Nl + NlType,
+        P: Nl,
+    {
+        self.recv_matching(Some(self.seq)).await
+    }
+
+    /// Receives the next message sent to a multicast group. These aren't responses to a request,
+    /// so every sequence number is accepted. Don't mix with requests on the same socket.
+    pub async fn recv_multicast<T, P>(&mut self) -> Result<NlResponse<T, P>, NlError>
+    where
+        T: Nl + NlType,
+        P: Nl,
+    {
+        self.recv_matching(None).await
+    }
+
+    async fn recv_matching<T, P>(&mut self, seq: Option<u32>) -> Result<NlResponse<T, P>, NlError>
+    where
+        T: Nl + NlType,
+        P: Nl,
+    {
+        let message = self.next_message(seq).await?;
+        let message = &self.buf[message];
+
+        let nl_type = u16::from_ne_bytes(message[4..6].try_into().unwrap());
+        Ok(match i32::from(nl_type) {
+            libc::NLMSG_DONE => NlResponse::Done,
+            libc::NLMSG_ERROR => {
+                let flags = u16::from_ne_bytes(message[6..8].try_into().unwrap());
+                match parse_nlmsgerr(flags, &message[NLMSG_HDRLEN..])? {
+                    None => NlResponse::Ack,
+                    Some(error) => NlResponse::Error(error),
+                }
+            }
+            _ => NlResponse::Message(Nlmsghdr::deserialize(&mut StreamReadBuffer::new(message))?),
+        })
+    }
+
+    /// Reads and discards the rest of an unfinished dump.
+    async fn finish_dump(&mut self) -> Result<(), NlError> {
+        while self.dump {
+            if let Err(err) = self.next_message(Some(self.seq)).await {
+                self.dump = false;
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+
+    /// Finds the next message with the sequence number in the buffer, receiving
+    /// more when needed. Any sequence number matches if it's `None`.
+    async fn next_message(&mut self, seq: Option<u32>) -> Result<Range<usize>, NlError> {
+        loop {
+            if self.pos >= self.len {
+                self.recv_datagram().await.map_err(io_error)?;
+            }
+
+            let remaining = &self.buf[self.pos..self.len];
+            let message_len = remaining
+                .get(..4)
+                .map(|len| u32::from_ne_bytes(len.try_into().unwrap()) as usize)
+                .filter(|&len| NLMSG_HDRLEN <= len && len <= remaining.len());
+            let message_len = match message_len {
+                Some(len) => len,
+                None => {
+                    self.pos = self.len;
+                    return Err(NlError::new("Received a truncated netlink message"));
+                }
+            };
+
+            let message = &remaining[..message_len];
+            let nl_type = u16::from_ne_bytes(message[4..6].try_into().unwrap());
+            let message_seq = u32::from_ne_bytes(message[8..12].try_into().unwrap());
+
+            let start = self.pos;
+            let aligned_len = (message_len + NLMSG_ALIGNTO - 1) & !(NLMSG_ALIGNTO - 1);
+            self.pos = (self.pos + aligned_len).min(self.len);
+
+            if seq.filter(|&seq| seq != message_seq).is_some() {
+                // Left over from a request that was cancelled.
+                continue;
+            }
+            if message_seq == self.seq
+                && matches!(i32::from(nl_type), libc::NLMSG_DONE | libc::NLMSG_ERROR)
+            {
+                self.dump = false;
+            }
+
+            return Ok(start..start + message_len);
+        }
+    }
+
+    /// Receives the acknowledgement of the last request sent.
+    pub async fn recv_ack<E>(&mut self) -> Result<(), E>
+    where
+        E: From<NlError> + From<KernelError>,
+    {
+        match self.recv_nl::<u16, Vec<u8>>().await? {
+            NlResponse::Ack => Ok(()),
+            NlResponse::Error(error) => Err(error.into()),
+            NlResponse::Message(_) | NlResponse::Done => Err(NlError::NoAck.into()),
+        }
+    }
+
+    async fn recv_datagram(&mut self) -> io::Result<()> {
+        let Self { fd, buf, .. } = self;
+        let len = loop {
+            let mut guard = fd.readable_mut().await?;
+            match guard.try_io(|fd| fd.get_mut().recv(&mut buf[..], 0)) {
+                Ok(result) => break result?,
+                Err(_would_block) => continue,
+            }
+        };
+
+        self.len = len as usize;
+        self.pos = 0;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::linux::socket::list_device_names_utils::get_list_device_names_msg;
+    use neli::consts::{Ifla, Nlmsg};
+    use neli::rtnl::Ifinfomsg;
+
+    type LinkResponse = NlResponse<Nlmsg, Ifinfomsg<Ifla>>;
+
+    #[tokio::test]
+    async fn cancelled_dump_is_finished_before_next_request() -> anyhow::Result<()> {
+        let mut sock = AsyncNlSocket::connect(NlFamily::Route)?;
+
+        // Read only the first link, the same as a future dropped mid-dump.
+        sock.send_nl(get_list_device_names_msg()).await?;
+        let response: LinkResponse = sock.recv_nl().await?;
+        assert!(matches!(response, NlResponse::Message(_)));
+        assert!(sock.dump);
+
+        sock.send_nl(get_list_device_names_msg()).await?;
+        let mut links = 0;
+        loop {
+            match sock.recv_nl().await? {
+                LinkResponse::Message(_) => links += 1,
+                LinkResponse::Done => break,
+                LinkResponse::Ack => panic!("Expected the end of the dump, got an ack"),
+                LinkResponse::Error(error) => return Err(error.into()),
+            }
+        }
+
+        // At least the loopback interface exists.
+        assert!(links > 0);
+        assert!(!sock.dump);
+        Ok(())
+    }
+}
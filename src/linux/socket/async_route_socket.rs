@@ -0,0 +1,322 @@
+use super::address_message::{address_message, get_addresses_msg, parse_ifaddrmsg};
+use super::async_nl_socket::{AsyncNlSocket, NlResponse};
+use super::link_message::{create_rtattr, get_link_message, link_in_netns_message, netns_rtattr};
+use super::link_message::{parse_link, set_link_message};
+use super::list_device_names_utils;
+use super::route_message::{get_routes_msg, parse_rtmsg, route_message};
+use super::WireGuardDeviceLinkOperation;
+use super::{link_message, AddressOperation, RouteOperation};
+use crate::address::Address;
+use crate::err::{AddressError, ConnectError, LinkDeviceError, LinkError, ListDevicesError};
+use crate::err::{KernelError, RouteError};
+use crate::get;
+use crate::link::Link;
+use crate::netns::NetNs;
+use crate::route::Route;
+use crate::DeviceInterface;
+use list_device_names_utils::PotentialWireGuardDeviceName;
+use neli::consts::{Ifa, Ifla, NlFamily, Nlmsg, Rta};
+use neli::err::NlError;
+use neli::rtnl::{Ifaddrmsg, Ifinfomsg, Rtattr, Rtmsg};
+use std::os::unix::io::AsRawFd;
+
+/// The async counterpart of [`RouteSocket`](crate::RouteSocket). Requires the
+/// `tokio` feature.
+///
+/// Operations can be cancelled by dropping their future, for example with
+/// `tokio::time::timeout`. The socket stays usable afterwards. The rest of a
+/// cancelled `list_*` call is read and discarded before the next request is
+/// sent, since the kernel refuses a new dump until the last one was read.
+pub struct AsyncRouteSocket {
+    sock: AsyncNlSocket,
+}
+
+impl AsyncRouteSocket {
+    pub async fn connect() -> Result<Self, ConnectError> {
+        let sock = AsyncNlSocket::connect(NlFamily::Route)?;
+        Ok(Self { sock })
+    }
+
+    pub async fn connect_netns(netns: &NetNs) -> Result<Self, ConnectError> {
+        let sock = AsyncNlSocket::connect_netns(NlFamily::Route, netns)?;
+        Ok(Self { sock })
+    }
+
+    pub async fn add_device(&mut self, ifname: &str) -> Result<(), LinkDeviceError> {
+        let operation = WireGuardDeviceLinkOperation::Add;
+        self.sock.send_nl(link_message(ifname, operation)?).await?;
+        self.sock.recv_ack().await
+    }
+
+    pub async fn add_device_in_netns(
+        &mut self,
+        ifname: &str,
+        netns: &NetNs,
+    ) -> Result<(), LinkDeviceError> {
+        let message = link_in_netns_message(ifname, netns.as_raw_fd())?;
+        self.sock.send_nl(message).await?;
+        self.sock.recv_ack().await
+    }
+
+    pub async fn del_device(&mut self, ifname: &str) -> Result<(), LinkDeviceError> {
+        let operation = WireGuardDeviceLinkOperation::Delete;
+        self.sock.send_nl(link_message(ifname, operation)?).await?;
+        self.sock.recv_ack().await
+    }
+
+    /// See [`RouteSocket::list_device_names`](crate::RouteSocket::list_device_names).
+    pub async fn list_device_names(&mut self) -> Result<Vec<String>, ListDevicesError> {
+        self.sock
+            .send_nl(list_device_names_utils::get_list_device_names_msg())
+            .await?;
+
+        let mut result_names = vec![];
+
+        loop {
+            let response = match self.sock.recv_nl::<Nlmsg, Ifinfomsg<Ifla>>().await? {
+                NlResponse::Message(response) => response,
+                NlResponse::Done | NlResponse::Ack => break,
+                NlResponse::Error(error) => return Err(error.into()),
+            };
+
+            let PotentialWireGuardDeviceName {
+                is_wireguard,
+                ifname,
+            } = list_device_names_utils::parse_ifinfomsg(response.nl_payload)?;
+
+            if is_wireguard {
+                if let Some(ifname) = ifname {
+                    result_names.push(ifname);
+                }
+            }
+        }
+
+        Ok(result_names)
+    }
+
+    /// See [`RouteSocket::list_devices`](crate::RouteSocket::list_devices).
+    pub async fn list_devices(&mut self) -> Result<Vec<Link>, ListDevicesError> {
+        self.sock
+            .send_nl(list_device_names_utils::get_list_device_names_msg())
+            .await?;
+
+        let mut links = vec![];
+        loop {
+            let response = match self.sock.recv_nl::<Nlmsg, Ifinfomsg<Ifla>>().await? {
+                NlResponse::Message(response) => response,
+                NlResponse::Done | NlResponse::Ack => break,
+                NlResponse::Error(error) => return Err(error.into()),
+            };
+
+            if list_device_names_utils::is_wireguard_link(&response.nl_payload)? {
+                links.push(parse_link(response.nl_payload)?);
+            }
+        }
+
+        Ok(links)
+    }
+
+    pub async fn get_link(&mut self, interface: DeviceInterface<'_>) -> Result<Link, LinkError> {
+        let infomsg = self.query_link::<LinkError>(&interface).await?;
+        Ok(parse_link(infomsg)?)
+    }
+
+    /// See [`RouteSocket::set_link_up`](crate::RouteSocket::set_link_up).
+    pub async fn set_link_up(&mut self, interface: DeviceInterface<'_>) -> Result<(), LinkError> {
+        self.update_link(interface, Some(true), vec![]).await
+    }
+
+    pub async fn set_link_down(&mut self, interface: DeviceInterface<'_>) -> Result<(), LinkError> {
+        self.update_link(interface, Some(false), vec![]).await
+    }
+
+    pub async fn set_mtu(
+        &mut self,
+        interface: DeviceInterface<'_>,
+        mtu: u32,
+    ) -> Result<(), LinkError> {
+        let mtu = create_rtattr(Ifla::Mtu, mtu.to_ne_bytes().to_vec());
+        self.update_link(interface, None, vec![mtu]).await
+    }
+
+    pub async fn set_txqueuelen(
+        &mut self,
+        interface: DeviceInterface<'_>,
+        txqueuelen: u32,
+    ) -> Result<(), LinkError> {
+        let rta_type = Ifla::UnrecognizedVariant(libc::IFLA_TXQLEN);
+        let txqueuelen = create_rtattr(rta_type, txqueuelen.to_ne_bytes().to_vec());
+        self.update_link(interface, None, vec![txqueuelen]).await
+    }
+
+    pub async fn set_link_netns(
+        &mut self,
+        interface: DeviceInterface<'_>,
+        netns: &NetNs,
+    ) -> Result<(), LinkError> {
+        let netns = netns_rtattr(netns.as_raw_fd());
+        self.update_link(interface, None, vec![netns]).await
+    }
+
+    async fn update_link(
+        &mut self,
+        interface: DeviceInterface<'_>,
+        up: Option<bool>,
+        rtattrs: Vec<Rtattr<Ifla, Vec<u8>>>,
+    ) -> Result<(), LinkError> {
+        self.sock
+            .send_nl(set_link_message(&interface, up, rtattrs)?)
+            .await?;
+        self.sock.recv_ack().await
+    }
+
+    /// See [`RouteSocket::add_route`](crate::RouteSocket::add_route).
+    pub async fn add_route(&mut self, route: &Route) -> Result<(), RouteError> {
+        self.sock
+            .send_nl(route_message(route, RouteOperation::Add))
+            .await?;
+        self.sock.recv_ack().await
+    }
+
+    /// See [`RouteSocket::replace_route`](crate::RouteSocket::replace_route).
+    pub async fn replace_route(&mut self, route: &Route) -> Result<(), RouteError> {
+        self.sock
+            .send_nl(route_message(route, RouteOperation::Replace))
+            .await?;
+        self.sock.recv_ack().await
+    }
+
+    pub async fn del_route(&mut self, route: &Route) -> Result<(), RouteError> {
+        self.sock
+            .send_nl(route_message(route, RouteOperation::Delete))
+            .await?;
+        self.sock.recv_ack().await
+    }
+
+    /// See [`RouteSocket::list_routes`](crate::RouteSocket::list_routes).
+    pub async fn list_routes(&mut self, ifindex: u32) -> Result<Vec<Route>, RouteError> {
+        self.sock.send_nl(get_routes_msg()).await?;
+
+        let mut routes = vec![];
+        loop {
+            let response = match self.sock.recv_nl::<Nlmsg, Rtmsg<Rta>>().await? {
+                NlResponse::Message(response) => response,
+                NlResponse::Done | NlResponse::Ack => break,
+                NlResponse::Error(error) => return Err(error.into()),
+            };
+
+            if let Some(route) = parse_rtmsg(response.nl_payload)? {
+                if route.ifindex == ifindex {
+                    routes.push(route);
+                }
+            }
+        }
+
+        Ok(routes)
+    }
+
+    /// See [`RouteSocket::add_allowed_ip_routes`](crate::RouteSocket::add_allowed_ip_routes).
+    pub async fn add_allowed_ip_routes(
+        &mut self,
+        device: &get::Device,
+        table: u32,
+        metric: Option<u32>,
+        protocol: u8,
+    ) -> Result<(), RouteError> {
+        for route in Route::for_allowed_ips_in_table(device, table, metric, protocol) {
+            self.add_route(&route).await?;
+        }
+        Ok(())
+    }
+
+    /// See [`RouteSocket::add_address`](crate::RouteSocket::add_address).
+    pub async fn add_address(
+        &mut self,
+        interface: DeviceInterface<'_>,
+        address: &Address,
+    ) -> Result<(), AddressError> {
+        let ifindex = self.resolve_ifindex::<AddressError>(interface).await?;
+        self.sock
+            .send_nl(address_message(ifindex, address, AddressOperation::Add))
+            .await?;
+        self.sock.recv_ack().await
+    }
+
+    pub async fn del_address(
+        &mut self,
+        interface: DeviceInterface<'_>,
+        address: &Address,
+    ) -> Result<(), AddressError> {
+        let ifindex = self.resolve_ifindex::<AddressError>(interface).await?;
+        self.sock
+            .send_nl(address_message(ifindex, address, AddressOperation::Delete))
+            .await?;
+        self.sock.recv_ack().await
+    }
+
+    /// See [`RouteSocket::list_addresses`](crate::RouteSocket::list_addresses).
+    pub async fn list_addresses(
+        &mut self,
+        interface: DeviceInterface<'_>,
+    ) -> Result<Vec<Address>, AddressError> {
+        let ifindex = self.resolve_ifindex::<AddressError>(interface).await?;
+        self.sock.send_nl(get_addresses_msg()).await?;
+
+        let mut addresses = vec![];
+        loop {
+            let response = match self.sock.recv_nl::<Nlmsg, Ifaddrmsg<Ifa>>().await? {
+                NlResponse::Message(response) => response,
+                NlResponse::Done | NlResponse::Ack => break,
+                NlResponse::Error(error) => return Err(error.into()),
+            };
+
+            if let Some((address_ifindex, address)) = parse_ifaddrmsg(response.nl_payload)? {
+                if address_ifindex == ifindex {
+                    addresses.push(address);
+                }
+            }
+        }
+
+        Ok(addresses)
+    }
+
+    /// See [`RouteSocket::flush_addresses`](crate::RouteSocket::flush_addresses).
+    pub async fn flush_addresses(
+        &mut self,
+        interface: DeviceInterface<'_>,
+    ) -> Result<(), AddressError> {
+        let ifindex = self.resolve_ifindex::<AddressError>(interface).await?;
+        let interface = DeviceInterface::from_index(ifindex);
+
+        for address in self.list_addresses(interface.clone()).await?.iter().rev() {
+            self.del_address(interface.clone(), address).await?;
+        }
+        Ok(())
+    }
+
+    async fn resolve_ifindex<E>(&mut self, interface: DeviceInterface<'_>) -> Result<u32, E>
+    where
+        E: From<NlError> + From<KernelError>,
+    {
+        match interface {
+            DeviceInterface::Index(ifindex) => Ok(ifindex),
+            DeviceInterface::Name(_) => {
+                Ok(self.query_link::<E>(&interface).await?.ifi_index as u32)
+            }
+        }
+    }
+
+    async fn query_link<E>(&mut self, interface: &DeviceInterface<'_>) -> Result<Ifinfomsg<Ifla>, E>
+    where
+        E: From<NlError> + From<KernelError>,
+    {
+        self.sock.send_nl(get_link_message(interface)).await?;
+        match self.sock.recv_nl::<Nlmsg, Ifinfomsg<Ifla>>().await? {
+            NlResponse::Message(response) => Ok(response.nl_payload),
+            NlResponse::Error(error) => Err(error.into()),
+            NlResponse::Done | NlResponse::Ack => {
+                Err(NlError::new("Expected a link in the netlink response").into())
+            }
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use super::async_nl_socket::{AsyncNlSocket, NlResponse};
+use super::wg_socket::get_device_message;
+use crate::get;
+use crate::linux::attr::WgDeviceAttribute;
+use crate::linux::cmd::WgCmd;
+use crate::linux::consts::WG_GENL_NAME;
+use crate::linux::err::{ConnectError, GetDeviceError, SetDeviceError};
+use crate::linux::set;
+use crate::linux::set::create_set_device_messages;
+use crate::linux::socket::parse::*;
+use crate::linux::socket::NlWgMsgType;
+use crate::linux::DeviceInterface;
+use crate::netns::NetNs;
+use neli::consts::{CtrlAttr, CtrlCmd, GenlId, NlFamily, NlmF};
+use neli::err::NlError;
+use neli::genl::Genlmsghdr;
+use neli::nl::Nlmsghdr;
+use neli::nlattr::Nlattr;
+
+/// The async counterpart of [`WgSocket`](crate::WgSocket). Requires the `tokio`
+/// feature.
+///
+/// Operations can be cancelled by dropping their future, for example with
+/// `tokio::time::timeout`. The socket stays usable afterwards. The rest of a
+/// cancelled `get_device` or `visit_device` is read and discarded before the
+/// next request is sent, since the kernel refuses a new dump until the last one
+/// was read. Note that a cancelled `set_device` may have applied some of its
+/// changes already.
+pub struct AsyncWgSocket {
+    sock: AsyncNlSocket,
+    family_id: NlWgMsgType,
+}
+
+impl AsyncWgSocket {
+    pub async fn connect() -> Result<Self, ConnectError> {
+        let mut sock = AsyncNlSocket::connect(NlFamily::Generic)?;
+        let family_id = resolve_genl_family(&mut sock, WG_GENL_NAME)
+            .await
+            .map_err(ConnectError::ResolveFamilyError)?;
+
+        Ok(Self { sock, family_id })
+    }
+
+    pub async fn connect_netns(netns: &NetNs) -> Result<Self, ConnectError> {
+        let mut sock = AsyncNlSocket::connect_netns(NlFamily::Generic, netns)?;
+        let family_id = resolve_genl_family(&mut sock, WG_GENL_NAME)
+            .await
+            .map_err(ConnectError::ResolveFamilyError)?;
+
+        Ok(Self { sock, family_id })
+    }
+
+    pub async fn get_device(
+        &mut self,
+        interface: DeviceInterface<'_>,
+    ) -> Result<get::Device, GetDeviceError> {
+        self.sock
+            .send_nl(get_device_message(self.family_id, interface)?)
+            .await?;
+
+        let mut device = None;
+        loop {
+            let response = match self
+                .sock
+                .recv_nl::<NlWgMsgType, Genlmsghdr<WgCmd, WgDeviceAttribute>>()
+                .await?
+            {
+                NlResponse::Message(response) => response,
+                NlResponse::Done | NlResponse::Ack => break,
+                NlResponse::Error(error) => return Err(error.into()),
+            };
+
+            let handle = response.nl_payload.get_attr_handle();
+            device = Some(match device {
+                Some(device) => extend_device(device, handle)?,
+                None => parse_device(handle)?,
+            });
+        }
+
+        device.ok_or(GetDeviceError::AccessError)
+    }
+
+    /// The async counterpart of
+    /// [`WgSocket::get_device_entries`](crate::WgSocket::get_device_entries). Each entry is
+    /// passed to `visit` as soon as it's parsed.
+    pub async fn visit_device<F>(
+        &mut self,
+        interface: DeviceInterface<'_>,
+        mut visit: F,
+    ) -> Result<(), GetDeviceError>
+    where
+        F: FnMut(get::DeviceEntry),
+    {
+        self.sock
+            .send_nl(get_device_message(self.family_id, interface)?)
+            .await?;
+
+        let mut entries = DeviceEntries::default();
+        loop {
+            match self
+                .sock
+                .recv_nl::<NlWgMsgType, Genlmsghdr<WgCmd, WgDeviceAttribute>>()
+                .await?
+            {
+                NlResponse::Message(response) => {
+                    entries.push(response.nl_payload.get_attr_handle())?
+                }
+                NlResponse::Done | NlResponse::Ack => break,
+                NlResponse::Error(error) => return Err(error.into()),
+            };
+
+            while let Some(entry) = entries.pop() {
+                visit(entry);
+            }
+        }
+
+        entries.finish();
+        while let Some(entry) = entries.pop() {
+            visit(entry);
+        }
+
+        match entries.started() {
+            true => Ok(()),
+            false => Err(GetDeviceError::AccessError),
+        }
+    }
+
+    /// See [`WgSocket::set_device`](crate::WgSocket::set_device).
+    pub async fn set_device(&mut self, device: set::Device<'_>) -> Result<(), SetDeviceError> {
+        for nl_message in create_set_device_messages(device, self.family_id)? {
+            self.sock.send_nl(nl_message).await?;
+            self.sock.recv_ack::<SetDeviceError>().await?;
+        }
+
+        Ok(())
+    }
+}
+
+async fn resolve_genl_family(sock: &mut AsyncNlSocket, family_name: &str) -> Result<u16, NlError> {
+    let attrs = vec![Nlattr::new(None, CtrlAttr::FamilyName, family_name)?];
+    let genlhdr = Genlmsghdr::new(CtrlCmd::Getfamily, 2, attrs)?;
+    let flags = vec![NlmF::Request, NlmF::Ack];
+    let nlhdr = Nlmsghdr::new(None, GenlId::Ctrl, flags, None, None, genlhdr);
+    sock.send_nl(nlhdr).await?;
+
+    let response = match sock
+        .recv_nl::<GenlId, Genlmsghdr<CtrlCmd, CtrlAttr>>()
+        .await?
+    {
+        NlResponse::Message(response) => response,
+        NlResponse::Error(error) => return Err(error.into()),
+        NlResponse::Done | NlResponse::Ack => {
+            return Err(NlError::new("Missing generic netlink family"))
+        }
+    };
+    sock.recv_ack::<NlError>().await?;
+
+    let handle = response.nl_payload.get_attr_handle();
+    Ok(handle.get_attr_payload_as::<u16>(CtrlAttr::FamilyId)?)
+}
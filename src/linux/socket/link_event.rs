@@ -0,0 +1,108 @@
+use super::link_message::parse_link;
+use super::list_device_names_utils::is_wireguard_link;
+use crate::err::LinkEventError;
+use crate::link::{Link, LinkEvent};
+use neli::consts::Ifla;
+use neli::rtnl::Ifinfomsg;
+use std::collections::HashSet;
+
+/// The kernel sends RTM_NEWLINK both when a link is added and when it changes. Keeping track of
+/// the links seen so far tells the two apart.
+pub struct LinkEventFilter {
+    known: HashSet<u32>,
+}
+
+impl LinkEventFilter {
+    pub fn new(links: &[Link]) -> Self {
+        Self {
+            known: links.iter().map(|link| link.ifindex).collect(),
+        }
+    }
+
+    /// Returns `None` for messages about links other than WireGuard ones.
+    pub fn filter(
+        &mut self,
+        nl_type: u16,
+        infomsg: Ifinfomsg<Ifla>,
+    ) -> Result<Option<LinkEvent>, LinkEventError> {
+        let ifindex = infomsg.ifi_index as u32;
+        let is_known = self.known.contains(&ifindex);
+        if !is_known && !is_wireguard_link(&infomsg)? {
+            return Ok(None);
+        }
+
+        let link = parse_link(infomsg)?;
+        Ok(match nl_type {
+            libc::RTM_NEWLINK if is_known => Some(LinkEvent::Changed(link)),
+            libc::RTM_NEWLINK => {
+                self.known.insert(ifindex);
+                Some(LinkEvent::Added(link))
+            }
+            libc::RTM_DELLINK => {
+                self.known.remove(&ifindex);
+                Some(LinkEvent::Removed(link))
+            }
+            _ => None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::linux::socket::link_message::create_rtattr;
+    use neli::consts::rtnl::RtAddrFamily;
+    use neli::consts::Arphrd;
+    use neli::nlattr::Nlattr;
+    use neli::{Nl, StreamWriteBuffer};
+
+    /// Builds a link the way the kernel reports it, with null-terminated strings.
+    fn infomsg(ifindex: i32, kind: &str) -> anyhow::Result<Ifinfomsg<Ifla>> {
+        let mut linkinfo = StreamWriteBuffer::new_growable(None);
+        Nlattr::new(
+            None,
+            libc::IFLA_INFO_KIND,
+            format!("{}\0", kind).into_bytes(),
+        )?
+        .serialize(&mut linkinfo)?;
+
+        let rtattrs = vec![
+            create_rtattr(Ifla::Ifname, b"wgtest0\0".to_vec()),
+            create_rtattr(
+                Ifla::UnrecognizedVariant(libc::IFLA_LINKINFO),
+                linkinfo.as_ref().to_vec(),
+            ),
+        ];
+        let family = RtAddrFamily::UnrecognizedVariant(libc::AF_UNSPEC as u8);
+        Ok(Ifinfomsg::new(
+            family,
+            Arphrd::None,
+            ifindex,
+            vec![],
+            rtattrs,
+        ))
+    }
+
+    #[test]
+    fn tells_added_and_changed_links_apart() -> anyhow::Result<()> {
+        let mut filter = LinkEventFilter::new(&[]);
+
+        let event = filter.filter(libc::RTM_NEWLINK, infomsg(7, "wireguard")?)?;
+        assert!(matches!(event, Some(LinkEvent::Added(link)) if link.ifname == "wgtest0"));
+        let event = filter.filter(libc::RTM_NEWLINK, infomsg(7, "wireguard")?)?;
+        assert!(matches!(event, Some(LinkEvent::Changed(_))));
+        let event = filter.filter(libc::RTM_DELLINK, infomsg(7, "wireguard")?)?;
+        assert!(matches!(event, Some(LinkEvent::Removed(_))));
+        let event = filter.filter(libc::RTM_NEWLINK, infomsg(7, "wireguard")?)?;
+        assert!(matches!(event, Some(LinkEvent::Added(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn ignores_other_links() -> anyhow::Result<()> {
+        let mut filter = LinkEventFilter::new(&[]);
+        let event = filter.filter(libc::RTM_NEWLINK, infomsg(7, "dummy")?)?;
+        assert_eq!(event, None);
+        Ok(())
+    }
+}
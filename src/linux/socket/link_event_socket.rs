@@ -0,0 +1,68 @@
+use super::deserialize_payload;
+use super::link_event::LinkEventFilter;
+use crate::err::LinkEventError;
+use crate::link::LinkEvent;
+use crate::RouteSocket;
+use neli::consts::{Ifla, NlFamily};
+use neli::rtnl::Ifinfomsg;
+use neli::socket::NlSocket;
+
+/// Receives an event whenever a WireGuard device interface is added, changed or removed.
+///
+/// ```no_run
+/// use wireguard_uapi::link::LinkEvent;
+/// use wireguard_uapi::LinkEventSocket;
+///
+/// let mut events = LinkEventSocket::connect()?;
+/// for event in events.events() {
+///     if let LinkEvent::Removed(link) = event? {
+///         println!("{} was removed", link.ifname);
+///     }
+/// }
+/// # Ok::<(), wireguard_uapi::err::LinkEventError>(())
+/// ```
+///
+/// The kernel drops events that aren't read quickly enough, after which receiving fails with
+/// `ENOBUFS`. Connect again to start over from the current set of devices.
+pub struct LinkEventSocket {
+    sock: NlSocket,
+    filter: LinkEventFilter,
+}
+
+impl LinkEventSocket {
+    pub fn connect() -> Result<Self, LinkEventError> {
+        let track_seq = false;
+        let mut sock = NlSocket::new(NlFamily::Route, track_seq)?;
+
+        // Autoselect a PID. neli turns the groups into a bitmask before joining them, which only
+        // lines up with the group number for RTNLGRP_LINK since it's 1.
+        let pid = None;
+        let groups = Some(vec![libc::RTNLGRP_LINK]);
+        sock.bind(pid, groups)?;
+
+        // Devices that already exist are reported as changed rather than added. Listing them
+        // after joining the group means none can be missed in between.
+        let links = RouteSocket::connect()?.list_devices()?;
+
+        Ok(Self {
+            sock,
+            filter: LinkEventFilter::new(&links),
+        })
+    }
+
+    /// Blocks until the next event.
+    pub fn recv_event(&mut self) -> Result<LinkEvent, LinkEventError> {
+        loop {
+            let message = self.sock.recv_nl::<u16, Vec<u8>>(None)?;
+            let infomsg = deserialize_payload::<Ifinfomsg<Ifla>>(&message.nl_payload)?;
+            if let Some(event) = self.filter.filter(message.nl_type, infomsg)? {
+                return Ok(event);
+            }
+        }
+    }
+
+    /// An endless iterator over [`LinkEventSocket::recv_event`].
+    pub fn events(&mut self) -> impl Iterator<Item = Result<LinkEvent, LinkEventError>> + '_ {
+        std::iter::from_fn(move || Some(self.recv_event()))
+    }
+}
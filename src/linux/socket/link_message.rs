@@ -1,6 +1,10 @@
+use super::parse::{parse_nla_nul_string, parse_nla_u32, parse_nla_u64, parse_nla_u8};
+use crate::err::ParseAttributeError;
+use crate::link::{Link, LinkStats, OperState};
 use crate::linux::consts::WG_GENL_NAME;
+use crate::DeviceInterface;
 use libc::{IFLA_INFO_KIND, IFLA_LINKINFO};
-use neli::consts::{Arphrd, Ifla, NlmF, Rtm};
+use neli::consts::{Arphrd, Ifla, NlmF, RtaType, Rtm};
 use neli::err::SerError;
 use neli::nl::Nlmsghdr;
 use neli::nlattr::Nlattr;
@@ -8,6 +12,7 @@ use neli::rtnl::Ifinfomsg;
 use neli::rtnl::Rtattr;
 use neli::Nl;
 use neli::StreamWriteBuffer;
+use std::os::unix::io::RawFd;
 
 const RTATTR_HEADER_LEN: libc::c_ushort = 4;
 
@@ -16,7 +21,7 @@ pub enum WireGuardDeviceLinkOperation {
     Delete,
 }
 
-fn create_rtattr(rta_type: Ifla, rta_payload: Vec<u8>) -> Rtattr<Ifla, Vec<u8>> {
+pub(crate) fn create_rtattr<T: RtaType>(rta_type: T, rta_payload: Vec<u8>) -> Rtattr<T, Vec<u8>> {
     let mut rtattr = Rtattr {
         rta_len: 0,
         rta_type,
@@ -80,3 +85,253 @@ pub fn link_message(
 
     Ok(nlmsg)
 }
+
+/// Creates the device inside another network namespace, like
+/// `ip link add wgtest0 netns blue type wireguard`. WireGuard keeps its UDP socket in the
+/// namespace the request was sent from.
+pub fn link_in_netns_message(
+    ifname: &str,
+    netns_fd: RawFd,
+) -> Result<Nlmsghdr<Rtm, Ifinfomsg<Ifla>>, SerError> {
+    let mut nlmsg = link_message(ifname, WireGuardDeviceLinkOperation::Add)?;
+    nlmsg.nl_payload.rtattrs.push(netns_rtattr(netns_fd));
+    nlmsg.nl_len = nlmsg.size() as u32;
+    Ok(nlmsg)
+}
+
+pub fn netns_rtattr(netns_fd: RawFd) -> Rtattr<Ifla, Vec<u8>> {
+    let rta_type = Ifla::UnrecognizedVariant(libc::IFLA_NET_NS_FD);
+    create_rtattr(rta_type, (netns_fd as u32).to_ne_bytes().to_vec())
+}
+
+/// Looks up a single link. The kernel replies with the same message a dump would contain for it.
+pub fn get_link_message(interface: &DeviceInterface) -> Nlmsghdr<Rtm, Ifinfomsg<Ifla>> {
+    let (ifi_index, rtattrs) = match interface {
+        &DeviceInterface::Index(ifindex) => (ifindex as libc::c_int, vec![]),
+        DeviceInterface::Name(ifname) => (
+            0,
+            vec![create_rtattr(Ifla::Ifname, ifname.as_bytes().to_vec())],
+        ),
+    };
+
+    let infomsg = {
+        let ifi_family =
+            neli::consts::rtnl::RtAddrFamily::UnrecognizedVariant(libc::AF_UNSPEC as u8);
+        let ifi_type = Arphrd::Netrom;
+        let ifi_flags = vec![];
+        Ifinfomsg::new(ifi_family, ifi_type, ifi_index, ifi_flags, rtattrs)
+    };
+
+    let len = None;
+    let nl_type = Rtm::Getlink;
+    let flags = vec![NlmF::Request];
+    let seq = None;
+    let pid = None;
+    Nlmsghdr::new(len, nl_type, flags, seq, pid, infomsg)
+}
+
+/// Changes an existing link in a single request. neli's `Ifinfomsg` always sends an
+/// `ifi_change` mask of all ones, which makes the kernel take every flag from the message, so the
+/// `ifinfomsg` header is written out here instead. Only `IFF_UP` is in the mask when `up` is set,
+/// and the mask is empty otherwise.
+pub fn set_link_message(
+    interface: &DeviceInterface,
+    up: Option<bool>,
+    mut rtattrs: Vec<Rtattr<Ifla, Vec<u8>>>,
+) -> Result<Nlmsghdr<Rtm, Vec<u8>>, SerError> {
+    // The kernel looks the link up by IFLA_IFNAME when the index is 0.
+    let ifi_index = match interface {
+        &DeviceInterface::Index(ifindex) => ifindex as libc::c_int,
+        DeviceInterface::Name(ifname) => {
+            rtattrs.push(create_rtattr(Ifla::Ifname, ifname.as_bytes().to_vec()));
+            0
+        }
+    };
+    let (ifi_flags, ifi_change) = match up {
+        Some(true) => (libc::IFF_UP as libc::c_uint, libc::IFF_UP as libc::c_uint),
+        Some(false) => (0, libc::IFF_UP as libc::c_uint),
+        None => (0, 0),
+    };
+
+    let mut payload = StreamWriteBuffer::new_growable(None);
+    (libc::AF_UNSPEC as u8).serialize(&mut payload)?;
+    0u8.serialize(&mut payload)?; // padding
+    Arphrd::Netrom.serialize(&mut payload)?;
+    ifi_index.serialize(&mut payload)?;
+    ifi_flags.serialize(&mut payload)?;
+    ifi_change.serialize(&mut payload)?;
+    rtattrs.serialize(&mut payload)?;
+
+    let len = None;
+    let nl_type = Rtm::Newlink;
+    let flags = vec![NlmF::Request, NlmF::Ack];
+    let seq = None;
+    let pid = None;
+    let payload = payload.as_ref().to_vec();
+    Ok(Nlmsghdr::new(len, nl_type, flags, seq, pid, payload))
+}
+
+pub fn parse_link(infomsg: Ifinfomsg<Ifla>) -> Result<Link, ParseAttributeError> {
+    let mut link = Link {
+        ifindex: infomsg.ifi_index as u32,
+        ifname: String::new(),
+        flags: infomsg
+            .ifi_flags
+            .iter()
+            .fold(0, |flags, flag| flags | u32::from(flag)),
+        mtu: 0,
+        txqueuelen: 0,
+        operstate: OperState::Unknown,
+        master: None,
+        link_netnsid: None,
+        stats: None,
+    };
+
+    for attr in infomsg.rtattrs {
+        match attr.rta_type {
+            Ifla::Ifname => link.ifname = parse_nla_nul_string(&attr.rta_payload)?,
+            Ifla::Mtu => link.mtu = parse_nla_u32(&attr.rta_payload)?,
+            Ifla::UnrecognizedVariant(libc::IFLA_TXQLEN) => {
+                link.txqueuelen = parse_nla_u32(&attr.rta_payload)?
+            }
+            Ifla::UnrecognizedVariant(libc::IFLA_OPERSTATE) => {
+                link.operstate = parse_nla_u8(&attr.rta_payload)?.into()
+            }
+            Ifla::UnrecognizedVariant(libc::IFLA_MASTER) => {
+                link.master = Some(parse_nla_u32(&attr.rta_payload)?)
+            }
+            Ifla::UnrecognizedVariant(libc::IFLA_LINK_NETNSID) => {
+                link.link_netnsid = Some(parse_nla_u32(&attr.rta_payload)? as i32)
+            }
+            Ifla::UnrecognizedVariant(libc::IFLA_STATS64) => {
+                link.stats = Some(parse_link_stats(&attr.rta_payload)?)
+            }
+            _ => {}
+        }
+    }
+
+    Ok(link)
+}
+
+fn parse_link_stats(buf: &[u8]) -> Result<LinkStats, ParseAttributeError> {
+    // Newer kernels append more counters, so only the minimum length is checked.
+    const FIELDS: usize = 10;
+    if buf.len() < FIELDS * 8 {
+        return Err(ParseAttributeError::StaticLengthError {
+            expected: FIELDS * 8,
+            found: buf.len(),
+        });
+    }
+
+    let field = |i: usize| parse_nla_u64(&buf[i * 8..(i + 1) * 8]);
+    Ok(LinkStats {
+        rx_packets: field(0)?,
+        tx_packets: field(1)?,
+        rx_bytes: field(2)?,
+        tx_bytes: field(3)?,
+        rx_errors: field(4)?,
+        tx_errors: field(5)?,
+        rx_dropped: field(6)?,
+        tx_dropped: field(7)?,
+        multicast: field(8)?,
+        collisions: field(9)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use neli::consts::Nlmsg;
+    use neli::StreamReadBuffer;
+
+    fn round_trip<P: Nl>(
+        message: Nlmsghdr<Rtm, P>,
+    ) -> anyhow::Result<Nlmsghdr<Nlmsg, Ifinfomsg<Ifla>>> {
+        let mut buf = StreamWriteBuffer::new_growable(None);
+        message.serialize(&mut buf)?;
+        let message = Nlmsghdr::<Nlmsg, Ifinfomsg<Ifla>>::deserialize(&mut StreamReadBuffer::new(
+            buf.as_ref(),
+        ))?;
+        assert_eq!(message.nl_len as usize, buf.as_ref().len());
+        Ok(message)
+    }
+
+    fn ifi_change(message: &Nlmsghdr<Rtm, Vec<u8>>) -> u32 {
+        let mut change = [0; 4];
+        change.copy_from_slice(&message.nl_payload[12..16]);
+        u32::from_ne_bytes(change)
+    }
+
+    fn ifname(message: &Nlmsghdr<Nlmsg, Ifinfomsg<Ifla>>) -> Option<&[u8]> {
+        message
+            .nl_payload
+            .rtattrs
+            .iter()
+            .find(|attr| attr.rta_type == Ifla::Ifname)
+            .map(|attr| &attr.rta_payload[..])
+    }
+
+    #[test]
+    fn get_link_message_round_trips() -> anyhow::Result<()> {
+        let message = round_trip(get_link_message(&DeviceInterface::from_index(7)))?;
+        assert_eq!(message.nl_payload.ifi_index, 7);
+        assert!(message.nl_payload.rtattrs.is_empty());
+
+        let message = round_trip(get_link_message(&DeviceInterface::from_name("wgtest0")))?;
+        assert_eq!(message.nl_payload.ifi_index, 0);
+        assert_eq!(ifname(&message), Some(&b"wgtest0"[..]));
+        Ok(())
+    }
+
+    #[test]
+    fn set_link_message_only_changes_iff_up() -> anyhow::Result<()> {
+        let interface = DeviceInterface::from_index(7);
+        let mtu = create_rtattr(Ifla::Mtu, 1280u32.to_ne_bytes().to_vec());
+
+        let message = set_link_message(&interface, Some(true), vec![mtu])?;
+        assert_eq!(ifi_change(&message), libc::IFF_UP as u32);
+        let changed = parse_link(round_trip(message)?.nl_payload)?;
+        assert_eq!(changed.ifindex, 7);
+        assert_eq!(changed.flags, libc::IFF_UP as u32);
+        assert_eq!(changed.mtu, 1280);
+
+        let message = set_link_message(&interface, Some(false), vec![])?;
+        assert_eq!(ifi_change(&message), libc::IFF_UP as u32);
+        assert_eq!(parse_link(round_trip(message)?.nl_payload)?.flags, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn set_link_message_without_up_changes_no_flags() -> anyhow::Result<()> {
+        let interface = DeviceInterface::from_name("wgtest0");
+        let message = set_link_message(&interface, None, vec![netns_rtattr(5)])?;
+        assert_eq!(ifi_change(&message), 0);
+
+        let message = round_trip(message)?;
+        assert_eq!(message.nl_payload.ifi_index, 0);
+        let netns_fd = message
+            .nl_payload
+            .rtattrs
+            .iter()
+            .find(|attr| attr.rta_type == Ifla::UnrecognizedVariant(libc::IFLA_NET_NS_FD))
+            .map(|attr| parse_nla_u32(&attr.rta_payload))
+            .transpose()?;
+        assert_eq!(netns_fd, Some(5));
+        assert_eq!(ifname(&message), Some(&b"wgtest0"[..]));
+        Ok(())
+    }
+
+    #[test]
+    fn link_in_netns_message_counts_the_netns_attribute() -> anyhow::Result<()> {
+        let message = round_trip(link_in_netns_message("wgtest0", 5)?)?;
+        let netns_fd = message
+            .nl_payload
+            .rtattrs
+            .iter()
+            .find(|attr| attr.rta_type == Ifla::UnrecognizedVariant(libc::IFLA_NET_NS_FD))
+            .map(|attr| parse_nla_u32(&attr.rta_payload))
+            .transpose()?;
+        assert_eq!(netns_fd, Some(5));
+        Ok(())
+    }
+}
@@ -1,6 +1,6 @@
 use super::parse::parse_nla_nul_string;
 use crate::err::ListDevicesError;
-use neli::consts::{Arphrd, Ifla, NlmF, Nlmsg, Rtm};
+use neli::consts::{Arphrd, Ifla, NlmF, Rtm};
 use neli::nl::Nlmsghdr;
 use neli::rtnl::Ifinfomsg;
 use neli::rtnl::Rtattr;
@@ -34,29 +34,15 @@ pub struct PotentialWireGuardDeviceName {
 }
 
 pub fn parse_ifinfomsg(
-    response: Nlmsghdr<Nlmsg, Ifinfomsg<Ifla>>,
+    infomsg: Ifinfomsg<Ifla>,
 ) -> Result<PotentialWireGuardDeviceName, ListDevicesError> {
-    let mut is_wireguard = false;
+    let is_wireguard = is_wireguard_link(&infomsg)?;
     let mut ifname: Option<String> = None;
 
-    for attr in response.nl_payload.rtattrs {
-        match attr.rta_type {
-            Ifla::UnrecognizedVariant(libc::IFLA_LINKINFO) => {
-                let mut buf = StreamReadBuffer::new(&attr.rta_payload);
-                let linkinfo = Rtattr::<u16, Vec<u8>>::deserialize(&mut buf)?;
-
-                if linkinfo.rta_type == libc::IFLA_INFO_KIND {
-                    let info_kind = parse_nla_nul_string(&linkinfo.rta_payload)?;
-                    if info_kind == crate::linux::consts::WG_GENL_NAME {
-                        is_wireguard = true;
-                    }
-                }
-            }
-            Ifla::Ifname => {
-                ifname = Some(parse_nla_nul_string(&attr.rta_payload)?);
-            }
-            _ => {}
-        };
+    for attr in infomsg.rtattrs {
+        if attr.rta_type == Ifla::Ifname {
+            ifname = Some(parse_nla_nul_string(&attr.rta_payload)?);
+        }
     }
 
     Ok(PotentialWireGuardDeviceName {
@@ -64,3 +50,20 @@ pub fn parse_ifinfomsg(
         is_wireguard,
     })
 }
+
+/// Checks for "wireguard" as the [IFLA_INFO_KIND](libc::IFLA_INFO_KIND) value.
+pub fn is_wireguard_link(infomsg: &Ifinfomsg<Ifla>) -> Result<bool, ListDevicesError> {
+    for attr in &infomsg.rtattrs {
+        if attr.rta_type == Ifla::UnrecognizedVariant(libc::IFLA_LINKINFO) {
+            let mut buf = StreamReadBuffer::new(&attr.rta_payload);
+            let linkinfo = Rtattr::<u16, Vec<u8>>::deserialize(&mut buf)?;
+
+            if linkinfo.rta_type == libc::IFLA_INFO_KIND {
+                let info_kind = parse_nla_nul_string(&linkinfo.rta_payload)?;
+                return Ok(info_kind == crate::linux::consts::WG_GENL_NAME);
+            }
+        }
+    }
+
+    Ok(false)
+}
@@ -4,11 +4,51 @@ pub use route_socket::RouteSocket;
 mod wg_socket;
 pub use wg_socket::WgSocket;
 
+mod link_event_socket;
+pub use link_event_socket::LinkEventSocket;
+
+#[cfg(feature = "tokio")]
+mod async_link_event_socket;
+#[cfg(feature = "tokio")]
+pub use async_link_event_socket::AsyncLinkEventSocket;
+#[cfg(feature = "tokio")]
+mod async_nl_socket;
+#[cfg(feature = "tokio")]
+mod async_route_socket;
+#[cfg(feature = "tokio")]
+pub use async_route_socket::AsyncRouteSocket;
+#[cfg(feature = "tokio")]
+mod async_wg_socket;
+#[cfg(feature = "tokio")]
+pub use async_wg_socket::AsyncWgSocket;
+
 pub(crate) mod parse;
 
 pub(crate) type NlWgMsgType = u16;
 
+/// `NlError`'s own conversion from `io::Error` loses the error message.
+pub(crate) fn io_error(err: std::io::Error) -> neli::err::NlError {
+    neli::err::NlError::Msg(err.to_string())
+}
+
+/// Deserializes the payload of a message that was received as raw bytes.
+pub(crate) fn deserialize_payload<P: neli::Nl>(payload: &[u8]) -> Result<P, neli::err::DeError> {
+    let mut buf = neli::StreamReadBuffer::new(payload);
+    buf.set_size_hint(payload.len());
+    P::deserialize(&mut buf)
+}
+
+pub(crate) mod address_message;
+pub(crate) use address_message::AddressOperation;
+
+pub(crate) mod link_event;
+
 pub(crate) mod link_message;
 pub(crate) use link_message::{link_message, WireGuardDeviceLinkOperation};
 
 pub(crate) mod list_device_names_utils;
+
+pub(crate) mod nlmsgerr;
+
+pub(crate) mod route_message;
+pub(crate) use route_message::RouteOperation;
@@ -0,0 +1,123 @@
+use crate::err::KernelError;
+use neli::consts::NlmF;
+use neli::err::NlError;
+use neli::socket::NlSocket;
+use std::convert::TryInto;
+use std::os::unix::io::AsRawFd;
+
+const NLMSG_HDRLEN: usize = 16;
+const NLMSG_ALIGNTO: usize = 4;
+const NLMSGERR_ATTR_MSG: u16 = 1;
+
+/// Asks the kernel to explain errors with an extended acknowledgement message, and to leave the
+/// request out of error responses other than its header. Kernels older than 4.12 don't support
+/// the former, in which case errors only carry an errno.
+pub fn enable_ext_ack(sock: &NlSocket) {
+    for option in &[libc::NETLINK_EXT_ACK, libc::NETLINK_CAP_ACK] {
+        let enable: libc::c_int = 1;
+        unsafe {
+            libc::setsockopt(
+                sock.as_raw_fd(),
+                libc::SOL_NETLINK,
+                *option,
+                &enable as *const libc::c_int as *const libc::c_void,
+                std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+            );
+        }
+    }
+}
+
+pub fn nl_flags(flags: &[NlmF]) -> u16 {
+    flags.iter().fold(0, |flags, flag| flags | u16::from(flag))
+}
+
+/// Parses the payload of an `NLMSG_ERROR` message, which is a `struct nlmsgerr` optionally
+/// followed by attributes. Returns `None` for acknowledgements.
+pub fn parse_nlmsgerr(flags: u16, payload: &[u8]) -> Result<Option<KernelError>, NlError> {
+    let truncated = || NlError::new("Received a truncated netlink error");
+    let errno = payload
+        .get(..4)
+        .map(|error| -i32::from_ne_bytes(error.try_into().unwrap()))
+        .ok_or_else(truncated)?;
+    if errno == 0 {
+        return Ok(None);
+    }
+
+    let mut message = None;
+    if flags & libc::NLM_F_ACK_TLVS as u16 != 0 {
+        // The request follows the error, in full unless it was capped to its header.
+        let request_len = match flags & libc::NLM_F_CAPPED as u16 {
+            0 => payload
+                .get(4..8)
+                .map(|len| u32::from_ne_bytes(len.try_into().unwrap()) as usize)
+                .ok_or_else(truncated)?,
+            _ => NLMSG_HDRLEN,
+        };
+
+        let mut attrs = payload.get(align(4 + request_len)..).unwrap_or(&[]);
+        while attrs.len() >= 4 {
+            let len = u16::from_ne_bytes(attrs[..2].try_into().unwrap()) as usize;
+            let attr_type = u16::from_ne_bytes(attrs[2..4].try_into().unwrap());
+            if len < 4 || len > attrs.len() {
+                break;
+            }
+            if attr_type == NLMSGERR_ATTR_MSG {
+                let text = &attrs[4..len];
+                let text = text.split(|&byte| byte == 0).next().unwrap_or(text);
+                message = Some(String::from_utf8_lossy(text).into_owned());
+            }
+            attrs = attrs.get(align(len)..).unwrap_or(&[]);
+        }
+    }
+
+    Ok(Some(KernelError { errno, message }))
+}
+
+fn align(len: usize) -> usize {
+    (len + NLMSG_ALIGNTO - 1) & !(NLMSG_ALIGNTO - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nlmsgerr(error: i32, request_len: u32, attrs: &[u8]) -> Vec<u8> {
+        let mut payload = error.to_ne_bytes().to_vec();
+        payload.extend_from_slice(&request_len.to_ne_bytes());
+        payload.resize(4 + NLMSG_HDRLEN, 0);
+        payload.extend_from_slice(attrs);
+        payload
+    }
+
+    #[test]
+    fn parses_errno_and_extended_ack_message() -> anyhow::Result<()> {
+        let text = b"Invalid key\0";
+        let mut attr = ((4 + text.len()) as u16).to_ne_bytes().to_vec();
+        attr.extend_from_slice(&NLMSGERR_ATTR_MSG.to_ne_bytes());
+        attr.extend_from_slice(text);
+
+        let flags = (libc::NLM_F_CAPPED | libc::NLM_F_ACK_TLVS) as u16;
+        let payload = nlmsgerr(-libc::EINVAL, 1024, &attr);
+        let error = parse_nlmsgerr(flags, &payload)?;
+
+        assert_eq!(
+            error,
+            Some(KernelError {
+                errno: libc::EINVAL,
+                message: Some("Invalid key".to_string()),
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn parses_errno_without_extended_ack() -> anyhow::Result<()> {
+        let payload = nlmsgerr(-libc::ENODEV, NLMSG_HDRLEN as u32, &[]);
+        let error = parse_nlmsgerr(0, &payload)?.unwrap();
+        assert_eq!(error.errno, libc::ENODEV);
+        assert_eq!(error.message, None);
+
+        assert_eq!(parse_nlmsgerr(0, &nlmsgerr(0, 0, &[]))?, None);
+        Ok(())
+    }
+}
@@ -1,11 +1,14 @@
 use crate::err::{ParseAttributeError, ParseDeviceError, ParseIpAddrError, ParseSockAddrError};
-use crate::get::{AllowedIp, AllowedIpBuilder, Device, DeviceBuilder, Peer, PeerBuilder};
+use crate::get::{
+    AllowedIp, AllowedIpBuilder, Device, DeviceBuilder, DeviceEntry, Peer, PeerBuilder,
+};
 use crate::linux::attr::{
     NlaNested, WgAllowedIpAttribute, WgDeviceAttribute, WgPeerAttribute, NLA_TYPE_MASK,
 };
 use libc::{in6_addr, in_addr, AF_INET, AF_INET6};
 use neli::nlattr::AttrHandle;
 use neli::nlattr::Nlattr;
+use std::collections::VecDeque;
 use std::convert::TryFrom;
 use std::mem::size_of;
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
@@ -57,23 +60,7 @@ pub fn extend_device(
     mut device: Device,
     handle: AttrHandle<WgDeviceAttribute>,
 ) -> Result<Device, ParseDeviceError> {
-    let next_peers = {
-        let peers_attr = handle
-            .iter()
-            .find(|attr| attr.nla_type.clone() & NLA_TYPE_MASK == WgDeviceAttribute::Peers)
-            .expect("Unable to find additional peers to coalesce.");
-        let handle = peers_attr.get_nested_attributes::<NlaNested>()?;
-
-        handle
-            .iter()
-            .map(Nlattr::<NlaNested, Vec<u8>>::get_nested_attributes::<WgPeerAttribute>)
-            .map(|handle| {
-                handle
-                    .map_err(|err| err.into())
-                    .and_then(parse_peer_builder)
-            })
-            .collect::<Result<Vec<PeerBuilder>, _>>()?
-    };
+    let next_peers = parse_next_peers(handle)?;
 
     for next_peer in next_peers {
         let matching_last_peer = device
@@ -92,6 +79,90 @@ pub fn extend_device(
     Ok(device)
 }
 
+/// The peers of a message after the first one in a get device response. The first of them may
+/// continue the allowed IPs of the last peer in the previous message, in which case only its
+/// public key and allowed IPs are present.
+fn parse_next_peers(
+    handle: AttrHandle<WgDeviceAttribute>,
+) -> Result<Vec<PeerBuilder>, ParseDeviceError> {
+    let peers_attr = handle
+        .iter()
+        .find(|attr| attr.nla_type.clone() & NLA_TYPE_MASK == WgDeviceAttribute::Peers);
+    let handle = match peers_attr {
+        Some(peers_attr) => peers_attr.get_nested_attributes::<NlaNested>()?,
+        None => return Ok(vec![]),
+    };
+
+    handle
+        .iter()
+        .map(Nlattr::<NlaNested, Vec<u8>>::get_nested_attributes::<WgPeerAttribute>)
+        .map(|handle| {
+            handle
+                .map_err(|err| err.into())
+                .and_then(parse_peer_builder)
+        })
+        .collect()
+}
+
+/// Parses the messages of a get device response into [`DeviceEntry`] values as they arrive. The
+/// last peer of each message is held back until the next one shows whether it continues, same as
+/// [`extend_device`].
+#[derive(Default)]
+pub struct DeviceEntries {
+    started: bool,
+    ready: VecDeque<DeviceEntry>,
+    last_peer: Option<Peer>,
+}
+
+impl DeviceEntries {
+    pub fn push(&mut self, handle: AttrHandle<WgDeviceAttribute>) -> Result<(), ParseDeviceError> {
+        if !self.started {
+            self.started = true;
+            let mut device = parse_device(handle)?;
+            let peers = std::mem::take(&mut device.peers);
+            self.ready.push_back(DeviceEntry::Device(device));
+            for peer in peers {
+                self.push_peer(peer);
+            }
+            return Ok(());
+        }
+
+        for next_peer in parse_next_peers(handle)? {
+            match self
+                .last_peer
+                .as_mut()
+                .filter(|last_peer| Some(last_peer.public_key) == next_peer.public_key)
+            {
+                Some(last_peer) => last_peer
+                    .allowed_ips
+                    .append(&mut next_peer.allowed_ips.unwrap_or_else(Vec::new)),
+                None => self.push_peer(next_peer.build()?),
+            }
+        }
+        Ok(())
+    }
+
+    /// Releases the held back peer once the response is done.
+    pub fn finish(&mut self) {
+        self.ready
+            .extend(self.last_peer.take().map(DeviceEntry::Peer));
+    }
+
+    pub fn pop(&mut self) -> Option<DeviceEntry> {
+        self.ready.pop_front()
+    }
+
+    /// Whether the response contained a device at all.
+    pub fn started(&self) -> bool {
+        self.started
+    }
+
+    fn push_peer(&mut self, peer: Peer) {
+        self.ready
+            .extend(self.last_peer.replace(peer).map(DeviceEntry::Peer));
+    }
+}
+
 pub fn parse_peers(handle: AttrHandle<NlaNested>) -> Result<Vec<Peer>, ParseDeviceError> {
     let mut peers = vec![];
 
@@ -182,9 +253,8 @@ pub fn parse_allowedip(
                     len if len == size_of::<in_addr>() => IpAddr::V4(parse_in_addr(payload)?),
                     len if len == size_of::<in6_addr>() => IpAddr::V6(parse_in6_addr(payload)?),
                     len => {
-                        return Err(ParseIpAddrError::InvalidIpAddrLengthError { found: len })
-                            .map_err(ParseAttributeError::from)
-                            .map_err(ParseDeviceError::from)
+                        let error = ParseIpAddrError::InvalidIpAddrLengthError { found: len };
+                        return Err(ParseAttributeError::from(error).into());
                     }
                 };
                 allowed_ip_builder.ipaddr(addr);
@@ -225,12 +295,12 @@ create_parse_nla_int!(parse_nla_u64, u64, size_of::<u64>());
 create_parse_nla_int!(parse_nla_i64, i64, size_of::<i64>());
 
 pub fn parse_nla_u16_be(buf: &[u8]) -> Result<u16, ParseAttributeError> {
-    Some(buf.len()).filter(|&len| len == 2).ok_or_else(|| {
-        ParseAttributeError::StaticLengthError {
+    Some(buf.len())
+        .filter(|&len| len == 2)
+        .ok_or(ParseAttributeError::StaticLengthError {
             expected: 2,
             found: buf.len(),
-        }
-    })?;
+        })?;
 
     let mut arr = [0u8; 2];
     arr.copy_from_slice(buf);
@@ -251,17 +321,17 @@ pub fn parse_nla_nul_string(payload: &[u8]) -> Result<String, ParseAttributeErro
     Ok(String::from_utf8(payload)?)
 }
 
-pub fn parse_device_key(buf: &[u8]) -> Result<[u8; 32], ParseAttributeError> {
-    Some(buf.len()).filter(|&len| len == 32).ok_or_else(|| {
-        ParseAttributeError::StaticLengthError {
+pub fn parse_device_key<K: From<[u8; 32]>>(buf: &[u8]) -> Result<K, ParseAttributeError> {
+    Some(buf.len())
+        .filter(|&len| len == 32)
+        .ok_or(ParseAttributeError::StaticLengthError {
             expected: 32,
             found: buf.len(),
-        }
-    })?;
+        })?;
 
     let mut key = [0u8; 32];
     key.copy_from_slice(buf);
-    Ok(key)
+    Ok(key.into())
 }
 
 pub fn parse_sockaddr_in(buf: &[u8]) -> Result<SocketAddr, ParseAttributeError> {
@@ -280,12 +350,12 @@ pub fn parse_sockaddr_in(buf: &[u8]) -> Result<SocketAddr, ParseAttributeError>
 }
 
 pub fn parse_last_handshake_time(buf: &[u8]) -> Result<Duration, ParseAttributeError> {
-    Some(buf.len()).filter(|&len| len == 16).ok_or_else(|| {
-        ParseAttributeError::StaticLengthError {
+    Some(buf.len())
+        .filter(|&len| len == 16)
+        .ok_or(ParseAttributeError::StaticLengthError {
             expected: 16,
             found: buf.len(),
-        }
-    })?;
+        })?;
 
     // WireGuard uses __kernel__timespec for last handshake time.
     // https://git.zx2c4.com/WireGuard/commit/?id=c870c7af53f44a37814dfc76ceb8ad88e290fcd8
@@ -302,23 +372,23 @@ pub fn parse_last_handshake_time(buf: &[u8]) -> Result<Duration, ParseAttributeE
 
 pub fn parse_in_addr(buf: &[u8]) -> Result<Ipv4Addr, ParseAttributeError> {
     // https://linux.die.net/man/7/ip
-    Some(buf.len()).filter(|&len| len == 4).ok_or_else(|| {
-        ParseAttributeError::StaticLengthError {
+    Some(buf.len())
+        .filter(|&len| len == 4)
+        .ok_or(ParseAttributeError::StaticLengthError {
             expected: 4,
             found: buf.len(),
-        }
-    })?;
+        })?;
     Ok(Ipv4Addr::new(buf[0], buf[1], buf[2], buf[3]))
 }
 
 pub fn parse_in6_addr(buf: &[u8]) -> Result<Ipv6Addr, ParseAttributeError> {
     // http://man7.org/linux/man-pages/man7/ipv6.7.html
-    Some(buf.len()).filter(|&len| len == 16).ok_or_else(|| {
-        ParseAttributeError::StaticLengthError {
+    Some(buf.len())
+        .filter(|&len| len == 16)
+        .ok_or(ParseAttributeError::StaticLengthError {
             expected: 16,
             found: buf.len(),
-        }
-    })?;
+        })?;
     Ok(Ipv6Addr::new(
         parse_nla_u16_be(&buf[0..2])?,
         parse_nla_u16_be(&buf[2..4])?,
@@ -334,6 +404,7 @@ pub fn parse_in6_addr(buf: &[u8]) -> Result<Ipv6Addr, ParseAttributeError> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::key::PresharedKey;
     use crate::linux::cmd::WgCmd;
     use anyhow::Error;
     use neli::err::DeError;
@@ -348,20 +419,14 @@ mod tests {
         Ok(Device {
             ifindex: 6,
             ifname: "test".to_string(),
-            private_key: Some(parse_device_key(&base64::decode(
-                "yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk=",
-            )?)?),
-            public_key: Some(parse_device_key(&base64::decode(
-                "HIgo9xNzJMWLKASShiTqIybxZ0U3wGLiUeJ1PKf8ykw=",
-            )?)?),
+            private_key: Some("yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk=".parse()?),
+            public_key: Some("HIgo9xNzJMWLKASShiTqIybxZ0U3wGLiUeJ1PKf8ykw=".parse()?),
             listen_port: 51820,
             fwmark: 0,
             peers: vec![
                 Peer {
-                    public_key: parse_device_key(&base64::decode(
-                        "xTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg",
-                    )?)?,
-                    preshared_key: [0u8; 32],
+                    public_key: "xTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg".parse()?,
+                    preshared_key: PresharedKey::zero(),
                     endpoint: Some("192.95.5.67:1234".parse()?),
                     persistent_keepalive_interval: 0,
                     last_handshake_time: Duration::new(0, 0),
@@ -382,10 +447,8 @@ mod tests {
                     protocol_version: 1,
                 },
                 Peer {
-                    public_key: parse_device_key(&base64::decode(
-                        "TrMvSoP4jYQlY6RIzBgbssQqY3vxI2Pi+y71lOWWXX0=",
-                    )?)?,
-                    preshared_key: [0u8; 32],
+                    public_key: "TrMvSoP4jYQlY6RIzBgbssQqY3vxI2Pi+y71lOWWXX0=".parse()?,
+                    preshared_key: PresharedKey::zero(),
                     endpoint: Some("[2607:5300:60:6b0::c05f:543]:2468".parse()?),
                     persistent_keepalive_interval: 0,
                     last_handshake_time: Duration::new(0, 0),
@@ -758,24 +821,32 @@ mod tests {
         let genlmsghdr = create_test_genlmsghdr(&second_payload)?;
         let device = extend_device(device, genlmsghdr.get_attr_handle())?;
 
+        let mut entries = DeviceEntries::default();
+        for payload in [&first_payload[..], &second_payload[..]].iter() {
+            entries.push(create_test_genlmsghdr(payload)?.get_attr_handle())?;
+        }
+        entries.finish();
+        let mut streamed = match entries.pop() {
+            Some(DeviceEntry::Device(device)) => device,
+            entry => panic!("Expected the device first, got {:?}", entry),
+        };
+        while let Some(DeviceEntry::Peer(peer)) = entries.pop() {
+            streamed.peers.push(peer);
+        }
+        assert_eq!(streamed, device);
+
         assert_eq!(
             device,
             Device {
                 ifindex: 6,
                 ifname: "test".to_string(),
-                private_key: Some(parse_device_key(&base64::decode(
-                    "yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk="
-                )?)?),
-                public_key: Some(parse_device_key(&base64::decode(
-                    "HIgo9xNzJMWLKASShiTqIybxZ0U3wGLiUeJ1PKf8ykw="
-                )?)?),
+                private_key: Some("yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk=".parse()?),
+                public_key: Some("HIgo9xNzJMWLKASShiTqIybxZ0U3wGLiUeJ1PKf8ykw=".parse()?),
                 listen_port: 51820,
                 fwmark: 0,
                 peers: vec![Peer {
-                    public_key: parse_device_key(&base64::decode(
-                        "xTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg="
-                    )?)?,
-                    preshared_key: [0u8; 32],
+                    public_key: "xTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg=".parse()?,
+                    preshared_key: PresharedKey::zero(),
                     endpoint: Some("192.95.5.67:1234".parse()?),
                     persistent_keepalive_interval: 0,
                     last_handshake_time: Duration::new(0, 0),
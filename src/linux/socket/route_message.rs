@@ -0,0 +1,160 @@
+use super::link_message::create_rtattr;
+use super::parse::{parse_in6_addr, parse_in_addr, parse_nla_u32};
+use crate::err::RouteError;
+use crate::route::Route;
+use neli::consts::rtnl::RtAddrFamily;
+use neli::consts::{NlmF, RtScope, RtTable, Rta, Rtm, RtmF, Rtn, Rtprot};
+use neli::nl::Nlmsghdr;
+use neli::rtnl::Rtmsg;
+use std::convert::TryInto;
+use std::net::IpAddr;
+
+pub enum RouteOperation {
+    Add,
+    Replace,
+    Delete,
+}
+
+pub fn route_message(route: &Route, operation: RouteOperation) -> Nlmsghdr<Rtm, Rtmsg<Rta>> {
+    let (family, destination) = match route.destination {
+        IpAddr::V4(addr) => (libc::AF_INET, addr.octets().to_vec()),
+        IpAddr::V6(addr) => (libc::AF_INET6, addr.octets().to_vec()),
+    };
+
+    let mut rtattrs = vec![
+        create_rtattr(Rta::Dst, destination),
+        create_rtattr(Rta::Oif, route.ifindex.to_ne_bytes().to_vec()),
+        // rtm_table only fits IDs up to 255. The kernel prefers this attribute when it's set.
+        create_rtattr(Rta::Table, route.table.to_ne_bytes().to_vec()),
+    ];
+    if let Some(metric) = route.metric {
+        rtattrs.push(create_rtattr(Rta::Priority, metric.to_ne_bytes().to_vec()));
+    }
+
+    let rtmsg = Rtmsg {
+        rtm_family: RtAddrFamily::UnrecognizedVariant(family as u8),
+        rtm_dst_len: route.prefix_len,
+        rtm_src_len: 0,
+        rtm_tos: 0,
+        rtm_table: RtTable::from(route.table.try_into().unwrap_or(libc::RT_TABLE_UNSPEC)),
+        rtm_protocol: Rtprot::from(route.protocol),
+        rtm_scope: match operation {
+            RouteOperation::Add | RouteOperation::Replace => RtScope::Link,
+            // Matches a route with any scope, same as `ip route del`.
+            RouteOperation::Delete => RtScope::Nowhere,
+        },
+        rtm_type: Rtn::Unicast,
+        rtm_flags: vec![],
+        rtattrs,
+    };
+
+    let len = None;
+    let nl_type = match operation {
+        RouteOperation::Add | RouteOperation::Replace => Rtm::Newroute,
+        RouteOperation::Delete => Rtm::Delroute,
+    };
+    let flags = match operation {
+        RouteOperation::Add => vec![NlmF::Request, NlmF::Ack, NlmF::Create, NlmF::Excl],
+        RouteOperation::Replace => vec![NlmF::Request, NlmF::Ack, NlmF::Create, NlmF::Replace],
+        RouteOperation::Delete => vec![NlmF::Request, NlmF::Ack],
+    };
+    let seq = None;
+    let pid = None;
+    Nlmsghdr::new(len, nl_type, flags, seq, pid, rtmsg)
+}
+
+pub fn get_routes_msg() -> Nlmsghdr<Rtm, Rtmsg<Rta>> {
+    let rtmsg = Rtmsg {
+        // Dumps both IPv4 and IPv6 routes.
+        rtm_family: RtAddrFamily::UnrecognizedVariant(libc::AF_UNSPEC as u8),
+        rtm_dst_len: 0,
+        rtm_src_len: 0,
+        rtm_tos: 0,
+        rtm_table: RtTable::Unspec,
+        rtm_protocol: Rtprot::Unspec,
+        rtm_scope: RtScope::Universe,
+        rtm_type: Rtn::Unspec,
+        rtm_flags: vec![],
+        rtattrs: vec![],
+    };
+
+    let len = None;
+    let nl_type = Rtm::Getroute;
+    let flags = vec![NlmF::Request, NlmF::Dump];
+    let seq = None;
+    let pid = None;
+    Nlmsghdr::new(len, nl_type, flags, seq, pid, rtmsg)
+}
+
+/// Returns `None` for routes that can't be expressed as a [`Route`], such as local, broadcast,
+/// multipath and cached routes.
+pub fn parse_rtmsg(rtmsg: Rtmsg<Rta>) -> Result<Option<Route>, RouteError> {
+    let family = u8::from(rtmsg.rtm_family);
+    if rtmsg.rtm_type != Rtn::Unicast || rtmsg.rtm_flags.contains(&RtmF::Cloned) {
+        return Ok(None);
+    }
+
+    let mut destination = match i32::from(family) {
+        libc::AF_INET => IpAddr::from([0; 4]),
+        libc::AF_INET6 => IpAddr::from([0; 16]),
+        _ => return Ok(None),
+    };
+    let mut ifindex = None;
+    let mut table = u32::from(u8::from(rtmsg.rtm_table));
+    let mut metric = None;
+    let prefix_len = rtmsg.rtm_dst_len;
+    let protocol = u8::from(rtmsg.rtm_protocol);
+
+    for attr in rtmsg.rtattrs {
+        let payload = &attr.rta_payload[..];
+        match attr.rta_type {
+            Rta::Dst => {
+                destination = match destination {
+                    IpAddr::V4(_) => parse_in_addr(payload)?.into(),
+                    IpAddr::V6(_) => parse_in6_addr(payload)?.into(),
+                }
+            }
+            Rta::Oif => ifindex = Some(parse_nla_u32(payload)?),
+            Rta::Table => table = parse_nla_u32(payload)?,
+            Rta::Priority => metric = Some(parse_nla_u32(payload)?),
+            _ => {}
+        }
+    }
+
+    Ok(ifindex.map(|ifindex| Route {
+        ifindex,
+        destination,
+        prefix_len,
+        table,
+        metric,
+        protocol,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use neli::consts::Nlmsg;
+    use neli::{Nl, StreamReadBuffer, StreamWriteBuffer};
+
+    fn round_trip(route: &Route) -> anyhow::Result<Option<Route>> {
+        let mut buf = StreamWriteBuffer::new_growable(None);
+        route_message(route, RouteOperation::Add).serialize(&mut buf)?;
+        let message =
+            Nlmsghdr::<Nlmsg, Rtmsg<Rta>>::deserialize(&mut StreamReadBuffer::new(buf.as_ref()))?;
+        Ok(parse_rtmsg(message.nl_payload)?)
+    }
+
+    #[test]
+    fn route_message_round_trips() -> anyhow::Result<()> {
+        let route = Route::new(7, "10.24.0.0".parse()?, 16);
+        assert_eq!(round_trip(&route)?, Some(route));
+
+        let route = Route::new(7, "fd00::".parse()?, 64)
+            .table(51820)
+            .metric(100)
+            .protocol(libc::RTPROT_STATIC);
+        assert_eq!(round_trip(&route)?, Some(route));
+        Ok(())
+    }
+}
@@ -1,10 +1,27 @@
+use super::address_message::{address_message, get_addresses_msg, parse_ifaddrmsg};
+use super::link_message::{create_rtattr, get_link_message, link_in_netns_message, netns_rtattr};
+use super::link_message::{parse_link, set_link_message};
 use super::list_device_names_utils;
-use super::{link_message, WireGuardDeviceLinkOperation};
-use crate::err::{ConnectError, LinkDeviceError, ListDevicesError};
+use super::nlmsgerr::{enable_ext_ack, nl_flags, parse_nlmsgerr};
+use super::route_message::{get_routes_msg, parse_rtmsg, route_message};
+use super::WireGuardDeviceLinkOperation;
+use super::{deserialize_payload, link_message, AddressOperation, RouteOperation};
+use crate::address::Address;
+use crate::err::{AddressError, ConnectError, LinkDeviceError, LinkError, ListDevicesError};
+use crate::err::{KernelError, RouteError};
+use crate::get;
+use crate::link::Link;
+use crate::netns::NetNs;
+use crate::route::Route;
+use crate::DeviceInterface;
 use list_device_names_utils::PotentialWireGuardDeviceName;
-use neli::consts::{Ifla, NlFamily, Nlmsg};
-use neli::rtnl::Ifinfomsg;
+use neli::consts::{Ifa, Ifla, NlFamily, Nlmsg, Rta};
+use neli::err::{DeError, NlError};
+use neli::nl::Nlmsghdr;
+use neli::rtnl::{Ifaddrmsg, Ifinfomsg, Rtattr, Rtmsg};
 use neli::socket::NlSocket;
+use neli::Nl;
+use std::os::unix::io::AsRawFd;
 
 pub struct RouteSocket {
     sock: NlSocket,
@@ -19,22 +36,41 @@ impl RouteSocket {
         let pid = None;
         let groups = None;
         sock.bind(pid, groups)?;
+        enable_ext_ack(&sock);
 
         Ok(Self { sock })
     }
 
+    /// Connects to the route netlink of another network namespace. Every request made through
+    /// the socket, including [`RouteSocket::add_device`], applies to that namespace.
+    pub fn connect_netns(netns: &NetNs) -> Result<Self, ConnectError> {
+        netns.run(Self::connect)?
+    }
+
+    /// The device starts out down. See [`RouteSocket::set_link_up`].
     pub fn add_device(&mut self, ifname: &str) -> Result<(), LinkDeviceError> {
         let operation = WireGuardDeviceLinkOperation::Add;
         self.sock.send_nl(link_message(ifname, operation)?)?;
-        self.sock.recv_ack()?;
-        Ok(())
+        self.recv_ack()
+    }
+
+    /// Creates the device directly inside another network namespace. Its UDP socket stays in
+    /// the namespace of this route socket, so a container can be given a WireGuard interface
+    /// that sends its encrypted traffic through the host's network.
+    pub fn add_device_in_netns(
+        &mut self,
+        ifname: &str,
+        netns: &NetNs,
+    ) -> Result<(), LinkDeviceError> {
+        self.sock
+            .send_nl(link_in_netns_message(ifname, netns.as_raw_fd())?)?;
+        self.recv_ack()
     }
 
     pub fn del_device(&mut self, ifname: &str) -> Result<(), LinkDeviceError> {
         let operation = WireGuardDeviceLinkOperation::Delete;
         self.sock.send_nl(link_message(ifname, operation)?)?;
-        self.sock.recv_ack()?;
-        Ok(())
+        self.recv_ack()
     }
 
     /// Retrieves all interface names that have the string "wireguard" as an
@@ -43,21 +79,13 @@ impl RouteSocket {
         self.sock
             .send_nl(list_device_names_utils::get_list_device_names_msg())?;
 
-        let mut iter = self.sock.iter::<Nlmsg, Ifinfomsg<Ifla>>();
-
         let mut result_names = vec![];
 
-        while let Some(Ok(response)) = iter.next() {
-            match response.nl_type {
-                Nlmsg::Error => return Err(ListDevicesError::Unknown),
-                Nlmsg::Done => break,
-                _ => (),
-            };
-
+        for infomsg in self.recv_dump::<Ifinfomsg<Ifla>, ListDevicesError>()? {
             let PotentialWireGuardDeviceName {
                 is_wireguard,
                 ifname,
-            } = list_device_names_utils::parse_ifinfomsg(response)?;
+            } = list_device_names_utils::parse_ifinfomsg(infomsg)?;
 
             if is_wireguard {
                 if let Some(ifname) = ifname {
@@ -68,4 +96,255 @@ impl RouteSocket {
 
         Ok(result_names)
     }
+
+    /// Retrieves the link state of every WireGuard device interface. See
+    /// [`RouteSocket::list_device_names`] for how they're recognized.
+    pub fn list_devices(&mut self) -> Result<Vec<Link>, ListDevicesError> {
+        self.sock
+            .send_nl(list_device_names_utils::get_list_device_names_msg())?;
+
+        let mut links = vec![];
+        for infomsg in self.recv_dump::<Ifinfomsg<Ifla>, ListDevicesError>()? {
+            if list_device_names_utils::is_wireguard_link(&infomsg)? {
+                links.push(parse_link(infomsg)?);
+            }
+        }
+
+        Ok(links)
+    }
+
+    pub fn get_link(&mut self, interface: DeviceInterface) -> Result<Link, LinkError> {
+        let infomsg = self.query_link::<LinkError>(&interface)?;
+        Ok(parse_link(infomsg)?)
+    }
+
+    /// Brings the device interface up, like `ip link set up dev wgtest0`. Devices created with
+    /// [`RouteSocket::add_device`] start out down.
+    pub fn set_link_up(&mut self, interface: DeviceInterface) -> Result<(), LinkError> {
+        self.update_link(interface, Some(true), vec![])
+    }
+
+    pub fn set_link_down(&mut self, interface: DeviceInterface) -> Result<(), LinkError> {
+        self.update_link(interface, Some(false), vec![])
+    }
+
+    pub fn set_mtu(&mut self, interface: DeviceInterface, mtu: u32) -> Result<(), LinkError> {
+        let mtu = create_rtattr(Ifla::Mtu, mtu.to_ne_bytes().to_vec());
+        self.update_link(interface, None, vec![mtu])
+    }
+
+    pub fn set_txqueuelen(
+        &mut self,
+        interface: DeviceInterface,
+        txqueuelen: u32,
+    ) -> Result<(), LinkError> {
+        let rta_type = Ifla::UnrecognizedVariant(libc::IFLA_TXQLEN);
+        let txqueuelen = create_rtattr(rta_type, txqueuelen.to_ne_bytes().to_vec());
+        self.update_link(interface, None, vec![txqueuelen])
+    }
+
+    /// Moves the device interface into another network namespace, like
+    /// `ip link set wgtest0 netns blue`. A WireGuard device keeps its UDP socket in the namespace
+    /// it was created in. The link stays up if it was up.
+    pub fn set_link_netns(
+        &mut self,
+        interface: DeviceInterface,
+        netns: &NetNs,
+    ) -> Result<(), LinkError> {
+        self.update_link(interface, None, vec![netns_rtattr(netns.as_raw_fd())])
+    }
+
+    fn update_link(
+        &mut self,
+        interface: DeviceInterface,
+        up: Option<bool>,
+        rtattrs: Vec<Rtattr<Ifla, Vec<u8>>>,
+    ) -> Result<(), LinkError> {
+        self.sock
+            .send_nl(set_link_message(&interface, up, rtattrs)?)?;
+        self.recv_ack()
+    }
+
+    /// Fails if the route already exists.
+    pub fn add_route(&mut self, route: &Route) -> Result<(), RouteError> {
+        self.sock
+            .send_nl(route_message(route, RouteOperation::Add))?;
+        self.recv_ack()
+    }
+
+    /// Adds the route, replacing any existing route to the same destination in the same table.
+    pub fn replace_route(&mut self, route: &Route) -> Result<(), RouteError> {
+        self.sock
+            .send_nl(route_message(route, RouteOperation::Replace))?;
+        self.recv_ack()
+    }
+
+    pub fn del_route(&mut self, route: &Route) -> Result<(), RouteError> {
+        self.sock
+            .send_nl(route_message(route, RouteOperation::Delete))?;
+        self.recv_ack()
+    }
+
+    /// Retrieves the IPv4 and IPv6 unicast routes through the device interface from every table.
+    pub fn list_routes(&mut self, ifindex: u32) -> Result<Vec<Route>, RouteError> {
+        self.sock.send_nl(get_routes_msg())?;
+
+        let mut routes = vec![];
+        for rtmsg in self.recv_dump::<Rtmsg<Rta>, RouteError>()? {
+            if let Some(route) = parse_rtmsg(rtmsg)? {
+                if route.ifindex == ifindex {
+                    routes.push(route);
+                }
+            }
+        }
+
+        Ok(routes)
+    }
+
+    /// Makes the peers of the device reachable at their allowed IPs by adding a route for each of
+    /// them to the table, with the metric and `RTPROT_*` protocol. Use `libc::RT_TABLE_MAIN` and
+    /// `libc::RTPROT_BOOT` for the same routes as `ip route add`.
+    ///
+    /// Fails if a route to the same destination already exists in the table, for example through
+    /// another interface, rather than taking it over.
+    ///
+    /// **Default routes (`0.0.0.0/0` and `::/0`) are skipped in the main table**, since they'd
+    /// replace the host's own default route. wg-quick instead adds them to a separate table and
+    /// routes everything but the tunnel's own packets there with an fwmark rule. Pass that table
+    /// to add them, or add them yourself with [`RouteSocket::add_route`].
+    pub fn add_allowed_ip_routes(
+        &mut self,
+        device: &get::Device,
+        table: u32,
+        metric: Option<u32>,
+        protocol: u8,
+    ) -> Result<(), RouteError> {
+        for route in Route::for_allowed_ips_in_table(device, table, metric, protocol) {
+            self.add_route(&route)?;
+        }
+        Ok(())
+    }
+
+    /// Fails if the address is already assigned to the device interface.
+    pub fn add_address(
+        &mut self,
+        interface: DeviceInterface,
+        address: &Address,
+    ) -> Result<(), AddressError> {
+        let ifindex = self.resolve_ifindex::<AddressError>(interface)?;
+        self.sock
+            .send_nl(address_message(ifindex, address, AddressOperation::Add))?;
+        self.recv_ack()
+    }
+
+    pub fn del_address(
+        &mut self,
+        interface: DeviceInterface,
+        address: &Address,
+    ) -> Result<(), AddressError> {
+        let ifindex = self.resolve_ifindex::<AddressError>(interface)?;
+        self.sock
+            .send_nl(address_message(ifindex, address, AddressOperation::Delete))?;
+        self.recv_ack()
+    }
+
+    /// Retrieves the IPv4 and IPv6 addresses assigned to the device interface.
+    pub fn list_addresses(
+        &mut self,
+        interface: DeviceInterface,
+    ) -> Result<Vec<Address>, AddressError> {
+        let ifindex = self.resolve_ifindex::<AddressError>(interface)?;
+        self.sock.send_nl(get_addresses_msg())?;
+
+        let mut addresses = vec![];
+        for addrmsg in self.recv_dump::<Ifaddrmsg<Ifa>, AddressError>()? {
+            if let Some((address_ifindex, address)) = parse_ifaddrmsg(addrmsg)? {
+                if address_ifindex == ifindex {
+                    addresses.push(address);
+                }
+            }
+        }
+
+        Ok(addresses)
+    }
+
+    /// Removes every address from the device interface, like `ip address flush dev wgtest0`.
+    pub fn flush_addresses(&mut self, interface: DeviceInterface) -> Result<(), AddressError> {
+        let ifindex = self.resolve_ifindex::<AddressError>(interface)?;
+        let interface = DeviceInterface::from_index(ifindex);
+
+        // Secondary IPv4 addresses are listed after their primary address and are removed along
+        // with it, so they're deleted first.
+        for address in self.list_addresses(interface.clone())?.iter().rev() {
+            self.del_address(interface.clone(), address)?;
+        }
+        Ok(())
+    }
+
+    fn resolve_ifindex<E>(&mut self, interface: DeviceInterface) -> Result<u32, E>
+    where
+        E: From<NlError> + From<DeError> + From<KernelError>,
+    {
+        match interface {
+            DeviceInterface::Index(ifindex) => Ok(ifindex),
+            DeviceInterface::Name(_) => Ok(self.query_link::<E>(&interface)?.ifi_index as u32),
+        }
+    }
+
+    fn query_link<E>(&mut self, interface: &DeviceInterface) -> Result<Ifinfomsg<Ifla>, E>
+    where
+        E: From<NlError> + From<DeError> + From<KernelError>,
+    {
+        self.sock.send_nl(get_link_message(interface))?;
+        let response = self.recv_response::<E>()?;
+        if response.nl_type == Nlmsg::Error {
+            return Err(NlError::new("Expected a link in the netlink response").into());
+        }
+
+        Ok(deserialize_payload(&response.nl_payload)?)
+    }
+
+    fn recv_ack<E>(&mut self) -> Result<(), E>
+    where
+        E: From<NlError> + From<KernelError>,
+    {
+        match self.recv_response::<E>()?.nl_type {
+            Nlmsg::Error => Ok(()),
+            _ => Err(NlError::NoAck.into()),
+        }
+    }
+
+    fn recv_dump<P, E>(&mut self) -> Result<Vec<P>, E>
+    where
+        P: Nl,
+        E: From<NlError> + From<DeError> + From<KernelError>,
+    {
+        let mut payloads = vec![];
+        loop {
+            let response = self.recv_response::<E>()?;
+            match response.nl_type {
+                Nlmsg::Done | Nlmsg::Error => break,
+                _ => payloads.push(deserialize_payload(&response.nl_payload)?),
+            };
+        }
+        Ok(payloads)
+    }
+
+    /// `NlSocket::recv_ack` and `NlSocket::iter` drop the errno the kernel replies with, which is
+    /// most of what there is to know about why a link, route or address couldn't be changed.
+    /// Messages are received as raw bytes here and errors are returned as a [`KernelError`]
+    /// instead.
+    fn recv_response<E>(&mut self) -> Result<Nlmsghdr<Nlmsg, Vec<u8>>, E>
+    where
+        E: From<NlError> + From<KernelError>,
+    {
+        let response = self.sock.recv_nl::<Nlmsg, Vec<u8>>(None)?;
+        if response.nl_type == Nlmsg::Error {
+            let flags = nl_flags(&response.nl_flags);
+            if let Some(error) = parse_nlmsgerr(flags, &response.nl_payload)? {
+                return Err(error.into());
+            }
+        }
+        Ok(response)
+    }
 }
@@ -1,25 +1,37 @@
+use crate::api::{self, ApiError, WireGuardApi};
 use crate::get;
 use crate::linux::attr::WgDeviceAttribute;
 use crate::linux::cmd::WgCmd;
 use crate::linux::consts::{WG_GENL_NAME, WG_GENL_VERSION};
-use crate::linux::err::{ConnectError, GetDeviceError, SetDeviceError};
+use crate::linux::err::{ConnectError, GetDeviceError, ListDevicesError, SetDeviceError};
 use crate::linux::set;
-use crate::linux::set::create_set_device_messages;
+use crate::linux::set::{create_set_device_messages, NlWgMessage};
+use crate::linux::socket::deserialize_payload;
+use crate::linux::socket::nlmsgerr::{enable_ext_ack, nl_flags, parse_nlmsgerr};
 use crate::linux::socket::parse::*;
 use crate::linux::socket::NlWgMsgType;
 use crate::linux::DeviceInterface;
+use crate::netns::NetNs;
+use crate::RouteSocket;
 use libc::IFNAMSIZ;
 use neli::consts::{NlFamily, NlmF, Nlmsg};
+use neli::err::NlError;
 use neli::genl::Genlmsghdr;
 use neli::nl::Nlmsghdr;
 use neli::nlattr::Nlattr;
 use neli::socket::NlSocket;
 use neli::Nl;
-use neli::StreamWriteBuffer;
+use neli::{StreamWriteBuffer, MAX_NL_LENGTH};
+use std::convert::TryInto;
+use zeroize::Zeroizing;
+
+const NLMSG_HDRLEN: usize = 16;
+const NLMSG_ALIGNTO: usize = 4;
 
 pub struct WgSocket {
     sock: NlSocket,
     family_id: NlWgMsgType,
+    buf: RecvBuffer,
 }
 
 impl WgSocket {
@@ -37,62 +49,44 @@ impl WgSocket {
         let pid = None;
         let groups = None;
         wgsock.bind(pid, groups)?;
+        enable_ext_ack(&wgsock);
 
         Ok(Self {
             sock: wgsock,
             family_id,
+            buf: RecvBuffer::new(),
         })
     }
 
+    /// Connects to the WireGuard devices of another network namespace.
+    pub fn connect_netns(netns: &NetNs) -> Result<Self, ConnectError> {
+        netns.run(Self::connect)?
+    }
+
     pub fn get_device(
         &mut self,
         interface: DeviceInterface,
     ) -> Result<get::Device, GetDeviceError> {
-        let mut mem = StreamWriteBuffer::new_growable(None);
-        let attr = match interface {
-            DeviceInterface::Name(name) => {
-                Some(name.len())
-                    .filter(|&len| 0 < len && len < IFNAMSIZ)
-                    .ok_or(GetDeviceError::InvalidInterfaceName)?;
-                name.as_ref().serialize(&mut mem)?;
-                Nlattr::new(None, WgDeviceAttribute::Ifname, mem.as_ref())?
-            }
-            DeviceInterface::Index(index) => {
-                index.serialize(&mut mem)?;
-                Nlattr::new(None, WgDeviceAttribute::Ifindex, mem.as_ref())?
-            }
-        };
-        let genlhdr = {
-            let cmd = WgCmd::GetDevice;
-            let version = WG_GENL_VERSION;
-            let attrs = vec![attr];
-            Genlmsghdr::new(cmd, version, attrs)?
-        };
-        let nlhdr = {
-            let size = None;
-            let nl_type = self.family_id;
-            let flags = vec![NlmF::Request, NlmF::Ack, NlmF::Dump];
-            let seq = None;
-            let pid = None;
-            let payload = genlhdr;
-            Nlmsghdr::new(size, nl_type, flags, seq, pid, payload)
-        };
-
-        self.sock.send_nl(nlhdr)?;
-
-        let mut iter = self
-            .sock
-            .iter::<Nlmsg, Genlmsghdr<WgCmd, WgDeviceAttribute>>();
+        self.sock
+            .send_nl(get_device_message(self.family_id, interface)?)?;
 
         let mut device = None;
-        while let Some(Ok(response)) = iter.next() {
+        loop {
+            let response = self.sock.recv_nl::<Nlmsg, Vec<u8>>(None)?;
+            // The payload may contain private and preshared keys.
+            let payload = Zeroizing::new(response.nl_payload);
             match response.nl_type {
-                Nlmsg::Error => return Err(GetDeviceError::AccessError),
+                Nlmsg::Error => match parse_nlmsgerr(nl_flags(&response.nl_flags), &payload)? {
+                    Some(error) => return Err(error.into()),
+                    None => break,
+                },
                 Nlmsg::Done => break,
                 _ => (),
             };
 
-            let handle = response.nl_payload.get_attr_handle();
+            let genlmsghdr = deserialize_payload::<Genlmsghdr<WgCmd, WgDeviceAttribute>>(&payload)
+                .map_err(NlError::from)?;
+            let handle = genlmsghdr.get_attr_handle();
             device = Some(match device {
                 Some(device) => extend_device(device, handle)?,
                 None => parse_device(handle)?,
@@ -102,13 +96,104 @@ impl WgSocket {
         device.ok_or(GetDeviceError::AccessError)
     }
 
+    /// Retrieves the device like [`WgSocket::get_device`], but yields it as soon as the first
+    /// message of the response arrives, followed by each of its peers. The whole device never has
+    /// to be held in memory, which helps with devices that have many thousands of peers.
+    ///
+    /// Dropping the iterator early reads and discards the rest of the response, so the socket can
+    /// be used again afterwards.
+    ///
+    /// ```no_run
+    /// use wireguard_uapi::get::DeviceEntry;
+    /// use wireguard_uapi::{DeviceInterface, WgSocket};
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let mut wg = WgSocket::connect()?;
+    /// for entry in wg.get_device_entries(DeviceInterface::from_name("wgtest0"))? {
+    ///     match entry? {
+    ///         DeviceEntry::Device(device) => println!("{}", device.ifname),
+    ///         DeviceEntry::Peer(peer) => println!("  {}", peer.public_key),
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn get_device_entries(
+        &mut self,
+        interface: DeviceInterface,
+    ) -> Result<impl Iterator<Item = Result<get::DeviceEntry, GetDeviceError>> + '_, GetDeviceError>
+    {
+        self.device_entries(interface)
+    }
+
+    /// Retrieves every WireGuard device along with its peers, one at a time as the iterator is
+    /// advanced. The kernel can only dump a single device per request, so the devices are listed
+    /// through the route socket first and then requested by index over this socket. The responses
+    /// are all received into the same buffer, which the socket keeps for its lifetime.
+    ///
+    /// A device deleted after it was listed yields an error. The remaining devices can still be
+    /// retrieved by continuing to iterate.
+    ///
+    /// ```no_run
+    /// use wireguard_uapi::{RouteSocket, WgSocket};
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let mut route = RouteSocket::connect()?;
+    /// let mut wg = WgSocket::connect()?;
+    /// for device in wg.get_devices(&mut route)? {
+    ///     let device = device?;
+    ///     println!("{}: {} peers", device.ifname, device.peers.len());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn get_devices(
+        &mut self,
+        route_socket: &mut RouteSocket,
+    ) -> Result<impl Iterator<Item = Result<get::Device, GetDeviceError>> + '_, ListDevicesError>
+    {
+        let links = route_socket.list_devices()?;
+        Ok(links.into_iter().map(move |link| {
+            let mut device = None;
+            for entry in self.device_entries(DeviceInterface::from_index(link.ifindex))? {
+                match entry? {
+                    get::DeviceEntry::Device(first) => device = Some(first),
+                    get::DeviceEntry::Peer(peer) => device
+                        .as_mut()
+                        .ok_or(GetDeviceError::AccessError)?
+                        .peers
+                        .push(peer),
+                }
+            }
+            device.ok_or(GetDeviceError::AccessError)
+        }))
+    }
+
+    fn device_entries(
+        &mut self,
+        interface: DeviceInterface,
+    ) -> Result<DeviceEntryIter<'_>, GetDeviceError> {
+        self.buf.clear();
+        self.sock
+            .send_nl(get_device_message(self.family_id, interface)?)?;
+
+        Ok(DeviceEntryIter {
+            sock: &mut self.sock,
+            buf: &mut self.buf,
+            entries: DeviceEntries::default(),
+            done: false,
+            unread: true,
+        })
+    }
+
     /// This assumes that the device interface has already been created. Otherwise an error will
     /// be returned. You can create a new device interface with
     /// [`RouteSocket::add_device`](./struct.RouteSocket.html#add_device.v).
     ///
     /// The peers in this device won't be reachable at their allowed IPs until they're added to the
-    /// newly created device interface through a Netlink Route message. This library doesn't have
-    /// built-in way to do that right now. Here's how it would be done with the `ip` command:
+    /// newly created device interface through a Netlink Route message. Once the device is set,
+    /// [`RouteSocket::add_allowed_ip_routes`](crate::RouteSocket::add_allowed_ip_routes) does
+    /// that for every allowed IP. Here's how it would be done with the `ip` command:
     ///
     ///
     /// ```sh
@@ -116,10 +201,270 @@ impl WgSocket {
     /// ```
     pub fn set_device(&mut self, device: set::Device) -> Result<(), SetDeviceError> {
         for nl_message in create_set_device_messages(device, self.family_id)? {
-            self.sock.send_nl(nl_message)?;
-            self.sock.recv_ack()?;
+            self.send_set_device_message(nl_message)?;
+            self.recv_set_device_ack()?;
+        }
+
+        Ok(())
+    }
+
+    /// Set device messages may contain private and preshared keys. These are serialized into a
+    /// buffer owned here rather than through `NlSocket::send_nl` so it can be wiped after sending.
+    fn send_set_device_message(&mut self, message: NlWgMessage) -> Result<(), SetDeviceError> {
+        let mut buf = Zeroizing::new(Vec::with_capacity(message.asize()));
+        message.serialize(&mut StreamWriteBuffer::new_growable_ref(&mut buf))?;
+        self.sock.send(&buf[..], 0).map_err(NlError::from)?;
+        Ok(())
+    }
+
+    /// `NlSocket::recv_ack` expects the sequence number it assigned in `send_nl`. Since set device
+    /// messages bypass it, check the acknowledgement here instead.
+    fn recv_set_device_ack(&mut self) -> Result<(), SetDeviceError> {
+        let ack = self.sock.recv_nl::<Nlmsg, Vec<u8>>(None)?;
+        if ack.nl_type != Nlmsg::Error {
+            return Err(NlError::NoAck.into());
+        }
+
+        match parse_nlmsgerr(nl_flags(&ack.nl_flags), &ack.nl_payload)? {
+            Some(error) => Err(error.into()),
+            None => Ok(()),
+        }
+    }
+}
+
+struct DeviceEntryIter<'a> {
+    sock: &'a mut NlSocket,
+    buf: &'a mut RecvBuffer,
+    entries: DeviceEntries,
+    /// Nothing is left to yield.
+    done: bool,
+    /// The rest of the response still has to be received.
+    unread: bool,
+}
+
+impl DeviceEntryIter<'_> {
+    fn recv(&mut self) -> Result<(), GetDeviceError> {
+        let response = match self.buf.next(self.sock) {
+            Ok(response) => response,
+            Err(err) => {
+                self.unread = false;
+                return Err(err.into());
+            }
+        };
+
+        match i32::from(response.nl_type) {
+            libc::NLMSG_ERROR => {
+                // An error ends the response, even one that can't be parsed.
+                self.unread = false;
+                if let Some(error) = parse_nlmsgerr(response.nl_flags, response.payload)? {
+                    return Err(error.into());
+                }
+                self.finish()
+            }
+            libc::NLMSG_DONE => {
+                self.unread = false;
+                self.finish()
+            }
+            _ => {
+                let genlmsghdr =
+                    deserialize_payload::<Genlmsghdr<WgCmd, WgDeviceAttribute>>(response.payload)
+                        .map_err(NlError::from)?;
+                Ok(self.entries.push(genlmsghdr.get_attr_handle())?)
+            }
+        }
+    }
+
+    /// Releases the last entries once the response is done.
+    fn finish(&mut self) -> Result<(), GetDeviceError> {
+        self.done = true;
+        self.entries.finish();
+        match self.entries.started() {
+            true => Ok(()),
+            false => Err(GetDeviceError::AccessError),
+        }
+    }
+}
+
+impl Iterator for DeviceEntryIter<'_> {
+    type Item = Result<get::DeviceEntry, GetDeviceError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(entry) = self.entries.pop() {
+                return Some(Ok(entry));
+            }
+            if self.done {
+                return None;
+            }
+            if let Err(err) = self.recv() {
+                self.done = true;
+                return Some(Err(err));
+            }
+        }
+    }
+}
+
+impl Drop for DeviceEntryIter<'_> {
+    fn drop(&mut self) {
+        while self.unread {
+            match self.buf.next(self.sock) {
+                Ok(response) => {
+                    self.unread = !matches!(
+                        i32::from(response.nl_type),
+                        libc::NLMSG_DONE | libc::NLMSG_ERROR
+                    );
+                }
+                Err(_) => self.unread = false,
+            }
+        }
+    }
+}
+
+/// Holds the datagrams of get device responses. `NlSocket::recv_nl` allocates a new buffer for
+/// every datagram it receives, which adds up when requesting hundreds of devices in a row.
+struct RecvBuffer {
+    // Responses may contain private and preshared keys.
+    buf: Zeroizing<Vec<u8>>,
+    len: usize,
+    pos: usize,
+}
+
+struct RecvMessage<'a> {
+    nl_type: u16,
+    nl_flags: u16,
+    payload: &'a [u8],
+}
+
+impl RecvBuffer {
+    fn new() -> Self {
+        Self {
+            buf: Zeroizing::new(vec![0; MAX_NL_LENGTH]),
+            len: 0,
+            pos: 0,
+        }
+    }
+
+    /// Discards anything left over from an earlier response.
+    fn clear(&mut self) {
+        self.pos = self.len;
+    }
+
+    /// Returns the next message, receiving another datagram once all of the last one was read.
+    fn next(&mut self, sock: &mut NlSocket) -> Result<RecvMessage<'_>, NlError> {
+        if self.pos >= self.len {
+            let len = sock.recv(&mut self.buf[..], 0)?;
+            if len == 0 {
+                return Err(NlError::new("No data could be read from the socket"));
+            }
+            self.len = len as usize;
+            self.pos = 0;
+        }
+
+        let remaining = &self.buf[self.pos..self.len];
+        let message_len = remaining
+            .get(..4)
+            .map(|len| u32::from_ne_bytes(len.try_into().unwrap()) as usize)
+            .filter(|&len| NLMSG_HDRLEN <= len && len <= remaining.len());
+        let message_len = match message_len {
+            Some(len) => len,
+            None => {
+                self.pos = self.len;
+                return Err(NlError::new("Received a truncated netlink message"));
+            }
+        };
+
+        let start = self.pos;
+        let aligned_len = (message_len + NLMSG_ALIGNTO - 1) & !(NLMSG_ALIGNTO - 1);
+        self.pos = (self.pos + aligned_len).min(self.len);
+
+        let message = &self.buf[start..start + message_len];
+        Ok(RecvMessage {
+            nl_type: u16::from_ne_bytes(message[4..6].try_into().unwrap()),
+            nl_flags: u16::from_ne_bytes(message[6..8].try_into().unwrap()),
+            payload: &message[NLMSG_HDRLEN..],
+        })
+    }
+}
+
+pub(crate) fn get_device_message(
+    family_id: NlWgMsgType,
+    interface: DeviceInterface,
+) -> Result<Nlmsghdr<NlWgMsgType, Genlmsghdr<WgCmd, WgDeviceAttribute>>, GetDeviceError> {
+    let mut mem = StreamWriteBuffer::new_growable(None);
+    let attr = match interface {
+        DeviceInterface::Name(name) => {
+            Some(name.len())
+                .filter(|&len| 0 < len && len < IFNAMSIZ)
+                .ok_or(GetDeviceError::InvalidInterfaceName)?;
+            name.as_ref().serialize(&mut mem)?;
+            Nlattr::new(None, WgDeviceAttribute::Ifname, mem.as_ref())?
+        }
+        DeviceInterface::Index(index) => {
+            index.serialize(&mut mem)?;
+            Nlattr::new(None, WgDeviceAttribute::Ifindex, mem.as_ref())?
+        }
+    };
+    let genlhdr = {
+        let cmd = WgCmd::GetDevice;
+        let version = WG_GENL_VERSION;
+        let attrs = vec![attr];
+        Genlmsghdr::new(cmd, version, attrs)?
+    };
+    let nlhdr = {
+        let size = None;
+        let nl_type = family_id;
+        let flags = vec![NlmF::Request, NlmF::Ack, NlmF::Dump];
+        let seq = None;
+        let pid = None;
+        let payload = genlhdr;
+        Nlmsghdr::new(size, nl_type, flags, seq, pid, payload)
+    };
+    Ok(nlhdr)
+}
+
+impl WireGuardApi for WgSocket {
+    fn get_device(&mut self, ifname: &str) -> Result<get::Device, ApiError> {
+        Ok(WgSocket::get_device(
+            self,
+            DeviceInterface::from_name(ifname),
+        )?)
+    }
+
+    fn set_device(&mut self, ifname: &str, device: &api::set::Device) -> Result<(), ApiError> {
+        let device = device.to_linux_set_device(DeviceInterface::from_name(ifname));
+        Ok(WgSocket::set_device(self, device)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::linux::socket::list_device_names_utils::get_list_device_names_msg;
+
+    #[test]
+    fn recv_buffer_is_reused_across_responses() -> anyhow::Result<()> {
+        // Any dump does, and the route socket works without the WireGuard module.
+        let mut sock = NlSocket::connect(NlFamily::Route, None, None, false)?;
+        let mut buf = RecvBuffer::new();
+        let addr = buf.buf.as_ptr();
+
+        for _ in 0..2 {
+            buf.clear();
+            sock.send_nl(get_list_device_names_msg())?;
+            let mut links = 0;
+            loop {
+                let response = buf.next(&mut sock)?;
+                match i32::from(response.nl_type) {
+                    libc::NLMSG_DONE => break,
+                    libc::NLMSG_ERROR => panic!("Expected the end of the dump, got an error"),
+                    _ => links += 1,
+                }
+            }
+            // At least the loopback interface exists.
+            assert!(links > 0);
         }
 
+        assert_eq!(buf.buf.as_ptr(), addr);
         Ok(())
     }
 }
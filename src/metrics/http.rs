@@ -0,0 +1,221 @@
+//! A minimal HTTP server for Prometheus to scrape.
+//!
+//! Only `GET /metrics` is answered. Connections are handled one at a time and
+//! closed after each response, which is all a scraper needs. Reads and writes
+//! time out, so a client that stops responding only delays the next scrape.
+
+use std::fmt::Display;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+pub const CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Bytes of the request line and headers read before answering. A scrape
+// request is far smaller.
+const MAX_REQUEST_LEN: u64 = 8 * 1024;
+
+/// Serves the text returned by `collect` on `/metrics`. An error from `collect`
+/// is answered with a 500 status.
+///
+/// ```no_run
+/// use wireguard_uapi::metrics::http::MetricsServer;
+/// use wireguard_uapi::metrics::Renderer;
+/// use wireguard_uapi::{RouteSocket, WgSocket};
+///
+/// # fn main() -> anyhow::Result<()> {
+/// let renderer = Renderer::new();
+/// let mut wg = WgSocket::connect()?;
+/// let mut route = RouteSocket::connect()?;
+///
+/// let mut server = MetricsServer::bind("[::]:9586", || -> anyhow::Result<String> {
+///     let devices = wg.get_devices(&mut route)?.collect::<Result<Vec<_>, _>>()?;
+///     Ok(renderer.render(&devices))
+/// })?;
+/// server.serve()?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct MetricsServer<F> {
+    listener: TcpListener,
+    timeout: Duration,
+    collect: F,
+}
+
+impl<F, E> MetricsServer<F>
+where
+    F: FnMut() -> Result<String, E>,
+    E: Display,
+{
+    pub fn bind(addr: impl ToSocketAddrs, collect: F) -> io::Result<Self> {
+        Ok(Self::from_listener(TcpListener::bind(addr)?, collect))
+    }
+
+    pub fn from_listener(listener: TcpListener, collect: F) -> Self {
+        Self {
+            listener,
+            timeout: DEFAULT_TIMEOUT,
+            collect,
+        }
+    }
+
+    /// Sets the read and write timeout of each connection.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn listener(&self) -> &TcpListener {
+        &self.listener
+    }
+
+    /// Accepts a single connection and answers its request.
+    pub fn accept(&mut self) -> io::Result<()> {
+        let (stream, _) = self.listener.accept()?;
+        serve_connection(stream, self.timeout, &mut self.collect)
+    }
+
+    /// Answers connections one at a time until accepting a connection fails.
+    /// A misbehaving client only closes its own connection.
+    pub fn serve(&mut self) -> io::Result<()> {
+        for stream in self.listener.incoming() {
+            let _ = serve_connection(stream?, self.timeout, &mut self.collect);
+        }
+        Ok(())
+    }
+}
+
+/// Answers the request on an already accepted connection and closes it.
+pub fn serve_connection<F, E>(
+    mut stream: TcpStream,
+    timeout: Duration,
+    collect: F,
+) -> io::Result<()>
+where
+    F: FnOnce() -> Result<String, E>,
+    E: Display,
+{
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+
+    let mut reader = BufReader::new((&mut stream).take(MAX_REQUEST_LEN));
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+
+    // The headers don't change the response, but are read so that closing the
+    // connection doesn't reset it before the client received the response.
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 0 && !header.trim_end().is_empty() {
+        header.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (status, content_type, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/metrics")) => match collect() {
+            Ok(text) => ("200 OK", CONTENT_TYPE, text),
+            Err(err) => (
+                "500 Internal Server Error",
+                "text/plain; charset=utf-8",
+                format!("{}\n", err),
+            ),
+        },
+        (Some("GET"), Some(_)) => (
+            "404 Not Found",
+            "text/plain; charset=utf-8",
+            "Not Found\n".to_string(),
+        ),
+        _ => (
+            "405 Method Not Allowed",
+            "text/plain; charset=utf-8",
+            "Method Not Allowed\n".to_string(),
+        ),
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::SocketAddr;
+    use std::thread;
+
+    fn request(addr: SocketAddr, request_line: &str) -> anyhow::Result<String> {
+        let mut stream = TcpStream::connect(addr)?;
+        write!(stream, "{}\r\nHost: localhost\r\n\r\n", request_line)?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response)?;
+        Ok(response)
+    }
+
+    #[test]
+    fn serves_metrics_over_a_local_socket() -> anyhow::Result<()> {
+        let mut calls = 0;
+        let mut server = MetricsServer::bind("127.0.0.1:0", || {
+            calls += 1;
+            match calls {
+                1 => Ok("# EOF\n".to_string()),
+                _ => Err("Device wg0 not found"),
+            }
+        })?;
+        let addr = server.listener().local_addr()?;
+
+        let client = thread::spawn(move || -> anyhow::Result<Vec<String>> {
+            Ok(vec![
+                request(addr, "GET /metrics HTTP/1.1")?,
+                request(addr, "GET /metrics HTTP/1.1")?,
+                request(addr, "GET / HTTP/1.1")?,
+                request(addr, "POST /metrics HTTP/1.1")?,
+            ])
+        });
+        for _ in 0..4 {
+            server.accept()?;
+        }
+        let responses = client.join().unwrap()?;
+
+        assert_eq!(
+            responses[0],
+            format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: 6\r\nConnection: close\r\n\r\n# EOF\n",
+                CONTENT_TYPE
+            )
+        );
+        assert!(responses[1].starts_with("HTTP/1.1 500 Internal Server Error\r\n"));
+        assert!(responses[1].ends_with("\r\n\r\nDevice wg0 not found\n"));
+        assert!(responses[2].starts_with("HTTP/1.1 404 Not Found\r\n"));
+        assert!(responses[3].starts_with("HTTP/1.1 405 Method Not Allowed\r\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn idle_connection_does_not_block_scrapes() -> anyhow::Result<()> {
+        let mut server =
+            MetricsServer::bind("127.0.0.1:0", || Ok::<_, String>("# EOF\n".to_string()))?
+                .timeout(Duration::from_millis(100));
+        let addr = server.listener().local_addr()?;
+
+        let client = thread::spawn(move || -> anyhow::Result<String> {
+            let _idle = TcpStream::connect(addr)?;
+            request(addr, "GET /metrics HTTP/1.1")
+        });
+
+        let error = server.accept().unwrap_err();
+        assert!(matches!(
+            error.kind(),
+            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+        ));
+        server.accept()?;
+        assert!(client.join().unwrap()?.starts_with("HTTP/1.1 200 OK\r\n"));
+        Ok(())
+    }
+}
@@ -0,0 +1,348 @@
+//! OpenMetrics exporter
+//!
+//! Renders [`get::Device`] values in the [OpenMetrics text format], which
+//! Prometheus scrapes. It is disabled by default, and guarded behind the
+//! `metrics` feature flag.
+//!
+//! Devices can come from any backend, such as
+//! [`WgSocket::get_devices`][crate::WgSocket::get_devices] or an
+//! [`xplatform::Client`][crate::xplatform::Client]. The [`http`] module serves
+//! the rendered text on `/metrics`.
+//!
+//! ```
+//! use wireguard_uapi::get::Device;
+//! use wireguard_uapi::metrics::Renderer;
+//! use wireguard_uapi::PrivateKey;
+//!
+//! let laptop = PrivateKey::generate().public_key();
+//! let renderer = Renderer::new()
+//!     .label("instance", "gateway-1")?
+//!     .friendly_name(laptop, "alice-laptop");
+//!
+//! let devices: Vec<Device> = vec![];
+//! let text = renderer.render(&devices);
+//! assert!(text.ends_with("# EOF\n"));
+//! # Ok::<(), wireguard_uapi::metrics::LabelError>(())
+//! ```
+//!
+//! [OpenMetrics text format]: https://openmetrics.io
+
+pub mod http;
+
+use crate::get;
+use crate::key::PublicKey;
+use std::collections::HashMap;
+use std::fmt::{self, Write};
+
+/// Labels the renderer sets itself.
+const RESERVED_LABELS: &[&str] = &[
+    "interface",
+    "public_key",
+    "friendly_name",
+    "listen_port",
+    "fwmark",
+];
+
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum LabelError {
+    #[error("Invalid label name: `{0}`")]
+    InvalidName(String),
+    #[error("Label `{0}` is reserved")]
+    Reserved(String),
+    #[error("Label `{0}` was already added")]
+    Duplicate(String),
+}
+
+/// Renders devices as OpenMetrics text. The constant labels and friendly names
+/// are added to the samples they apply to.
+#[derive(Clone, Debug, Default)]
+pub struct Renderer {
+    labels: Vec<(String, String)>,
+    friendly_names: HashMap<PublicKey, String>,
+}
+
+impl Renderer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a label to every sample, such as the host the devices belong to.
+    ///
+    /// The name must match `[a-zA-Z_][a-zA-Z0-9_]*`. Names starting with `__` and the labels the
+    /// renderer sets itself, such as `interface` and `public_key`, are rejected.
+    pub fn label(mut self, name: &str, value: &str) -> Result<Self, LabelError> {
+        let mut chars = name.chars();
+        let valid = chars
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid {
+            return Err(LabelError::InvalidName(name.to_string()));
+        }
+        if name.starts_with("__") || RESERVED_LABELS.contains(&name) {
+            return Err(LabelError::Reserved(name.to_string()));
+        }
+        if self.labels.iter().any(|(label, _)| label == name) {
+            return Err(LabelError::Duplicate(name.to_string()));
+        }
+
+        self.labels.push((name.to_string(), value.to_string()));
+        Ok(self)
+    }
+
+    /// Adds a `friendly_name` label to the samples of a peer.
+    pub fn friendly_name(mut self, public_key: PublicKey, name: &str) -> Self {
+        self.friendly_names.insert(public_key, name.to_string());
+        self
+    }
+
+    pub fn friendly_names(mut self, names: HashMap<PublicKey, String>) -> Self {
+        self.friendly_names.extend(names);
+        self
+    }
+
+    pub fn render(&self, devices: &[get::Device]) -> String {
+        let mut text = String::new();
+        // Writing to a String can't fail.
+        self.write(&mut text, devices).unwrap();
+        text
+    }
+
+    pub fn write(&self, out: &mut impl Write, devices: &[get::Device]) -> fmt::Result {
+        family(
+            out,
+            "wireguard_device",
+            "info",
+            None,
+            "Configuration of the WireGuard device.",
+        )?;
+        for device in devices {
+            let mut labels = self.device_labels(device);
+            if let Some(public_key) = &device.public_key {
+                labels.push(("public_key", public_key.to_string()));
+            }
+            labels.push(("listen_port", device.listen_port.to_string()));
+            labels.push(("fwmark", device.fwmark.to_string()));
+            sample(out, "wireguard_device_info", &labels, 1)?;
+        }
+
+        let peers = || {
+            devices.iter().flat_map(move |device| {
+                device
+                    .peers
+                    .iter()
+                    .map(move |peer| (self.peer_labels(device, peer), peer))
+            })
+        };
+
+        family(
+            out,
+            "wireguard_peer_received_bytes",
+            "counter",
+            Some("bytes"),
+            "Bytes received from the peer.",
+        )?;
+        for (labels, peer) in peers() {
+            sample(
+                out,
+                "wireguard_peer_received_bytes_total",
+                &labels,
+                peer.rx_bytes,
+            )?;
+        }
+
+        family(
+            out,
+            "wireguard_peer_sent_bytes",
+            "counter",
+            Some("bytes"),
+            "Bytes sent to the peer.",
+        )?;
+        for (labels, peer) in peers() {
+            sample(
+                out,
+                "wireguard_peer_sent_bytes_total",
+                &labels,
+                peer.tx_bytes,
+            )?;
+        }
+
+        family(
+            out,
+            "wireguard_peer_latest_handshake_seconds",
+            "gauge",
+            Some("seconds"),
+            "Time of the latest handshake with the peer since the Unix epoch, or 0 if there was none.",
+        )?;
+        for (labels, peer) in peers() {
+            let seconds = peer.last_handshake_time.as_secs_f64();
+            sample(
+                out,
+                "wireguard_peer_latest_handshake_seconds",
+                &labels,
+                seconds,
+            )?;
+        }
+
+        family(
+            out,
+            "wireguard_peer_allowed_ips",
+            "gauge",
+            None,
+            "Number of allowed IPs routed to the peer.",
+        )?;
+        for (labels, peer) in peers() {
+            let count = peer.allowed_ips.len();
+            sample(out, "wireguard_peer_allowed_ips", &labels, count)?;
+        }
+
+        writeln!(out, "# EOF")
+    }
+
+    fn device_labels(&self, device: &get::Device) -> Vec<(&str, String)> {
+        let mut labels: Vec<_> = self
+            .labels
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.clone()))
+            .collect();
+        labels.push(("interface", device.ifname.clone()));
+        labels
+    }
+
+    fn peer_labels(&self, device: &get::Device, peer: &get::Peer) -> Vec<(&str, String)> {
+        let mut labels = self.device_labels(device);
+        labels.push(("public_key", peer.public_key.to_string()));
+        if let Some(name) = self.friendly_names.get(&peer.public_key) {
+            labels.push(("friendly_name", name.clone()));
+        }
+        labels
+    }
+}
+
+fn family(
+    out: &mut impl Write,
+    name: &str,
+    metric_type: &str,
+    unit: Option<&str>,
+    help: &str,
+) -> fmt::Result {
+    writeln!(out, "# TYPE {} {}", name, metric_type)?;
+    if let Some(unit) = unit {
+        writeln!(out, "# UNIT {} {}", name, unit)?;
+    }
+    writeln!(out, "# HELP {} {}", name, help)
+}
+
+fn sample(
+    out: &mut impl Write,
+    name: &str,
+    labels: &[(&str, String)],
+    value: impl fmt::Display,
+) -> fmt::Result {
+    write!(out, "{}{{", name)?;
+    for (i, (label, value)) in labels.iter().enumerate() {
+        if i > 0 {
+            write!(out, ",")?;
+        }
+        write!(out, "{}=\"", label)?;
+        for c in value.chars() {
+            match c {
+                '\\' => write!(out, "\\\\")?,
+                '"' => write!(out, "\\\"")?,
+                '\n' => write!(out, "\\n")?,
+                c => out.write_char(c)?,
+            }
+        }
+        write!(out, "\"")?;
+    }
+    writeln!(out, "}} {}", value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key::{PresharedKey, PublicKey};
+    use std::time::Duration;
+
+    fn device() -> anyhow::Result<get::Device> {
+        let public_key = PublicKey::from_base64("HIgo9xNzJMWLKASShiTqIybxZ0U3wGLiUeJ1PKf8ykw=")?;
+        Ok(get::Device {
+            ifindex: 3,
+            ifname: "wg0".to_string(),
+            private_key: None,
+            public_key: None,
+            listen_port: 51820,
+            fwmark: 0,
+            peers: vec![get::Peer {
+                public_key,
+                preshared_key: PresharedKey::zero(),
+                endpoint: None,
+                persistent_keepalive_interval: 0,
+                last_handshake_time: Duration::new(1_600_000_000, 500_000_000),
+                rx_bytes: 1024,
+                tx_bytes: 2048,
+                allowed_ips: vec!["10.24.24.2/32".parse()?, "fd00::2/128".parse()?],
+                protocol_version: 1,
+            }],
+        })
+    }
+
+    #[test]
+    fn render_devices() -> anyhow::Result<()> {
+        let device = device()?;
+        let text = Renderer::new()
+            .label("instance", "gateway \"1\"")?
+            .friendly_name(device.peers[0].public_key, "alice")
+            .render(&[device]);
+
+        let peer = r#"instance="gateway \"1\"",interface="wg0",public_key="HIgo9xNzJMWLKASShiTqIybxZ0U3wGLiUeJ1PKf8ykw=",friendly_name="alice""#;
+        let expected = [
+            "# TYPE wireguard_device info".to_string(),
+            "# HELP wireguard_device Configuration of the WireGuard device.".to_string(),
+            r#"wireguard_device_info{instance="gateway \"1\"",interface="wg0",listen_port="51820",fwmark="0"} 1"#.to_string(),
+            "# TYPE wireguard_peer_received_bytes counter".to_string(),
+            "# UNIT wireguard_peer_received_bytes bytes".to_string(),
+            "# HELP wireguard_peer_received_bytes Bytes received from the peer.".to_string(),
+            format!("wireguard_peer_received_bytes_total{{{}}} 1024", peer),
+            "# TYPE wireguard_peer_sent_bytes counter".to_string(),
+            "# UNIT wireguard_peer_sent_bytes bytes".to_string(),
+            "# HELP wireguard_peer_sent_bytes Bytes sent to the peer.".to_string(),
+            format!("wireguard_peer_sent_bytes_total{{{}}} 2048", peer),
+            "# TYPE wireguard_peer_latest_handshake_seconds gauge".to_string(),
+            "# UNIT wireguard_peer_latest_handshake_seconds seconds".to_string(),
+            "# HELP wireguard_peer_latest_handshake_seconds Time of the latest handshake with the peer since the Unix epoch, or 0 if there was none.".to_string(),
+            format!("wireguard_peer_latest_handshake_seconds{{{}}} 1600000000.5", peer),
+            "# TYPE wireguard_peer_allowed_ips gauge".to_string(),
+            "# HELP wireguard_peer_allowed_ips Number of allowed IPs routed to the peer.".to_string(),
+            format!("wireguard_peer_allowed_ips{{{}}} 2", peer),
+            "# EOF".to_string(),
+        ];
+
+        assert_eq!(text.lines().collect::<Vec<_>>(), expected);
+        Ok(())
+    }
+
+    #[test]
+    fn reject_invalid_label_names() {
+        let error = |name| Renderer::new().label(name, "value").unwrap_err();
+        assert_eq!(error(""), LabelError::InvalidName("".to_string()));
+        assert_eq!(error("1st"), LabelError::InvalidName("1st".to_string()));
+        assert_eq!(
+            error("host-name"),
+            LabelError::InvalidName("host-name".to_string())
+        );
+        assert_eq!(
+            error("__name__"),
+            LabelError::Reserved("__name__".to_string())
+        );
+        for name in RESERVED_LABELS {
+            assert_eq!(error(name), LabelError::Reserved(name.to_string()));
+        }
+
+        let renderer = Renderer::new().label("_region2", "eu").unwrap();
+        assert_eq!(
+            renderer.label("_region2", "us").unwrap_err(),
+            LabelError::Duplicate("_region2".to_string())
+        );
+    }
+}
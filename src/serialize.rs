@@ -0,0 +1,305 @@
+//! Serde support
+//!
+//! Implements `Serialize` and `Deserialize` for the [`get`][crate::get] models,
+//! the owned [`api::set`][crate::api::set] models and the
+//! [`xplatform::set`][crate::xplatform::set] models. It is disabled by default,
+//! and guarded behind the `serde` feature flag.
+//!
+//! - Keys are base64 strings, the same as in configuration files.
+//! - Allowed IPs are strings such as `"10.0.0.1/32"`.
+//! - Handshake times are Unix timestamps in seconds, or 0 if there was none.
+//! - A preshared key of all zeros in a [`get::Peer`] is `null`.
+//!
+//! Private keys are never serialized as part of a device, so that state can be
+//! returned from an API or written to logs without leaking them. Wrap the
+//! device in [`WithPrivateKey`] to include it. Deserializing accepts a private
+//! key either way.
+//!
+//! ```
+//! use wireguard_uapi::api::set;
+//! use wireguard_uapi::serialize::WithPrivateKey;
+//! use wireguard_uapi::PrivateKey;
+//!
+//! let device = set::Device::default()
+//!     .private_key(PrivateKey::generate())
+//!     .listen_port(51820);
+//!
+//! let json = serde_json::to_value(&device).unwrap();
+//! assert!(json.get("private_key").is_none());
+//!
+//! let json = serde_json::to_value(WithPrivateKey(&device)).unwrap();
+//! assert!(json.get("private_key").is_some());
+//! ```
+
+use crate::get;
+use crate::key::{PresharedKey, PrivateKey, PublicKey};
+use serde::de::{self, Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
+use std::net::IpAddr;
+use zeroize::Zeroizing;
+
+#[cfg(feature = "xplatform")]
+use crate::xplatform;
+
+/// Serializes a device including its private key, which is skipped otherwise.
+#[derive(Clone, Copy, Debug)]
+pub struct WithPrivateKey<'a, T>(pub &'a T);
+
+#[derive(serde::Serialize)]
+struct Flattened<'a, T> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    private_key: Option<&'a PrivateKey>,
+    #[serde(flatten)]
+    device: &'a T,
+}
+
+macro_rules! impl_with_private_key {
+    ($($device:ty),*) => {
+        $(
+            impl Serialize for WithPrivateKey<'_, $device> {
+                fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                    Flattened {
+                        private_key: self.0.private_key.as_ref(),
+                        device: self.0,
+                    }
+                    .serialize(serializer)
+                }
+            }
+        )*
+    };
+}
+
+impl_with_private_key!(get::Device, crate::api::set::Device);
+#[cfg(feature = "xplatform")]
+impl_with_private_key!(xplatform::set::Device);
+
+macro_rules! impl_serde_key {
+    ($($name:ident),*) => {
+        $(
+            /// Serializes the key as base64.
+            impl Serialize for $name {
+                fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                    serializer.collect_str(self)
+                }
+            }
+
+            /// Deserializes either a base64 or hex encoded key.
+            impl<'de> Deserialize<'de> for $name {
+                fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                    let s = Zeroizing::new(String::deserialize(deserializer)?);
+                    s.parse().map_err(de::Error::custom)
+                }
+            }
+        )*
+    };
+}
+
+impl_serde_key!(PrivateKey, PublicKey, PresharedKey);
+
+fn serialize_allowed_ip<S: Serializer>(
+    ipaddr: &IpAddr,
+    cidr_mask: u8,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(&format_args!("{}/{}", ipaddr, cidr_mask))
+}
+
+fn deserialize_allowed_ip<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<get::AllowedIp, D::Error> {
+    String::deserialize(deserializer)?
+        .parse()
+        .map_err(de::Error::custom)
+}
+
+macro_rules! impl_serde_allowed_ip {
+    ($($allowed_ip:ty => $convert:expr),*) => {
+        $(
+            impl Serialize for $allowed_ip {
+                fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                    serialize_allowed_ip(&self.ipaddr, self.cidr_mask, serializer)
+                }
+            }
+
+            impl<'de> Deserialize<'de> for $allowed_ip {
+                fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                    deserialize_allowed_ip(deserializer).map($convert)
+                }
+            }
+        )*
+    };
+}
+
+impl_serde_allowed_ip!(
+    get::AllowedIp => |allowed_ip| allowed_ip,
+    crate::api::set::AllowedIp => |allowed_ip| (&allowed_ip).into()
+);
+#[cfg(feature = "xplatform")]
+impl_serde_allowed_ip!(
+    xplatform::set::AllowedIp => |allowed_ip| xplatform::set::AllowedIp {
+        ipaddr: allowed_ip.ipaddr,
+        cidr_mask: allowed_ip.cidr_mask,
+    }
+);
+
+/// A time since the Unix epoch as whole seconds.
+pub(crate) mod unix_timestamp {
+    use super::*;
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(time: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(time.as_secs())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        u64::deserialize(deserializer).map(Duration::from_secs)
+    }
+}
+
+/// An all zero preshared key as `null`.
+pub(crate) mod optional_preshared_key {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(key: &PresharedKey, serializer: S) -> Result<S::Ok, S::Error> {
+        match key.is_zero() {
+            true => serializer.serialize_none(),
+            false => serializer.serialize_some(key),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<PresharedKey, D::Error> {
+        Ok(Option::deserialize(deserializer)?.unwrap_or_else(PresharedKey::zero))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::set;
+    use serde_json::json;
+    use std::time::Duration;
+
+    const PRIVATE_KEY: &str = "yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk=";
+    const PUBLIC_KEY: &str = "xTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg=";
+
+    fn get_device() -> anyhow::Result<get::Device> {
+        Ok(get::Device {
+            ifindex: 3,
+            ifname: "wg0".to_string(),
+            private_key: Some(PRIVATE_KEY.parse()?),
+            public_key: Some(PrivateKey::from_base64(PRIVATE_KEY)?.public_key()),
+            listen_port: 51820,
+            fwmark: 0,
+            peers: vec![get::Peer {
+                public_key: PUBLIC_KEY.parse()?,
+                preshared_key: PresharedKey::zero(),
+                endpoint: Some("192.95.5.67:1234".parse()?),
+                persistent_keepalive_interval: 25,
+                last_handshake_time: Duration::from_secs(1_600_000_000),
+                rx_bytes: 1024,
+                tx_bytes: 2048,
+                allowed_ips: vec!["10.0.0.1/32".parse()?, "fd00::/64".parse()?],
+                protocol_version: 1,
+            }],
+        })
+    }
+
+    #[test]
+    fn serialize_get_device() -> anyhow::Result<()> {
+        let device = get_device()?;
+        let json = serde_json::to_value(&device)?;
+
+        assert_eq!(
+            json,
+            json!({
+                "ifindex": 3,
+                "ifname": "wg0",
+                "public_key": "HIgo9xNzJMWLKASShiTqIybxZ0U3wGLiUeJ1PKf8ykw=",
+                "listen_port": 51820,
+                "fwmark": 0,
+                "peers": [{
+                    "public_key": PUBLIC_KEY,
+                    "preshared_key": null,
+                    "endpoint": "192.95.5.67:1234",
+                    "persistent_keepalive_interval": 25,
+                    "last_handshake_time": 1_600_000_000,
+                    "rx_bytes": 1024,
+                    "tx_bytes": 2048,
+                    "allowed_ips": ["10.0.0.1/32", "fd00::/64"],
+                    "protocol_version": 1,
+                }],
+            })
+        );
+
+        let parsed: get::Device = serde_json::from_value(json)?;
+        assert_eq!(parsed.private_key, None);
+        assert_eq!(parsed.peers, device.peers);
+        Ok(())
+    }
+
+    #[test]
+    fn serialize_with_private_key() -> anyhow::Result<()> {
+        let device = get_device()?;
+        let json = serde_json::to_string(&WithPrivateKey(&device))?;
+        assert_eq!(serde_json::from_str::<get::Device>(&json)?, device);
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_set_device() -> anyhow::Result<()> {
+        let device: set::Device = serde_json::from_value(json!({
+            "private_key": PRIVATE_KEY,
+            "replace_peers": true,
+            "peers": [{
+                "public_key": PUBLIC_KEY,
+                "allowed_ips": ["10.0.0.1/32"],
+            }],
+        }))?;
+
+        let expected = set::Device::default()
+            .private_key(PRIVATE_KEY.parse()?)
+            .replace_peers(true)
+            .peers(vec![set::Peer::from_public_key(PUBLIC_KEY.parse()?)
+                .allowed_ips(vec![set::AllowedIp::from_ipaddr(
+                    "10.0.0.1".parse()?,
+                )])]);
+        assert_eq!(device, expected);
+
+        let json = serde_json::to_value(&device)?;
+        assert!(json.get("private_key").is_none());
+        assert_eq!(json["peers"][0]["allowed_ips"], json!(["10.0.0.1/32"]));
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "xplatform")]
+    fn serialize_xplatform_set_device() -> anyhow::Result<()> {
+        let device = xplatform::set::Device {
+            private_key: Some(PRIVATE_KEY.parse()?),
+            peers: vec![xplatform::set::Peer {
+                preshared_key: Some(PresharedKey::zero()),
+                ..xplatform::set::Peer::from_public_key(PUBLIC_KEY.parse()?)
+            }],
+            ..Default::default()
+        };
+
+        let json = serde_json::to_string(&WithPrivateKey(&device))?;
+        assert_eq!(
+            serde_json::from_str::<xplatform::set::Device>(&json)?,
+            device
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_invalid_values() {
+        let error = |json| serde_json::from_value::<get::AllowedIp>(json).unwrap_err();
+        assert!(error(json!("10.0.0.1")).to_string().contains("CIDR mask"));
+        assert!(error(json!(32)).is_data());
+
+        let error = serde_json::from_value::<PublicKey>(json!("AAAA")).unwrap_err();
+        assert_eq!(error.to_string(), "Keys must be 32 bytes. Found 3.");
+    }
+}
@@ -0,0 +1,362 @@
+//! Throughput and handshake health of a device's peers, computed from
+//! consecutive [`get::Device`] snapshots.
+//!
+//! The kernel and userspace implementations only report running byte counters
+//! and the time of the last handshake. [`StatsPoller`] fetches a device through
+//! any [`WireGuardApi`] backend and diffs each snapshot against the previous
+//! one.
+//!
+//! ```no_run
+//! use std::thread;
+//! use std::time::Duration;
+//! use wireguard_uapi::stats::{PeerHealth, StatsPoller};
+//! use wireguard_uapi::WgSocket;
+//!
+//! # fn main() -> anyhow::Result<()> {
+//! let mut poller = StatsPoller::new(WgSocket::connect()?, "wg0");
+//! loop {
+//!     for peer in poller.poll()?.peers {
+//!         if peer.health == PeerHealth::Stale {
+//!             println!("{} has gone quiet", peer.public_key.to_base64());
+//!         }
+//!         if let Some(rx_rate) = peer.rx_rate {
+//!             println!("{}: {:.0} B/s", peer.public_key.to_base64(), rx_rate);
+//!         }
+//!     }
+//!     thread::sleep(Duration::from_secs(10));
+//! }
+//! # }
+//! ```
+
+use crate::api::{ApiError, WireGuardApi};
+use crate::get;
+use crate::key::PublicKey;
+use std::net::SocketAddr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// WireGuard stops using a session's keys 180 seconds after its handshake
+/// (`REJECT_AFTER_TIME`), so a peer that hasn't had a handshake since can no
+/// longer exchange traffic until it has another one.
+pub const DEFAULT_STALE_AFTER: Duration = Duration::from_secs(180);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PeerHealth {
+    /// The last handshake was recent enough for the session to be usable.
+    Connected,
+    /// The peer had a handshake, but not recently.
+    Stale,
+    /// The peer never completed a handshake.
+    NeverSeen,
+}
+
+/// A device along with the time it was retrieved at.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Snapshot {
+    pub device: get::Device,
+    pub taken_at: SystemTime,
+}
+
+impl Snapshot {
+    pub fn new(device: get::Device) -> Self {
+        Self::at(device, SystemTime::now())
+    }
+
+    pub fn at(device: get::Device, taken_at: SystemTime) -> Self {
+        Self { device, taken_at }
+    }
+
+    /// Computes the statistics of each peer in this snapshot. Rates are only
+    /// known when there's an earlier snapshot of the same device to compare
+    /// against.
+    pub fn stats(&self, previous: Option<&Snapshot>, stale_after: Duration) -> DeviceStats {
+        // A clock that went backwards gives no usable interval.
+        let interval = previous
+            .and_then(|previous| self.taken_at.duration_since(previous.taken_at).ok())
+            .filter(|interval| *interval > Duration::from_secs(0));
+
+        let peers = self
+            .device
+            .peers
+            .iter()
+            .map(|peer| {
+                let rates = match (previous, interval) {
+                    (Some(previous), Some(interval)) => {
+                        let previous = previous
+                            .device
+                            .peers
+                            .iter()
+                            .find(|previous| previous.public_key == peer.public_key);
+                        let (rx_bytes, tx_bytes) = counter_deltas(previous, peer);
+                        let secs = interval.as_secs_f64();
+                        Some((rx_bytes as f64 / secs, tx_bytes as f64 / secs))
+                    }
+                    _ => None,
+                };
+                let handshake_age = handshake_age(peer, self.taken_at);
+
+                PeerStats {
+                    public_key: peer.public_key,
+                    endpoint: peer.endpoint,
+                    rx_bytes: peer.rx_bytes,
+                    tx_bytes: peer.tx_bytes,
+                    rx_rate: rates.map(|(rx_rate, _)| rx_rate),
+                    tx_rate: rates.map(|(_, tx_rate)| tx_rate),
+                    handshake_age,
+                    health: match handshake_age {
+                        None => PeerHealth::NeverSeen,
+                        Some(age) if age <= stale_after => PeerHealth::Connected,
+                        Some(_) => PeerHealth::Stale,
+                    },
+                }
+            })
+            .collect();
+
+        DeviceStats {
+            ifname: self.device.ifname.clone(),
+            taken_at: self.taken_at,
+            interval,
+            peers,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DeviceStats {
+    pub ifname: String,
+    pub taken_at: SystemTime,
+    /// The time since the previous snapshot, if there was one.
+    pub interval: Option<Duration>,
+    pub peers: Vec<PeerStats>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PeerStats {
+    pub public_key: PublicKey,
+    pub endpoint: Option<SocketAddr>,
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+    /// Bytes received per second since the previous snapshot.
+    pub rx_rate: Option<f64>,
+    /// Bytes sent per second since the previous snapshot.
+    pub tx_rate: Option<f64>,
+    /// The time since the last handshake, or `None` if there never was one.
+    pub handshake_age: Option<Duration>,
+    pub health: PeerHealth,
+}
+
+/// Fetches a device through a [`WireGuardApi`] backend such as
+/// [`WgSocket`][crate::WgSocket] or [`xplatform::Client`][crate::xplatform::Client]
+/// and keeps the last snapshot to compute rates with.
+#[derive(Debug)]
+pub struct StatsPoller<A: WireGuardApi> {
+    api: A,
+    ifname: String,
+    stale_after: Duration,
+    previous: Option<Snapshot>,
+}
+
+impl<A: WireGuardApi> StatsPoller<A> {
+    pub fn new(api: A, ifname: &str) -> Self {
+        Self {
+            api,
+            ifname: ifname.to_string(),
+            stale_after: DEFAULT_STALE_AFTER,
+            previous: None,
+        }
+    }
+
+    /// How long after its last handshake a peer is considered stale. Defaults
+    /// to [`DEFAULT_STALE_AFTER`].
+    pub fn stale_after(mut self, stale_after: Duration) -> Self {
+        self.stale_after = stale_after;
+        self
+    }
+
+    /// Retrieves the device and compares it against the previous poll. Rates
+    /// are `None` on the first poll.
+    pub fn poll(&mut self) -> Result<DeviceStats, ApiError> {
+        let snapshot = Snapshot::new(self.api.get_device(&self.ifname)?);
+        let stats = snapshot.stats(self.previous.as_ref(), self.stale_after);
+        self.previous = Some(snapshot);
+        Ok(stats)
+    }
+
+    /// The snapshot taken by the last successful poll.
+    pub fn previous(&self) -> Option<&Snapshot> {
+        self.previous.as_ref()
+    }
+
+    pub fn api_mut(&mut self) -> &mut A {
+        &mut self.api
+    }
+
+    pub fn into_api(self) -> A {
+        self.api
+    }
+}
+
+/// The bytes received and sent since the previous snapshot. Counters start over
+/// when a peer is removed and added again, so a peer that's new or whose
+/// counters went down is assumed to have started from zero in between.
+fn counter_deltas(previous: Option<&get::Peer>, current: &get::Peer) -> (u64, u64) {
+    match previous {
+        Some(previous)
+            if current.rx_bytes >= previous.rx_bytes && current.tx_bytes >= previous.tx_bytes =>
+        {
+            (
+                current.rx_bytes - previous.rx_bytes,
+                current.tx_bytes - previous.tx_bytes,
+            )
+        }
+        _ => (current.rx_bytes, current.tx_bytes),
+    }
+}
+
+fn handshake_age(peer: &get::Peer, now: SystemTime) -> Option<Duration> {
+    // A zero timestamp is how both backends report that there was no handshake.
+    if peer.last_handshake_time == Duration::from_secs(0) {
+        return None;
+    }
+    let handshake = UNIX_EPOCH + peer.last_handshake_time;
+    // Handshakes in the future come from clocks that disagree slightly.
+    Some(
+        now.duration_since(handshake)
+            .unwrap_or_else(|_| Duration::from_secs(0)),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key::{PresharedKey, PrivateKey};
+
+    fn device(peers: Vec<get::Peer>) -> get::Device {
+        get::Device {
+            ifindex: 1,
+            ifname: "wgtest0".to_string(),
+            private_key: None,
+            public_key: None,
+            listen_port: 51820,
+            fwmark: 0,
+            peers,
+        }
+    }
+
+    fn peer(public_key: PublicKey, rx_bytes: u64, tx_bytes: u64, handshake: u64) -> get::Peer {
+        get::Peer {
+            public_key,
+            preshared_key: PresharedKey::zero(),
+            endpoint: None,
+            persistent_keepalive_interval: 0,
+            last_handshake_time: Duration::from_secs(handshake),
+            rx_bytes,
+            tx_bytes,
+            allowed_ips: vec![],
+            protocol_version: 1,
+        }
+    }
+
+    fn at(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn first_snapshot_has_no_rates() {
+        let key = PrivateKey::generate().public_key();
+        let snapshot = Snapshot::at(device(vec![peer(key, 100, 200, 990)]), at(1000));
+        let stats = snapshot.stats(None, DEFAULT_STALE_AFTER);
+
+        assert_eq!(stats.interval, None);
+        assert_eq!(stats.peers[0].rx_rate, None);
+        assert_eq!(stats.peers[0].tx_rate, None);
+        assert_eq!(stats.peers[0].handshake_age, Some(Duration::from_secs(10)));
+        assert_eq!(stats.peers[0].health, PeerHealth::Connected);
+    }
+
+    #[test]
+    fn rates_are_computed_between_snapshots() {
+        let key = PrivateKey::generate().public_key();
+        let previous = Snapshot::at(device(vec![peer(key, 1000, 500, 990)]), at(1000));
+        let current = Snapshot::at(device(vec![peer(key, 3000, 1500, 990)]), at(1010));
+        let stats = current.stats(Some(&previous), DEFAULT_STALE_AFTER);
+
+        assert_eq!(stats.interval, Some(Duration::from_secs(10)));
+        assert_eq!(stats.peers[0].rx_rate, Some(200.0));
+        assert_eq!(stats.peers[0].tx_rate, Some(100.0));
+    }
+
+    #[test]
+    fn counters_that_went_down_were_reset() {
+        let readded = PrivateKey::generate().public_key();
+        let added = PrivateKey::generate().public_key();
+        let previous = Snapshot::at(device(vec![peer(readded, 5000, 5000, 990)]), at(1000));
+        let current = Snapshot::at(
+            device(vec![
+                peer(readded, 400, 6000, 1005),
+                peer(added, 100, 50, 0),
+            ]),
+            at(1010),
+        );
+        let stats = current.stats(Some(&previous), DEFAULT_STALE_AFTER);
+
+        assert_eq!(stats.peers[0].rx_rate, Some(40.0));
+        assert_eq!(stats.peers[0].tx_rate, Some(600.0));
+        assert_eq!(stats.peers[1].rx_rate, Some(10.0));
+        assert_eq!(stats.peers[1].tx_rate, Some(5.0));
+    }
+
+    #[test]
+    fn peers_are_classified_by_handshake_age() {
+        let keys: Vec<_> = (0..3)
+            .map(|_| PrivateKey::generate().public_key())
+            .collect();
+        let snapshot = Snapshot::at(
+            device(vec![
+                peer(keys[0], 0, 0, 820),
+                peer(keys[1], 0, 0, 819),
+                peer(keys[2], 0, 0, 0),
+            ]),
+            at(1000),
+        );
+        let health: Vec<_> = snapshot
+            .stats(None, DEFAULT_STALE_AFTER)
+            .peers
+            .iter()
+            .map(|peer| peer.health)
+            .collect();
+
+        assert_eq!(
+            health,
+            vec![
+                PeerHealth::Connected,
+                PeerHealth::Stale,
+                PeerHealth::NeverSeen
+            ]
+        );
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn poller_keeps_the_previous_snapshot() -> anyhow::Result<()> {
+        use crate::api::mock::MockApi;
+
+        let key = PrivateKey::generate().public_key();
+        let mut api = MockApi::new();
+        api.add_device("wgtest0").peers.push(peer(key, 0, 0, 0));
+
+        let mut poller = StatsPoller::new(api, "wgtest0");
+        assert_eq!(poller.poll()?.interval, None);
+        poller.api_mut().device_mut("wgtest0").unwrap().peers[0].rx_bytes = 100;
+
+        let stats = poller.poll()?;
+        assert_eq!(stats.peers[0].rx_bytes, 100);
+        assert_eq!(stats.peers[0].health, PeerHealth::NeverSeen);
+        assert_eq!(
+            poller
+                .previous()
+                .map(|previous| &previous.device.peers[0].rx_bytes),
+            Some(&100)
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,138 @@
+use crate::get;
+use crate::xplatform::client::{parse_set_response, GET_CMD, SET_CMD};
+use crate::xplatform::error::{GetDeviceError, SetDeviceError};
+use crate::xplatform::parser::parse;
+use crate::xplatform::set;
+use std::io;
+use std::path::Path;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::UnixStream;
+use zeroize::Zeroizing;
+
+/// The async counterpart of [`Client`](super::Client). Requires the `tokio`
+/// feature.
+///
+/// Every operation uses a connection of its own, so operations can be
+/// cancelled by dropping their future, for example with `tokio::time::timeout`.
+pub struct AsyncClient<P: AsRef<Path>> {
+    path: P,
+}
+
+impl<P: AsRef<Path>> AsyncClient<P> {
+    /// A path to the unix socket file. Ex: `/var/run/wireguard/utun0.sock`
+    pub fn create(path: P) -> Self {
+        Self { path }
+    }
+
+    pub async fn get(&self) -> Result<get::Device, GetDeviceError> {
+        let mut stream = UnixStream::connect(&self.path).await?;
+
+        stream.write_all(GET_CMD.as_bytes()).await?;
+
+        let response_lines = read_response(stream).await;
+        Ok(parse(response_lines.into_iter())?)
+    }
+
+    pub async fn set(&self, set_request: set::Device) -> Result<(), SetDeviceError> {
+        let mut stream = UnixStream::connect(&self.path).await?;
+
+        // The request may contain private and preshared keys.
+        let request = Zeroizing::new(format!("{}{}\n", SET_CMD, set_request));
+        stream.write_all(request.as_bytes()).await?;
+
+        let response_lines = read_response(stream).await;
+        parse_set_response(response_lines.into_iter())
+    }
+}
+
+/// Reads lines up to and including the empty line that ends a response. The
+/// parsers take an iterator of lines, so the response is collected first.
+async fn read_response(stream: UnixStream) -> Vec<io::Result<String>> {
+    let mut lines = BufReader::new(stream).lines();
+    let mut response = vec![];
+
+    loop {
+        match lines.next_line().await {
+            Ok(Some(line)) => {
+                let end_of_response = line.is_empty();
+                response.push(Ok(line));
+                if end_of_response {
+                    break;
+                }
+            }
+            Ok(None) => break,
+            Err(err) => {
+                response.push(Err(err));
+                break;
+            }
+        }
+    }
+
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::xplatform::{Handler, Server};
+    use std::time::Duration;
+
+    struct ListenPortHandler(u16);
+
+    impl Handler for ListenPortHandler {
+        fn get(&mut self) -> io::Result<get::Device> {
+            Ok(get::Device {
+                ifindex: 0,
+                ifname: "".to_string(),
+                private_key: None,
+                public_key: None,
+                listen_port: self.0,
+                fwmark: 0,
+                peers: vec![],
+            })
+        }
+
+        fn set(&mut self, device: set::Device) -> io::Result<()> {
+            self.0 = device.listen_port.unwrap_or(self.0);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn get_and_set() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("wgtest0.sock");
+        let mut server = Server::bind(&path, ListenPortHandler(51820))?;
+        let server_thread = std::thread::spawn(move || -> anyhow::Result<()> {
+            for _ in 0..3 {
+                server.accept()?;
+            }
+            Ok(())
+        });
+
+        let client = AsyncClient::create(&path);
+        assert_eq!(client.get().await?.listen_port, 51820);
+        client
+            .set(set::Device {
+                listen_port: Some(12912),
+                ..Default::default()
+            })
+            .await?;
+        assert_eq!(client.get().await?.listen_port, 12912);
+
+        server_thread.join().unwrap()
+    }
+
+    #[tokio::test]
+    async fn timeout() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("wgtest0.sock");
+        // Connections are accepted by the kernel but never answered.
+        let _listener = std::os::unix::net::UnixListener::bind(&path)?;
+
+        let client = AsyncClient::create(&path);
+        let result = tokio::time::timeout(Duration::from_millis(50), client.get()).await;
+        assert!(result.is_err());
+        Ok(())
+    }
+}
@@ -1,3 +1,4 @@
+use crate::api::{self, ApiError, WireGuardApi};
 use crate::get;
 use crate::xplatform::error::GetDeviceError;
 use crate::xplatform::error::SetDeviceError;
@@ -8,8 +9,8 @@ use std::io::Write;
 use std::os::unix::net::UnixStream;
 use std::path::Path;
 
-const GET_CMD: &str = "get=1\n\n";
-const SET_CMD: &str = "set=1\n";
+pub(crate) const GET_CMD: &str = "get=1\n\n";
+pub(crate) const SET_CMD: &str = "set=1\n";
 
 pub struct Client<P: AsRef<Path>> {
     path: P,
@@ -21,6 +22,19 @@ impl<P: AsRef<Path>> Client<P> {
         Self { path }
     }
 
+    /// The name of the interface the socket belongs to, which is the file
+    /// name of the socket without its `.sock` extension.
+    pub fn ifname(&self) -> Option<&str> {
+        self.path.as_ref().file_stem()?.to_str()
+    }
+
+    fn check_ifname(&self, ifname: &str) -> Result<(), ApiError> {
+        match self.ifname() == Some(ifname) {
+            true => Ok(()),
+            false => Err(ApiError::NoSuchDevice(ifname.to_string())),
+        }
+    }
+
     pub fn get(&self) -> Result<get::Device, GetDeviceError> {
         let mut stream = UnixStream::connect(&self.path)?;
 
@@ -42,38 +56,59 @@ impl<P: AsRef<Path>> Client<P> {
         stream.write_all(b"\n")?;
 
         let reader = std::io::BufReader::new(stream);
-        let mut response_lines = reader.lines();
-
-        // The response for protocol_version=1 is expected to be a single
-        // "errno=N" line followed by an empty line.
-        let errno_line = response_lines
-            .next()
-            .ok_or(SetDeviceError::EmptyResponse)??;
-
-        let (raw_key, raw_value) = {
-            let mut tokens = errno_line.trim().splitn(2, '=');
-            let raw_key = tokens.next().unwrap();
-            let raw_value = match tokens.next() {
-                Some(val) => val,
-                None => return Err(SetDeviceError::InvalidResponse(errno_line)),
-            };
-
-            (raw_key, raw_value)
+        parse_set_response(reader.lines())
+    }
+}
+
+pub(crate) fn parse_set_response(
+    mut response_lines: impl Iterator<Item = std::io::Result<String>>,
+) -> Result<(), SetDeviceError> {
+    // The response for protocol_version=1 is expected to be a single
+    // "errno=N" line followed by an empty line.
+    let errno_line = response_lines
+        .next()
+        .ok_or(SetDeviceError::EmptyResponse)??;
+
+    let (raw_key, raw_value) = {
+        let mut tokens = errno_line.trim().splitn(2, '=');
+        let raw_key = tokens.next().unwrap();
+        let raw_value = match tokens.next() {
+            Some(val) => val,
+            None => return Err(SetDeviceError::InvalidResponse(errno_line)),
         };
 
-        match (raw_key, raw_value) {
-            ("errno", "0") => {}
-            ("errno", val) => return Err(SetDeviceError::ServerError(val.to_string())),
-            (_, _) => return Err(SetDeviceError::InvalidResponse(errno_line)),
-        }
+        (raw_key, raw_value)
+    };
 
-        let empty_line = response_lines
-            .next()
-            .ok_or(SetDeviceError::EmptyResponse)??;
-        if !empty_line.is_empty() {
-            return Err(SetDeviceError::InvalidEndOfResponse(empty_line));
-        }
+    match (raw_key, raw_value) {
+        ("errno", "0") => {}
+        ("errno", val) => return Err(SetDeviceError::ServerError(val.to_string())),
+        (_, _) => return Err(SetDeviceError::InvalidResponse(errno_line)),
+    }
+
+    let empty_line = response_lines
+        .next()
+        .ok_or(SetDeviceError::EmptyResponse)??;
+    if !empty_line.is_empty() {
+        return Err(SetDeviceError::InvalidEndOfResponse(empty_line));
+    }
+
+    Ok(())
+}
+
+/// A client is connected to the socket of a single interface. Any other
+/// interface name is rejected with [`ApiError::NoSuchDevice`].
+impl<P: AsRef<Path>> WireGuardApi for Client<P> {
+    fn get_device(&mut self, ifname: &str) -> Result<get::Device, ApiError> {
+        self.check_ifname(ifname)?;
+        let mut device = self.get()?;
+        // The cross-platform protocol doesn't report the interface name.
+        device.ifname = ifname.to_string();
+        Ok(device)
+    }
 
-        Ok(())
+    fn set_device(&mut self, ifname: &str, device: &api::set::Device) -> Result<(), ApiError> {
+        self.check_ifname(ifname)?;
+        Ok(self.set(device.to_xplatform_set_device())?)
     }
 }
@@ -1,4 +1,4 @@
-pub use super::parser::ParseGetResponseError;
+pub use super::parser::{ParseGetResponseError, ParseSetRequestError};
 
 #[derive(Debug, thiserror::Error)]
 pub enum GetDeviceError {
@@ -21,3 +21,15 @@ pub enum SetDeviceError {
     #[error("Invalid end of response. Expected empty line but saw: `{0}`")]
     InvalidEndOfResponse(String),
 }
+
+#[derive(Debug, thiserror::Error)]
+pub enum ServeError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("Unknown operation: `{0}`")]
+    UnknownOperation(String),
+    #[error("Get requests require an empty line after `get=1`. Saw: `{0}`")]
+    InvalidGetRequest(String),
+    #[error(transparent)]
+    ParseSetRequest(#[from] ParseSetRequestError),
+}
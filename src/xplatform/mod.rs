@@ -4,6 +4,9 @@
 //! [wireguard.com/xplatform]. It is disabled by default, and guarded behind the
 //! `xplatform` feature flag.
 //!
+//! [`Client`] talks to an existing userspace implementation. [`Server`] is the
+//! other end of the protocol, for userspace implementations written in Rust.
+//!
 //! Similar to the Linux-specific client, structs are organized into
 //! [`set`][crate::xplatform::set] and [`get`][crate::get] modules. The
 //! [`get`][crate::get] module for the cross-platform client is shared with the
@@ -18,10 +21,18 @@
 //! [wireguard.com/xplatform]: https://www.wireguard.com/xplatform
 //! [xplatform-interface]: https://www.wireguard.com/xplatform/#interface
 
+#[cfg(feature = "tokio")]
+mod async_client;
 mod client;
 pub mod error;
 mod parser;
 mod protocol;
+mod server;
 pub mod set;
 
+#[cfg(feature = "tokio")]
+pub use async_client::AsyncClient;
 pub use client::Client;
+pub use parser::parse_set_request;
+pub use protocol::{GetKey, SetKey};
+pub use server::{serve_connection, Handler, Server, DEFAULT_TIMEOUT};
@@ -1,5 +1,7 @@
 mod parse;
+mod set;
 mod state;
 
 pub(crate) use parse::parse;
 pub use parse::ParseGetResponseError;
+pub use set::{parse_set_request, ParseSetRequestError};
@@ -1,12 +1,14 @@
 use super::state::{ParsePeerState, ParseState};
 use crate::get;
 use crate::get::ParseAllowedIpError;
-use crate::xplatform::protocol::{GetKey, ParseKeyError};
+use crate::key::{PresharedKey, PrivateKey, PublicKey};
+use crate::xplatform::protocol::{GetKey, UnknownKeyError};
 use std::net::AddrParseError;
 use std::num::ParseIntError;
 use std::{str::FromStr, time::Duration};
 use take_until::TakeUntilExt;
 use thiserror::Error;
+use zeroize::Zeroizing;
 
 #[derive(Error, Debug)]
 pub enum ParseGetResponseError {
@@ -36,8 +38,8 @@ pub enum ParseGetResponseError {
     InvalidPrivateKey,
     #[error("Invalid public_key: `{0}`")]
     InvalidPublicKey(String),
-    #[error("Invalid preshared_key: `{0}`")]
-    InvalidPresharedKey(String),
+    #[error("Invalid preshared_key")]
+    InvalidPresharedKey,
     #[error("{0}")]
     InvalidListenPort(#[source] ParseIntError),
     #[error("{0}")]
@@ -72,8 +74,8 @@ pub enum ParseGetResponseError {
     DataAfterEndOfResponse(GetKey),
 }
 
-impl From<ParseKeyError> for ParseGetResponseError {
-    fn from(err: ParseKeyError) -> Self {
+impl From<UnknownKeyError> for ParseGetResponseError {
+    fn from(err: UnknownKeyError) -> Self {
         Self::UnknownKey(err.unknown_key)
     }
 }
@@ -113,7 +115,8 @@ fn process_line(
 ) -> Result<ParseState, ParseGetResponseError> {
     type ParseErr = ParseGetResponseError;
 
-    let line = line.map_err(ParseErr::ReadLineIoError)?;
+    // Lines may contain private or preshared keys. Wipe them once parsed.
+    let line = Zeroizing::new(line.map_err(ParseErr::ReadLineIoError)?);
 
     // An empty line signifies the end of a "get" response.
     if line.is_empty() {
@@ -146,10 +149,8 @@ fn process_line(
     match state {
         ParseState::Initial(mut device_builder) => match key {
             GetKey::PrivateKey => {
-                let private_key: [u8; 32] = hex::decode(raw_val)
-                    .ok()
-                    .and_then(|buf| parse_device_key(&buf))
-                    .ok_or(ParseErr::InvalidPrivateKey)?;
+                let private_key =
+                    PrivateKey::from_hex(raw_val).map_err(|_| ParseErr::InvalidPrivateKey)?;
                 device_builder.private_key(Some(private_key));
                 Ok(ParseState::InterfaceLevelKeys(device_builder))
             }
@@ -182,12 +183,10 @@ fn process_line(
             // Transition the parser state to receive peer-level keys.
             GetKey::PublicKey => {
                 let mut peer_builder = get::PeerBuilder::default();
-                let public_key = hex::decode(raw_val)
-                    .ok()
-                    .and_then(|buf| parse_device_key(&buf))
-                    .ok_or_else(|| ParseErr::InvalidPublicKey(raw_val.to_string()))?;
+                let public_key = PublicKey::from_hex(raw_val)
+                    .map_err(|_| ParseErr::InvalidPublicKey(raw_val.to_string()))?;
                 peer_builder.public_key(public_key);
-                peer_builder.preshared_key([0u8; 32]);
+                peer_builder.preshared_key(PresharedKey::zero());
                 peer_builder.persistent_keepalive_interval(0);
                 peer_builder.tx_bytes(0);
                 peer_builder.rx_bytes(0);
@@ -237,12 +236,10 @@ fn process_line(
                 state.peers.push(peer);
 
                 state.peer_builder = get::PeerBuilder::default();
-                let public_key = hex::decode(raw_val)
-                    .ok()
-                    .and_then(|buf| parse_device_key(&buf))
-                    .ok_or_else(|| ParseErr::InvalidPublicKey(raw_val.to_string()))?;
+                let public_key = PublicKey::from_hex(raw_val)
+                    .map_err(|_| ParseErr::InvalidPublicKey(raw_val.to_string()))?;
                 state.peer_builder.public_key(public_key);
-                state.peer_builder.preshared_key([0u8; 32]);
+                state.peer_builder.preshared_key(PresharedKey::zero());
                 state.peer_builder.persistent_keepalive_interval(0);
                 state.peer_builder.tx_bytes(0);
                 state.peer_builder.rx_bytes(0);
@@ -252,10 +249,8 @@ fn process_line(
                 Ok(ParseState::PeerLevelKeys(state))
             }
             GetKey::PresharedKey => {
-                let preshared_key = hex::decode(raw_val)
-                    .ok()
-                    .and_then(|buf| parse_device_key(&buf))
-                    .ok_or_else(|| ParseErr::InvalidPresharedKey(raw_val.to_string()))?;
+                let preshared_key =
+                    PresharedKey::from_hex(raw_val).map_err(|_| ParseErr::InvalidPresharedKey)?;
                 state.peer_builder.preshared_key(preshared_key);
                 Ok(ParseState::PeerLevelKeys(state))
             }
@@ -315,21 +310,11 @@ fn process_line(
     }
 }
 
-// TODO: Get this from a shared util
-pub fn parse_device_key(buf: &[u8]) -> Option<[u8; 32]> {
-    if buf.len() != 32 {
-        return None;
-    }
-
-    let mut key = [0u8; 32];
-    key.copy_from_slice(buf);
-    Some(key)
-}
-
 #[cfg(test)]
 mod tests {
-    use super::{parse, parse_device_key};
+    use super::parse;
     use crate::get;
+    use crate::key::PresharedKey;
     use std::time::Duration;
 
     #[test]
@@ -352,18 +337,13 @@ mod tests {
         let expected = get::Device {
             ifindex: 0,
             ifname: "".to_string(),
-            private_key: parse_device_key(&base64::decode(
-                "GKoQwFpTH1xTehhCazdjh/wsvXAa4bm0Jx4yeqrenU8=",
-            )?),
+            private_key: Some("GKoQwFpTH1xTehhCazdjh/wsvXAa4bm0Jx4yeqrenU8=".parse()?),
             public_key: None,
             listen_port: 56137,
             fwmark: 0,
             peers: vec![get::Peer {
-                public_key: parse_device_key(&base64::decode(
-                    "kT6g4g4owStcX1qFi5OgXmhtw85SThbzFDu7ECNnl1E=",
-                )?)
-                .unwrap(),
-                preshared_key: [0u8; 32],
+                public_key: "kT6g4g4owStcX1qFi5OgXmhtw85SThbzFDu7ECNnl1E=".parse()?,
+                preshared_key: PresharedKey::zero(),
                 endpoint: Some("192.168.64.73:51820".parse()?),
                 last_handshake_time: Duration::new(1_590_459_201, 283_546_000),
                 tx_bytes: 824,
@@ -394,9 +374,7 @@ mod tests {
         let expected = get::Device {
             ifindex: 0,
             ifname: "".to_string(),
-            private_key: parse_device_key(&base64::decode(
-                "GKoQwFpTH1xTehhCazdjh/wsvXAa4bm0Jx4yeqrenU8=",
-            )?),
+            private_key: Some("GKoQwFpTH1xTehhCazdjh/wsvXAa4bm0Jx4yeqrenU8=".parse()?),
             public_key: None,
             listen_port: 56137,
             fwmark: 0,
@@ -440,22 +418,14 @@ mod tests {
         let expected = get::Device {
             ifindex: 0,
             ifname: "".to_string(),
-            private_key: parse_device_key(&base64::decode(
-                "6EtabScXwQA6E7QxVwNT26ypFGzxUMX4V1aA/rpSAno=",
-            )?),
+            private_key: Some("6EtabScXwQA6E7QxVwNT26ypFGzxUMX4V1aA/rpSAno=".parse()?),
             public_key: None,
             listen_port: 12912,
             fwmark: 0,
             peers: vec![
                 get::Peer {
-                    public_key: parse_device_key(&base64::decode(
-                        "uFmW/sycfx/G0lcqdu2hHVm80gvo5UOxXOS9hajnWjM=",
-                    )?)
-                    .unwrap(),
-                    preshared_key: parse_device_key(&base64::decode(
-                        "GIUVCT6VL18i6GXO8wEucvi18LWYrAMJ1drM47cPz1I=",
-                    )?)
-                    .unwrap(),
+                    public_key: "uFmW/sycfx/G0lcqdu2hHVm80gvo5UOxXOS9hajnWjM=".parse()?,
+                    preshared_key: "GIUVCT6VL18i6GXO8wEucvi18LWYrAMJ1drM47cPz1I=".parse()?,
                     endpoint: Some("[abcd:23::33]:51820".parse()?),
                     last_handshake_time: Duration::new(0, 0),
                     tx_bytes: 0,
@@ -469,11 +439,8 @@ mod tests {
                     protocol_version: 1,
                 },
                 get::Peer {
-                    public_key: parse_device_key(&base64::decode(
-                        "WEAuaVuhdyscyTCXVfBDJR6nf9zxD75jmJzrfhkyE3Y=",
-                    )?)
-                    .unwrap(),
-                    preshared_key: [0u8; 32],
+                    public_key: "WEAuaVuhdyscyTCXVfBDJR6nf9zxD75jmJzrfhkyE3Y=".parse()?,
+                    preshared_key: PresharedKey::zero(),
                     endpoint: Some("182.122.22.19:3233".parse()?),
                     last_handshake_time: Duration::new(0, 0),
                     tx_bytes: 38333,
@@ -487,11 +454,8 @@ mod tests {
                     protocol_version: 1,
                 },
                 get::Peer {
-                    public_key: parse_device_key(&base64::decode(
-                        "Zi4U/VlFVvUiYEcDNANRJYkDtk81VTdj8ZQmqypRXFg=",
-                    )?)
-                    .unwrap(),
-                    preshared_key: [0u8; 32],
+                    public_key: "Zi4U/VlFVvUiYEcDNANRJYkDtk81VTdj8ZQmqypRXFg=".parse()?,
+                    preshared_key: PresharedKey::zero(),
                     endpoint: Some("5.152.198.39:51820".parse()?),
                     last_handshake_time: Duration::new(0, 0),
                     tx_bytes: 1_212_111,
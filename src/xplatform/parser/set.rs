@@ -0,0 +1,277 @@
+use crate::get::{self, ParseAllowedIpError};
+use crate::key::{PresharedKey, PrivateKey, PublicKey};
+use crate::xplatform::protocol::{SetKey, UnknownKeyError};
+use crate::xplatform::set;
+use std::collections::HashSet;
+use std::net::AddrParseError;
+use std::num::ParseIntError;
+use std::str::FromStr;
+use thiserror::Error;
+use zeroize::Zeroizing;
+
+#[derive(Error, Debug)]
+pub enum ParseSetRequestError {
+    #[error("Failed to read line from socket: `{0}`")]
+    ReadLineIoError(#[source] std::io::Error),
+
+    #[error("Set requests require an empty line. None found.")]
+    MissingEndOfRequestNewline,
+
+    #[error("Encountered unknown key `{0}`")]
+    UnknownKey(String),
+    #[error("Missing value for key `{0}`")]
+    MissingValueForKey(SetKey),
+
+    #[error("Invalid private_key")]
+    InvalidPrivateKey,
+    #[error("Invalid public_key: `{0}`")]
+    InvalidPublicKey(String),
+    #[error("Invalid preshared_key")]
+    InvalidPresharedKey,
+    #[error("{0}")]
+    InvalidListenPort(#[source] ParseIntError),
+    #[error("{0}")]
+    InvalidFwmark(#[source] ParseIntError),
+    #[error("{0}")]
+    InvalidEndpoint(#[source] AddrParseError),
+    #[error("{0}")]
+    InvalidPersistentKeepaliveInterval(#[source] ParseIntError),
+    #[error(transparent)]
+    InvalidAllowedIp(#[from] ParseAllowedIpError),
+    #[error("Invalid value for `{0}`. Expected `true` or `false`, saw: `{1}`")]
+    InvalidBool(SetKey, String),
+
+    // Invalid parser state transition errors
+    #[error("Observed peer-level key `{0}` before public_key was specified")]
+    PeerLevelKeyBeforePublicKey(SetKey),
+    #[error("Observed interface-level key `{0}` after a peer-level key")]
+    InterfaceLevelKeyAfterPeerLevelKey(SetKey),
+    #[error("public_key `{0:x}` was specified more than once")]
+    DuplicatePublicKey(PublicKey),
+    #[error("Observed data after end of request")]
+    DataAfterEndOfRequest,
+}
+
+impl From<UnknownKeyError> for ParseSetRequestError {
+    fn from(err: UnknownKeyError) -> Self {
+        Self::UnknownKey(err.unknown_key)
+    }
+}
+
+/// Parses the body of a `set=1` request, up to and including the empty line
+/// that ends it. The `set=1` line itself must already be consumed.
+///
+/// The request must follow the ordering rules of the protocol. Interface-level
+/// keys come before the first `public_key`, peer-level keys only follow a
+/// `public_key`, and each peer appears at most once.
+pub fn parse_set_request(
+    lines: impl Iterator<Item = Result<String, std::io::Error>>,
+) -> Result<set::Device, ParseSetRequestError> {
+    type ParseErr = ParseSetRequestError;
+
+    let mut device = set::Device::default();
+    let mut peer: Option<set::Peer> = None;
+    let mut public_keys = HashSet::new();
+
+    for line in lines {
+        // Lines may contain private or preshared keys. Wipe them once parsed.
+        let line = Zeroizing::new(line.map_err(ParseErr::ReadLineIoError)?);
+
+        // An empty line signifies the end of a "set" request.
+        if line.is_empty() {
+            device.peers.extend(peer);
+            return Ok(device);
+        }
+
+        let (key, raw_val) = {
+            let mut tokens = line.trim().splitn(2, '=');
+
+            // The first token should always exist.
+            let raw_key = tokens.next().unwrap();
+            let key = SetKey::from_str(raw_key)?;
+
+            let raw_val = match tokens.next() {
+                Some(val) => val,
+                None => return Err(ParseErr::MissingValueForKey(key)),
+            };
+
+            (key, raw_val)
+        };
+
+        match key {
+            SetKey::PrivateKey | SetKey::ListenPort | SetKey::Fwmark | SetKey::ReplacePeers
+                if peer.is_some() =>
+            {
+                return Err(ParseErr::InterfaceLevelKeyAfterPeerLevelKey(key));
+            }
+
+            SetKey::PrivateKey => {
+                let private_key =
+                    PrivateKey::from_hex(raw_val).map_err(|_| ParseErr::InvalidPrivateKey)?;
+                device.private_key = Some(private_key);
+            }
+            SetKey::ListenPort => {
+                let listen_port = raw_val.parse().map_err(ParseErr::InvalidListenPort)?;
+                device.listen_port = Some(listen_port);
+            }
+            SetKey::Fwmark => {
+                let fwmark = raw_val.parse().map_err(ParseErr::InvalidFwmark)?;
+                device.fwmark = Some(fwmark);
+            }
+            SetKey::ReplacePeers => {
+                device.replace_peers = Some(parse_bool(key, raw_val)?);
+            }
+
+            // A public_key entry specifies the start of a new peer block.
+            SetKey::PublicKey => {
+                let public_key = PublicKey::from_hex(raw_val)
+                    .map_err(|_| ParseErr::InvalidPublicKey(raw_val.to_string()))?;
+                if !public_keys.insert(public_key) {
+                    return Err(ParseErr::DuplicatePublicKey(public_key));
+                }
+                device
+                    .peers
+                    .extend(peer.replace(set::Peer::from_public_key(public_key)));
+            }
+
+            _ => {
+                let peer = peer
+                    .as_mut()
+                    .ok_or(ParseErr::PeerLevelKeyBeforePublicKey(key))?;
+                parse_peer_key(peer, key, raw_val)?;
+            }
+        }
+    }
+
+    Err(ParseErr::MissingEndOfRequestNewline)
+}
+
+fn parse_peer_key(
+    peer: &mut set::Peer,
+    key: SetKey,
+    raw_val: &str,
+) -> Result<(), ParseSetRequestError> {
+    type ParseErr = ParseSetRequestError;
+
+    match key {
+        SetKey::Remove => peer.remove = Some(parse_bool(key, raw_val)?),
+        SetKey::UpdateOnly => peer.update_only = Some(parse_bool(key, raw_val)?),
+        SetKey::PresharedKey => {
+            let preshared_key =
+                PresharedKey::from_hex(raw_val).map_err(|_| ParseErr::InvalidPresharedKey)?;
+            peer.preshared_key = Some(preshared_key);
+        }
+        SetKey::Endpoint => {
+            let endpoint = raw_val.parse().map_err(ParseErr::InvalidEndpoint)?;
+            peer.endpoint = Some(endpoint);
+        }
+        SetKey::PersistentKeepaliveInterval => {
+            let interval = raw_val
+                .parse()
+                .map_err(ParseErr::InvalidPersistentKeepaliveInterval)?;
+            peer.persistent_keepalive_interval = Some(interval);
+        }
+        SetKey::ReplaceAllowedIps => {
+            peer.replace_allowed_ips = Some(parse_bool(key, raw_val)?);
+        }
+        SetKey::AllowedIp => {
+            let allowed_ip: get::AllowedIp = raw_val.parse()?;
+            peer.allowed_ips.push(set::AllowedIp {
+                ipaddr: allowed_ip.ipaddr,
+                cidr_mask: allowed_ip.cidr_mask,
+            });
+        }
+        SetKey::PrivateKey
+        | SetKey::ListenPort
+        | SetKey::Fwmark
+        | SetKey::ReplacePeers
+        | SetKey::PublicKey => {
+            unreachable!("interface-level keys are handled by parse_set_request")
+        }
+    }
+
+    Ok(())
+}
+
+fn parse_bool(key: SetKey, raw_val: &str) -> Result<bool, ParseSetRequestError> {
+    match raw_val {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        _ => Err(ParseSetRequestError::InvalidBool(key, raw_val.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(request: &str) -> impl Iterator<Item = std::io::Result<String>> + '_ {
+        request.lines().map(String::from).map(Ok)
+    }
+
+    #[test]
+    fn parse_valid_set_request() -> anyhow::Result<()> {
+        let request = "\
+            private_key=e84b5a6d2717c1003a13b431570353dbaca9146cf150c5f8575680feba52027a\n\
+            listen_port=12912\n\
+            replace_peers=true\n\
+            public_key=b85996fecc9c7f1fc6d2572a76eda11d59bcd20be8e543b15ce4bd85a8e75a33\n\
+            endpoint=[abcd:23::33]:51820\n\
+            replace_allowed_ips=true\n\
+            allowed_ip=192.168.4.4/32\n\
+            public_key=58402e695ba1772b1cc9309755f043251ea77fdcf10fbe63989ceb7e19321376\n\
+            remove=true\n\
+            \n";
+
+        let expected = set::Device {
+            private_key: Some("6EtabScXwQA6E7QxVwNT26ypFGzxUMX4V1aA/rpSAno=".parse()?),
+            listen_port: Some(12912),
+            fwmark: None,
+            replace_peers: Some(true),
+            peers: vec![
+                set::Peer {
+                    endpoint: Some("[abcd:23::33]:51820".parse()?),
+                    replace_allowed_ips: Some(true),
+                    allowed_ips: vec![set::AllowedIp {
+                        ipaddr: "192.168.4.4".parse()?,
+                        cidr_mask: 32,
+                    }],
+                    ..set::Peer::from_public_key(
+                        "uFmW/sycfx/G0lcqdu2hHVm80gvo5UOxXOS9hajnWjM=".parse()?,
+                    )
+                },
+                set::Peer {
+                    remove: Some(true),
+                    ..set::Peer::from_public_key(
+                        "WEAuaVuhdyscyTCXVfBDJR6nf9zxD75jmJzrfhkyE3Y=".parse()?,
+                    )
+                },
+            ],
+        };
+
+        assert_eq!(parse_set_request(lines(request))?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn parse_invalid_set_request() {
+        assert!(matches!(
+            parse_set_request(lines("listen_port=1\n")),
+            Err(ParseSetRequestError::MissingEndOfRequestNewline)
+        ));
+        assert!(matches!(
+            parse_set_request(lines("errno=0\n\n")),
+            Err(ParseSetRequestError::UnknownKey(_))
+        ));
+        assert!(matches!(
+            parse_set_request(lines("allowed_ip=10.0.0.1/32\n\n")),
+            Err(ParseSetRequestError::PeerLevelKeyBeforePublicKey(
+                SetKey::AllowedIp
+            ))
+        ));
+        assert!(matches!(
+            parse_set_request(lines("replace_peers=yes\n\n")),
+            Err(ParseSetRequestError::InvalidBool(SetKey::ReplacePeers, _))
+        ));
+    }
+}
@@ -2,7 +2,7 @@ use std::fmt::Debug;
 use std::fmt::Display;
 use std::str::FromStr;
 
-pub struct ParseKeyError {
+pub struct UnknownKeyError {
     pub unknown_key: String,
 }
 
@@ -25,7 +25,7 @@ pub enum GetKey {
 }
 
 impl FromStr for GetKey {
-    type Err = ParseKeyError;
+    type Err = UnknownKeyError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
@@ -55,7 +55,7 @@ impl Display for GetKey {
         match self {
             GetKey::PrivateKey => f.write_str("private_key"),
             GetKey::ListenPort => f.write_str("listen_port"),
-            GetKey::Fwmark => f.write_str("fw_mark"),
+            GetKey::Fwmark => f.write_str("fwmark"),
             GetKey::PublicKey => f.write_str("public_key"),
             GetKey::PresharedKey => f.write_str("preshared_key"),
             GetKey::Endpoint => f.write_str("endpoint"),
@@ -71,7 +71,8 @@ impl Display for GetKey {
     }
 }
 
-pub(crate) enum SetKey {
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SetKey {
     PrivateKey,
     ListenPort,
     Fwmark,
@@ -86,6 +87,30 @@ pub(crate) enum SetKey {
     AllowedIp,
 }
 
+impl FromStr for SetKey {
+    type Err = UnknownKeyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "private_key" => Ok(Self::PrivateKey),
+            "listen_port" => Ok(Self::ListenPort),
+            "fwmark" => Ok(Self::Fwmark),
+            "replace_peers" => Ok(Self::ReplacePeers),
+            "public_key" => Ok(Self::PublicKey),
+            "remove" => Ok(Self::Remove),
+            "update_only" => Ok(Self::UpdateOnly),
+            "preshared_key" => Ok(Self::PresharedKey),
+            "endpoint" => Ok(Self::Endpoint),
+            "persistent_keepalive_interval" => Ok(Self::PersistentKeepaliveInterval),
+            "replace_allowed_ips" => Ok(Self::ReplaceAllowedIps),
+            "allowed_ip" => Ok(Self::AllowedIp),
+            _ => Err(Self::Err {
+                unknown_key: s.to_string(),
+            }),
+        }
+    }
+}
+
 impl From<&SetKey> for &'static str {
     fn from(set_key: &SetKey) -> &'static str {
         match set_key {
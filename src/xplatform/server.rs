@@ -0,0 +1,481 @@
+use crate::get;
+use crate::xplatform::error::ServeError;
+use crate::xplatform::parser::parse_set_request;
+use crate::xplatform::protocol::GetKey;
+use crate::xplatform::set;
+use std::fmt::Write as _;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::time::Duration;
+use zeroize::Zeroizing;
+
+// These have the same values on every platform with unix sockets.
+const EIO: i32 = 5;
+const EINVAL: i32 = 22;
+
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Implemented by a userspace WireGuard device to answer requests received by
+/// a [`Server`].
+///
+/// Errors are reported to the client as `errno=-N`, the same way wireguard-go
+/// does. Errors without a raw OS error code are reported as `EIO`.
+pub trait Handler {
+    /// Answers a `get=1` request. The `ifindex` and `ifname` of the returned
+    /// device aren't part of the protocol and are ignored.
+    fn get(&mut self) -> io::Result<get::Device>;
+
+    /// Applies a `set=1` request.
+    fn set(&mut self, device: set::Device) -> io::Result<()>;
+
+    /// Called by [`Server::serve`] when a connection ends with an error, such
+    /// as a malformed request or a client that timed out. Errors are ignored
+    /// by default.
+    fn connection_error(&mut self, _err: ServeError) {}
+}
+
+/// The server side of the cross-platform protocol. Stock `wg` and [`Client`]
+/// can configure a userspace WireGuard implementation through it.
+///
+/// ```no_run
+/// use std::io;
+/// use wireguard_uapi::get;
+/// use wireguard_uapi::xplatform::{set, Handler, Server};
+///
+/// struct Device {
+///     config: get::Device,
+/// }
+///
+/// impl Handler for Device {
+///     fn get(&mut self) -> io::Result<get::Device> {
+///         Ok(self.config.clone())
+///     }
+///
+///     fn set(&mut self, device: set::Device) -> io::Result<()> {
+///         if let Some(listen_port) = device.listen_port {
+///             self.config.listen_port = listen_port;
+///         }
+///         Ok(())
+///     }
+/// }
+///
+/// let device = Device {
+///     config: get::Device {
+///         ifindex: 0,
+///         ifname: "wg0".to_string(),
+///         private_key: None,
+///         public_key: None,
+///         listen_port: 51820,
+///         fwmark: 0,
+///         peers: vec![],
+///     },
+/// };
+/// let mut server = Server::bind("/var/run/wireguard/wg0.sock", device)?;
+/// server.serve()?;
+/// # Ok::<(), io::Error>(())
+/// ```
+///
+/// [`Client`]: crate::xplatform::Client
+pub struct Server<H: Handler> {
+    listener: UnixListener,
+    timeout: Duration,
+    handler: H,
+}
+
+impl<H: Handler> Server<H> {
+    /// Creates the unix socket file at `path`. Ex: `/var/run/wireguard/wg0.sock`
+    pub fn bind(path: impl AsRef<Path>, handler: H) -> io::Result<Self> {
+        Ok(Self::from_listener(UnixListener::bind(path)?, handler))
+    }
+
+    pub fn from_listener(listener: UnixListener, handler: H) -> Self {
+        Self {
+            listener,
+            timeout: DEFAULT_TIMEOUT,
+            handler,
+        }
+    }
+
+    /// Sets the read and write timeout of each connection. A client that
+    /// stays idle for longer is disconnected.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn handler(&self) -> &H {
+        &self.handler
+    }
+
+    pub fn handler_mut(&mut self) -> &mut H {
+        &mut self.handler
+    }
+
+    /// Accepts a single connection and answers its requests until the client
+    /// disconnects.
+    pub fn accept(&mut self) -> Result<(), ServeError> {
+        let (stream, _) = self.listener.accept()?;
+        serve_connection(stream, self.timeout, &mut self.handler)
+    }
+
+    /// Answers connections one at a time until accepting a connection fails.
+    /// A misbehaving client only closes its own connection, and its error is
+    /// passed to [`Handler::connection_error`].
+    pub fn serve(&mut self) -> io::Result<()> {
+        for stream in self.listener.incoming() {
+            if let Err(err) = serve_connection(stream?, self.timeout, &mut self.handler) {
+                self.handler.connection_error(err);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Answers the requests on an already accepted connection until the client
+/// disconnects. A malformed request is answered with `EINVAL` and closes the
+/// connection.
+pub fn serve_connection<H: Handler>(
+    stream: UnixStream,
+    timeout: Duration,
+    handler: &mut H,
+) -> Result<(), ServeError> {
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+    let mut writer = stream.try_clone()?;
+    let mut lines = BufReader::new(stream).lines();
+
+    while let Some(line) = lines.next() {
+        let line = line?;
+
+        match line.as_str() {
+            "get=1" => {
+                let empty_line = lines.next().transpose()?.unwrap_or_default();
+                if !empty_line.is_empty() {
+                    write_errno(&mut writer, EINVAL)?;
+                    return Err(ServeError::InvalidGetRequest(empty_line));
+                }
+
+                match handler.get() {
+                    Ok(device) => writer.write_all(format_get_response(&device).as_bytes())?,
+                    Err(err) => write_errno(&mut writer, errno(&err))?,
+                }
+            }
+            "set=1" => {
+                let device = match parse_set_request(&mut lines) {
+                    Ok(device) => device,
+                    Err(err) => {
+                        write_errno(&mut writer, EINVAL)?;
+                        return Err(err.into());
+                    }
+                };
+
+                match handler.set(device) {
+                    Ok(()) => write_errno(&mut writer, 0)?,
+                    Err(err) => write_errno(&mut writer, errno(&err))?,
+                }
+            }
+            _ => {
+                write_errno(&mut writer, EINVAL)?;
+                return Err(ServeError::UnknownOperation(line));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn errno(err: &io::Error) -> i32 {
+    err.raw_os_error().filter(|&errno| errno > 0).unwrap_or(EIO)
+}
+
+fn write_errno(writer: &mut impl Write, errno: i32) -> io::Result<()> {
+    writer.write_all(format!("{}={}\n\n", GetKey::Errno, -errno).as_bytes())
+}
+
+fn format_get_response(device: &get::Device) -> Zeroizing<String> {
+    // The response contains the private key. Allocate enough up front that
+    // the buffer never grows and leaves a copy behind.
+    let capacity = 256
+        + device
+            .peers
+            .iter()
+            .map(|peer| 512 + 64 * peer.allowed_ips.len())
+            .sum::<usize>();
+    let mut response = Zeroizing::new(String::with_capacity(capacity));
+
+    // Writing to a String can't fail.
+    let _ = write_get_device(&mut response, device);
+    response
+}
+
+fn write_get_device(f: &mut String, device: &get::Device) -> std::fmt::Result {
+    if let Some(private_key) = &device.private_key {
+        writeln!(f, "{}={:x}", GetKey::PrivateKey, private_key)?;
+    }
+
+    writeln!(f, "{}={}", GetKey::ListenPort, device.listen_port)?;
+
+    if device.fwmark != 0 {
+        writeln!(f, "{}={}", GetKey::Fwmark, device.fwmark)?;
+    }
+
+    for peer in &device.peers {
+        writeln!(f, "{}={:x}", GetKey::PublicKey, peer.public_key)?;
+
+        if !peer.preshared_key.is_zero() {
+            writeln!(f, "{}={:x}", GetKey::PresharedKey, peer.preshared_key)?;
+        }
+
+        writeln!(f, "{}={}", GetKey::ProtocolVersion, peer.protocol_version)?;
+
+        if let Some(endpoint) = peer.endpoint {
+            writeln!(f, "{}={}", GetKey::Endpoint, endpoint)?;
+        }
+
+        let last_handshake_time = peer.last_handshake_time;
+        writeln!(
+            f,
+            "{}={}",
+            GetKey::LastHandshakeTimeSec,
+            last_handshake_time.as_secs()
+        )?;
+        writeln!(
+            f,
+            "{}={}",
+            GetKey::LastHandshakeTimeNsec,
+            last_handshake_time.subsec_nanos()
+        )?;
+        writeln!(f, "{}={}", GetKey::TxBytes, peer.tx_bytes)?;
+        writeln!(f, "{}={}", GetKey::RxBytes, peer.rx_bytes)?;
+        writeln!(
+            f,
+            "{}={}",
+            GetKey::PersistentKeepaliveInterval,
+            peer.persistent_keepalive_interval
+        )?;
+
+        for allowed_ip in &peer.allowed_ips {
+            writeln!(
+                f,
+                "{}={}/{}",
+                GetKey::AllowedIp,
+                allowed_ip.ipaddr,
+                allowed_ip.cidr_mask
+            )?;
+        }
+    }
+
+    writeln!(f, "{}=0", GetKey::Errno)?;
+    writeln!(f)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key::PresharedKey;
+    use crate::xplatform::error::SetDeviceError;
+    use crate::xplatform::Client;
+    use std::thread;
+    use std::time::Duration;
+
+    struct TestHandler {
+        device: get::Device,
+        set_requests: Vec<set::Device>,
+    }
+
+    impl Handler for TestHandler {
+        fn get(&mut self) -> io::Result<get::Device> {
+            Ok(self.device.clone())
+        }
+
+        fn set(&mut self, device: set::Device) -> io::Result<()> {
+            if device.listen_port == Some(0) {
+                return Err(io::Error::from_raw_os_error(EINVAL));
+            }
+            self.set_requests.push(device);
+            Ok(())
+        }
+    }
+
+    fn example_device() -> anyhow::Result<get::Device> {
+        Ok(get::Device {
+            ifindex: 0,
+            ifname: "".to_string(),
+            private_key: Some("6EtabScXwQA6E7QxVwNT26ypFGzxUMX4V1aA/rpSAno=".parse()?),
+            public_key: None,
+            listen_port: 12912,
+            fwmark: 0x51820,
+            peers: vec![
+                get::Peer {
+                    public_key: "uFmW/sycfx/G0lcqdu2hHVm80gvo5UOxXOS9hajnWjM=".parse()?,
+                    preshared_key: "GIUVCT6VL18i6GXO8wEucvi18LWYrAMJ1drM47cPz1I=".parse()?,
+                    endpoint: Some("[abcd:23::33]:51820".parse()?),
+                    persistent_keepalive_interval: 25,
+                    last_handshake_time: Duration::new(1_590_459_201, 283_546_000),
+                    rx_bytes: 2224,
+                    tx_bytes: 38333,
+                    allowed_ips: vec!["192.168.4.4/32".parse()?, "fd00::/64".parse()?],
+                    protocol_version: 1,
+                },
+                get::Peer {
+                    public_key: "WEAuaVuhdyscyTCXVfBDJR6nf9zxD75jmJzrfhkyE3Y=".parse()?,
+                    preshared_key: PresharedKey::zero(),
+                    endpoint: None,
+                    persistent_keepalive_interval: 0,
+                    last_handshake_time: Duration::new(0, 0),
+                    rx_bytes: 0,
+                    tx_bytes: 0,
+                    allowed_ips: vec![],
+                    protocol_version: 1,
+                },
+            ],
+        })
+    }
+
+    #[test]
+    fn client_round_trips_through_server() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("wgtest0.sock");
+        let mut server = Server::bind(
+            &path,
+            TestHandler {
+                device: example_device()?,
+                set_requests: vec![],
+            },
+        )?;
+
+        let server_thread = thread::spawn(move || -> Result<TestHandler, ServeError> {
+            for _ in 0..3 {
+                server.accept()?;
+            }
+            Ok(server.handler)
+        });
+
+        let client = Client::create(&path);
+        assert_eq!(client.get()?, example_device()?);
+
+        let set_request = set::Device {
+            listen_port: Some(51820),
+            replace_peers: Some(true),
+            peers: vec![set::Peer {
+                remove: Some(true),
+                ..set::Peer::from_public_key(
+                    "WEAuaVuhdyscyTCXVfBDJR6nf9zxD75jmJzrfhkyE3Y=".parse()?,
+                )
+            }],
+            ..Default::default()
+        };
+        client.set(set_request)?;
+
+        let error = client.set(set::Device {
+            listen_port: Some(0),
+            ..Default::default()
+        });
+        assert!(matches!(error, Err(SetDeviceError::ServerError(errno)) if errno == "-22"));
+
+        let handler = server_thread.join().unwrap()?;
+        assert_eq!(handler.set_requests.len(), 1);
+        assert_eq!(handler.set_requests[0].listen_port, Some(51820));
+        assert_eq!(handler.set_requests[0].peers[0].remove, Some(true));
+        Ok(())
+    }
+
+    #[test]
+    fn api_rejects_other_interfaces() -> anyhow::Result<()> {
+        use crate::api::{self, ApiError, WireGuardApi};
+
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("wgtest0.sock");
+        let mut server = Server::bind(
+            &path,
+            TestHandler {
+                device: example_device()?,
+                set_requests: vec![],
+            },
+        )?;
+        let server_thread = thread::spawn(move || server.accept());
+
+        let mut client = Client::create(&path);
+        assert_eq!(client.ifname(), Some("wgtest0"));
+        assert!(matches!(
+            client.get_device("wgtest1"),
+            Err(ApiError::NoSuchDevice(ifname)) if ifname == "wgtest1"
+        ));
+        assert!(matches!(
+            client.set_device("wgtest1", &api::set::Device::default()),
+            Err(ApiError::NoSuchDevice(_))
+        ));
+
+        assert_eq!(client.get_device("wgtest0")?.ifname, "wgtest0");
+        server_thread.join().unwrap()?;
+        Ok(())
+    }
+
+    #[test]
+    fn get_response_uses_fwmark_key() -> anyhow::Result<()> {
+        // Upstream implementations write and expect `fwmark`, not `fw_mark`.
+        let response = format_get_response(&example_device()?);
+        assert!(response.contains("\nfwmark=333856\n"));
+
+        let (mut client, server) = UnixStream::pair()?;
+        let mut handler = TestHandler {
+            device: example_device()?,
+            set_requests: vec![],
+        };
+        client.write_all(b"get=1\n\n")?;
+        client.shutdown(std::net::Shutdown::Write)?;
+        serve_connection(server, DEFAULT_TIMEOUT, &mut handler)?;
+
+        let device = crate::xplatform::parser::parse(BufReader::new(client).lines())?;
+        assert_eq!(device.fwmark, 0x51820);
+        Ok(())
+    }
+
+    #[test]
+    fn idle_client_does_not_block_others() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("wgtest0.sock");
+        let mut server = Server::bind(
+            &path,
+            TestHandler {
+                device: example_device()?,
+                set_requests: vec![],
+            },
+        )?
+        .timeout(Duration::from_millis(100));
+
+        let idle = UnixStream::connect(&path)?;
+        let client_path = path.clone();
+        let client = thread::spawn(move || Client::create(client_path).get());
+
+        let error = server.accept().unwrap_err();
+        assert!(matches!(
+            error,
+            ServeError::Io(err)
+                if matches!(err.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut)
+        ));
+        server.accept()?;
+        assert_eq!(client.join().unwrap()?, example_device()?);
+        drop(idle);
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_unknown_operation() -> anyhow::Result<()> {
+        let (mut client, server) = UnixStream::pair()?;
+        let mut handler = TestHandler {
+            device: example_device()?,
+            set_requests: vec![],
+        };
+
+        client.write_all(b"get=2\n\n")?;
+        let result = serve_connection(server, DEFAULT_TIMEOUT, &mut handler);
+        assert!(matches!(result, Err(ServeError::UnknownOperation(op)) if op == "get=2"));
+
+        let mut response = String::new();
+        BufReader::new(client).read_line(&mut response)?;
+        assert_eq!(response, "errno=-22\n");
+        Ok(())
+    }
+}
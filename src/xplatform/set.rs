@@ -1,17 +1,24 @@
+use crate::key::{PresharedKey, PrivateKey, PublicKey};
+use crate::xplatform::error::ParseSetRequestError;
+use crate::xplatform::parser::parse_set_request;
 use crate::xplatform::protocol::SetKey;
 use std::fmt::Display;
 use std::net::IpAddr;
 use std::net::SocketAddr;
+use std::str::FromStr;
 
 /// Documentation of each field comes from:
 /// https://www.wireguard.com/xplatform/#configuration-protocol
 #[derive(Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct Device {
     /// The value for this key should be a lowercase hex-encoded private key of
     /// the interface. The value may be an all zero string in the case of a set
     /// operation, in which case it indicates that the private key should be
     /// removed.
-    pub private_key: Option<[u8; 32]>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing))]
+    pub private_key: Option<PrivateKey>,
 
     /// The value for this is a decimal-string integer corresponding to the
     /// listening port of the interface.
@@ -32,9 +39,8 @@ pub struct Device {
 
 impl Display for Device {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        if let Some(private_key) = self.private_key {
-            let private_key = hex::encode(private_key);
-            writeln!(f, "{}={}", SetKey::PrivateKey, private_key)?;
+        if let Some(private_key) = &self.private_key {
+            writeln!(f, "{}={:x}", SetKey::PrivateKey, private_key)?;
         }
 
         if let Some(listen_port) = self.listen_port {
@@ -57,14 +63,36 @@ impl Display for Device {
     }
 }
 
+/// Parses the format written by `Display`. The empty line that ends a request
+/// on the socket is optional.
+impl FromStr for Device {
+    type Err = ParseSetRequestError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = s
+            .lines()
+            .map(|line| Ok(line.to_string()))
+            .chain(std::iter::once(Ok(String::new())));
+        let device = parse_set_request(&mut lines)?;
+
+        // Only the empty line appended above may be left over.
+        if lines.any(|line| !matches!(line.as_deref(), Ok(""))) {
+            return Err(ParseSetRequestError::DataAfterEndOfRequest);
+        }
+
+        Ok(device)
+    }
+}
+
 /// Documentation of each field comes from:
 /// https://www.wireguard.com/xplatform/#configuration-protocol
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Peer {
     /// The value for this key should be a lowercase hex-encoded public key of a
     /// new peer entry, which this command adds. The same public key value may
     /// not repeat during a single message.
-    pub public_key: [u8; 32],
+    pub public_key: PublicKey,
 
     /// This key/value combo is only valid in a set operation, in which case it
     /// indicates that the previously added peer entry should be removed from the
@@ -80,7 +108,7 @@ pub struct Peer {
     /// the previously added peer entry. The value may be an all zero string in
     /// the case of a set operation, in which case it indicates that the
     /// preshared-key should be removed.
-    pub preshared_key: Option<[u8; 32]>,
+    pub preshared_key: Option<PresharedKey>,
 
     /// The value for this key is either IP:port for IPv4 or \[IP\]:port for
     /// IPv6, indicating the endpoint of the previously added peer entry.
@@ -101,11 +129,12 @@ pub struct Peer {
     /// for the previously added peer entry. If an identical value already exists
     /// as part of a prior peer, the allowed IP entry will be removed from that
     /// peer and added to this peer.
+    #[cfg_attr(feature = "serde", serde(default))]
     pub allowed_ips: Vec<AllowedIp>,
 }
 
 impl Peer {
-    pub fn from_public_key(public_key: [u8; 32]) -> Self {
+    pub fn from_public_key(public_key: PublicKey) -> Self {
         Self {
             public_key,
             remove: None,
@@ -121,7 +150,7 @@ impl Peer {
 
 impl Display for Peer {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "{}={}", SetKey::PublicKey, hex::encode(self.public_key))?;
+        writeln!(f, "{}={:x}", SetKey::PublicKey, self.public_key)?;
 
         if let Some(remove) = self.remove {
             writeln!(f, "{}={}", SetKey::Remove, remove)?;
@@ -131,9 +160,8 @@ impl Display for Peer {
             writeln!(f, "{}={}", SetKey::UpdateOnly, update_only)?;
         }
 
-        if let Some(preshared_key) = self.preshared_key {
-            let preshared_key = hex::encode(preshared_key);
-            writeln!(f, "{}={}", SetKey::PresharedKey, preshared_key)?;
+        if let Some(preshared_key) = &self.preshared_key {
+            writeln!(f, "{}={:x}", SetKey::PresharedKey, preshared_key)?;
         }
 
         if let Some(endpoint) = self.endpoint {
@@ -206,26 +234,26 @@ mod tests {
             remove=true\n";
 
         let set_request = Device {
-            private_key: Some([
+            private_key: Some(PrivateKey::from([
                 0xe8, 0x4b, 0x5a, 0x6d, 0x27, 0x17, 0xc1, 0x00, 0x3a, 0x13, 0xb4, 0x31, 0x57, 0x03,
                 0x53, 0xdb, 0xac, 0xa9, 0x14, 0x6c, 0xf1, 0x50, 0xc5, 0xf8, 0x57, 0x56, 0x80, 0xfe,
                 0xba, 0x52, 0x02, 0x7a,
-            ]),
+            ])),
             listen_port: Some(12912),
             fwmark: Some(0),
             replace_peers: Some(true),
             peers: vec![
                 {
-                    let mut peer = Peer::from_public_key([
+                    let mut peer = Peer::from_public_key(PublicKey::from([
                         0xb8, 0x59, 0x96, 0xfe, 0xcc, 0x9c, 0x7f, 0x1f, 0xc6, 0xd2, 0x57, 0x2a,
                         0x76, 0xed, 0xa1, 0x1d, 0x59, 0xbc, 0xd2, 0x0b, 0xe8, 0xe5, 0x43, 0xb1,
                         0x5c, 0xe4, 0xbd, 0x85, 0xa8, 0xe7, 0x5a, 0x33,
-                    ]);
-                    peer.preshared_key = Some([
+                    ]));
+                    peer.preshared_key = Some(PresharedKey::from([
                         0x18, 0x85, 0x15, 0x09, 0x3e, 0x95, 0x2f, 0x5f, 0x22, 0xe8, 0x65, 0xce,
                         0xf3, 0x01, 0x2e, 0x72, 0xf8, 0xb5, 0xf0, 0xb5, 0x98, 0xac, 0x03, 0x09,
                         0xd5, 0xda, 0xcc, 0xe3, 0xb7, 0x0f, 0xcf, 0x52,
-                    ]);
+                    ]));
                     peer.replace_allowed_ips = Some(true);
                     peer.allowed_ips.push(AllowedIp {
                         ipaddr: "192.168.4.4".parse().unwrap(),
@@ -235,11 +263,11 @@ mod tests {
                     peer
                 },
                 {
-                    let mut peer = Peer::from_public_key([
+                    let mut peer = Peer::from_public_key(PublicKey::from([
                         0x58, 0x40, 0x2e, 0x69, 0x5b, 0xa1, 0x77, 0x2b, 0x1c, 0xc9, 0x30, 0x97,
                         0x55, 0xf0, 0x43, 0x25, 0x1e, 0xa7, 0x7f, 0xdc, 0xf1, 0x0f, 0xbe, 0x63,
                         0x98, 0x9c, 0xeb, 0x7e, 0x19, 0x32, 0x13, 0x76,
-                    ]);
+                    ]));
                     peer.replace_allowed_ips = Some(true);
                     peer.allowed_ips.push(AllowedIp {
                         ipaddr: "192.168.4.6".parse().unwrap(),
@@ -250,11 +278,11 @@ mod tests {
                     peer
                 },
                 {
-                    let mut peer = Peer::from_public_key([
+                    let mut peer = Peer::from_public_key(PublicKey::from([
                         0x66, 0x2e, 0x14, 0xfd, 0x59, 0x45, 0x56, 0xf5, 0x22, 0x60, 0x47, 0x03,
                         0x34, 0x03, 0x51, 0x25, 0x89, 0x03, 0xb6, 0x4f, 0x35, 0x55, 0x37, 0x63,
                         0xf1, 0x94, 0x26, 0xab, 0x2a, 0x51, 0x5c, 0x58,
-                    ]);
+                    ]));
                     peer.endpoint = Some("5.152.198.39:51820".parse().unwrap());
                     peer.replace_allowed_ips = Some(true);
                     peer.allowed_ips.push(AllowedIp {
@@ -268,11 +296,11 @@ mod tests {
                     peer
                 },
                 {
-                    let mut peer = Peer::from_public_key([
+                    let mut peer = Peer::from_public_key(PublicKey::from([
                         0xe8, 0x18, 0xb5, 0x8d, 0xb5, 0x27, 0x40, 0x87, 0xfc, 0xc1, 0xbe, 0x5d,
                         0xc7, 0x28, 0xcf, 0x53, 0xd3, 0xb5, 0x72, 0x6b, 0x4c, 0xef, 0x6b, 0x9b,
                         0xab, 0x8f, 0x8f, 0x8c, 0x24, 0x52, 0xc2, 0x5c,
-                    ]);
+                    ]));
                     peer.remove = Some(true);
                     peer
                 },
@@ -281,6 +309,7 @@ mod tests {
         let actual = format!("{}", set_request);
 
         assert_eq!(expected, actual);
+        assert_eq!(expected.parse::<Device>().unwrap(), set_request);
     }
 
     #[test]
@@ -297,17 +326,17 @@ mod tests {
         .join("\n");
 
         let set_request = Device {
-            private_key: Some([
+            private_key: Some(PrivateKey::from([
                 0xe8, 0x4b, 0x5a, 0x6d, 0x27, 0x17, 0xc1, 0x00, 0x3a, 0x13, 0xb4, 0x31, 0x57, 0x03,
                 0x53, 0xdb, 0xac, 0xa9, 0x14, 0x6c, 0xf1, 0x50, 0xc5, 0xf8, 0x57, 0x56, 0x80, 0xfe,
                 0xba, 0x52, 0x02, 0x7a,
-            ]),
+            ])),
             peers: vec![{
-                let mut peer = Peer::from_public_key([
+                let mut peer = Peer::from_public_key(PublicKey::from([
                     0xb8, 0x59, 0x96, 0xfe, 0xcc, 0x9c, 0x7f, 0x1f, 0xc6, 0xd2, 0x57, 0x2a, 0x76,
                     0xed, 0xa1, 0x1d, 0x59, 0xbc, 0xd2, 0x0b, 0xe8, 0xe5, 0x43, 0xb1, 0x5c, 0xe4,
                     0xbd, 0x85, 0xa8, 0xe7, 0x5a, 0x33,
-                ]);
+                ]));
                 peer.update_only = Some(true);
                 peer.replace_allowed_ips = Some(true);
                 peer.allowed_ips.push(AllowedIp {
@@ -322,6 +351,42 @@ mod tests {
         let actual = format!("{}", set_request);
 
         assert_eq!(expected, actual);
+        assert_eq!(expected.parse::<Device>().unwrap(), set_request);
+    }
+
+    #[test]
+    fn parse_enforces_protocol_order() {
+        let public_key =
+            "public_key=b85996fecc9c7f1fc6d2572a76eda11d59bcd20be8e543b15ce4bd85a8e75a33";
+
+        assert!(matches!(
+            format!("{}\nlisten_port=12912", public_key).parse::<Device>(),
+            Err(ParseSetRequestError::InterfaceLevelKeyAfterPeerLevelKey(
+                SetKey::ListenPort
+            ))
+        ));
+        assert!(matches!(
+            format!("{}\nremove=true\n{}", public_key, public_key).parse::<Device>(),
+            Err(ParseSetRequestError::DuplicatePublicKey(_))
+        ));
+        for key in &["remove", "update_only", "replace_allowed_ips"] {
+            assert!(matches!(
+                format!("{}=true\n{}", key, public_key).parse::<Device>(),
+                Err(ParseSetRequestError::PeerLevelKeyBeforePublicKey(_))
+            ));
+        }
+        assert!(matches!(
+            format!("{}\n\nlisten_port=12912", public_key).parse::<Device>(),
+            Err(ParseSetRequestError::DataAfterEndOfRequest)
+        ));
+        assert_eq!(
+            format!("{}\n\n", public_key)
+                .parse::<Device>()
+                .unwrap()
+                .peers
+                .len(),
+            1
+        );
     }
 
     // Simple comparisons to make default, partial_eq, and debug derive code covered.
@@ -330,20 +395,20 @@ mod tests {
         let device1 = Device::default();
         let device2 = Device::default();
         assert_eq!(device1, device2);
-        format!("{:?}", device1);
+        let _ = format!("{:?}", device1);
 
-        let peer1 = Peer::from_public_key([
+        let peer1 = Peer::from_public_key(PublicKey::from([
             0xb8, 0x59, 0x96, 0xfe, 0xcc, 0x9c, 0x7f, 0x1f, 0xc6, 0xd2, 0x57, 0x2a, 0x76, 0xed,
             0xa1, 0x1d, 0x59, 0xbc, 0xd2, 0x0b, 0xe8, 0xe5, 0x43, 0xb1, 0x5c, 0xe4, 0xbd, 0x85,
             0xa8, 0xe7, 0x5a, 0x33,
-        ]);
-        let peer2 = Peer::from_public_key([
+        ]));
+        let peer2 = Peer::from_public_key(PublicKey::from([
             0xb8, 0x59, 0x96, 0xfe, 0xcc, 0x9c, 0x7f, 0x1f, 0xc6, 0xd2, 0x57, 0x2a, 0x76, 0xed,
             0xa1, 0x1d, 0x59, 0xbc, 0xd2, 0x0b, 0xe8, 0xe5, 0x43, 0xb1, 0x5c, 0xe4, 0xbd, 0x85,
             0xa8, 0xe7, 0x5a, 0x33,
-        ]);
+        ]));
         assert_eq!(peer1, peer2);
-        format!("{:?}", peer1);
+        let _ = format!("{:?}", peer1);
 
         let allowed_ip1 = AllowedIp {
             ipaddr: "::1".parse().unwrap(),
@@ -354,6 +419,6 @@ mod tests {
             cidr_mask: 64,
         };
         assert_eq!(allowed_ip1, allowed_ip2);
-        format!("{:?}", allowed_ip1);
+        let _ = format!("{:?}", allowed_ip1);
     }
 }
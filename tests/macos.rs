@@ -8,6 +8,7 @@ mod tests {
     use std::path::PathBuf;
     use std::process::Command;
     use tempfile::NamedTempFile;
+    use wireguard_uapi::PrivateKey;
 
     const MACOS_WG_SOCK_DIR: &str = "/var/run/wireguard";
 
@@ -46,15 +47,6 @@ mod tests {
         Ok(sock_path)
     }
 
-    // https://github.com/jedisct1/libsodium/blob/3de0b3cdad90bbe0c44393fb0a264e9af6d76724/src/libsodium/crypto_scalarmult/curve25519/ref10/x25519_ref10.c#L91-L93
-    fn curve25519_clamp(key: [u8; 32]) -> [u8; 32] {
-        let mut key = key;
-        key[0] &= 248;
-        key[31] &= 127;
-        key[31] |= 64;
-        key
-    }
-
     #[test]
     fn empty() -> anyhow::Result<()> {
         let socket = create_random_interface_for_testing()?;
@@ -75,9 +67,9 @@ mod tests {
         let interface = client.get()?;
         assert_eq!(interface.private_key, None);
 
-        let private_key = curve25519_clamp(rand::random());
+        let private_key = PrivateKey::generate();
         client.set(set::Device {
-            private_key: Some(private_key),
+            private_key: Some(private_key.clone()),
             ..Default::default()
         })?;
         let interface = client.get()?;
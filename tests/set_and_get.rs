@@ -2,7 +2,7 @@
 use {
     std::net::{IpAddr, Ipv6Addr},
     std::time::Duration,
-    wireguard_uapi::{get, set, DeviceInterface, RouteSocket, WgSocket},
+    wireguard_uapi::{get, set, DeviceInterface, PresharedKey, RouteSocket, WgSocket},
 };
 
 #[cfg(target_os = "linux")]
@@ -11,14 +11,7 @@ fn get_random_ifname() -> String {
 }
 
 #[cfg(target_os = "linux")]
-fn parse_device_key(buf: &[u8]) -> [u8; 32] {
-    let mut key = [0u8; 32];
-    key.copy_from_slice(buf);
-    key
-}
-
-#[cfg(target_os = "linux")]
-fn create_set_allowed_ips(allowed_ips: &[get::AllowedIp]) -> Vec<set::AllowedIp> {
+fn create_set_allowed_ips(allowed_ips: &[get::AllowedIp]) -> Vec<set::AllowedIp<'_>> {
     allowed_ips
         .iter()
         .map(|allowed_ip| set::AllowedIp {
@@ -34,20 +27,14 @@ fn simple() -> anyhow::Result<()> {
     let mut test_device = get::Device {
         ifindex: 0,
         ifname: get_random_ifname(),
-        private_key: Some(parse_device_key(&base64::decode(
-            "EHhtoXVXpnXz31cx8nrAxQfvaRqe1vf343GVSyEtqUU=",
-        )?)),
-        public_key: Some(parse_device_key(&base64::decode(
-            "MhBzmIBrzw8b8iF2FH4ejh/7Vumn6Q/KoR0H5+o7mlY=",
-        )?)),
+        private_key: Some("EHhtoXVXpnXz31cx8nrAxQfvaRqe1vf343GVSyEtqUU=".parse()?),
+        public_key: Some("MhBzmIBrzw8b8iF2FH4ejh/7Vumn6Q/KoR0H5+o7mlY=".parse()?),
         listen_port: 1234,
         fwmark: 0,
         peers: vec![
             get::Peer {
-                public_key: parse_device_key(&base64::decode(
-                    "DNeiCuVE2CuDy9QH3K3/egRK1rdn/oThlPtWNc4FfSw=",
-                )?),
-                preshared_key: [0u8; 32],
+                public_key: "DNeiCuVE2CuDy9QH3K3/egRK1rdn/oThlPtWNc4FfSw=".parse()?,
+                preshared_key: PresharedKey::zero(),
                 endpoint: Some("[::1]:8080".parse()?),
                 persistent_keepalive_interval: 0,
                 last_handshake_time: Duration::new(0, 0),
@@ -68,12 +55,8 @@ fn simple() -> anyhow::Result<()> {
                 protocol_version: 1,
             },
             get::Peer {
-                public_key: parse_device_key(&base64::decode(
-                    "6KUaqULa+M6JI+b6DP3p0ZZZWyClN7ioMpYJp0kNFxQ=",
-                )?),
-                preshared_key: parse_device_key(&base64::decode(
-                    "cMeE5GWUzUbvxbnBKco2MwAnW78nsk8vr04+KupVFkQ=",
-                )?),
+                public_key: "6KUaqULa+M6JI+b6DP3p0ZZZWyClN7ioMpYJp0kNFxQ=".parse()?,
+                preshared_key: "cMeE5GWUzUbvxbnBKco2MwAnW78nsk8vr04+KupVFkQ=".parse()?,
                 endpoint: Some("127.0.0.1:12345".parse()?),
                 persistent_keepalive_interval: 60,
                 last_handshake_time: Duration::new(0, 0),
@@ -160,19 +143,13 @@ fn large_peer() -> anyhow::Result<()> {
     let mut test_device = get::Device {
         ifindex: 6,
         ifname: get_random_ifname(),
-        private_key: Some(parse_device_key(&base64::decode(
-            "yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk=",
-        )?)),
-        public_key: Some(parse_device_key(&base64::decode(
-            "HIgo9xNzJMWLKASShiTqIybxZ0U3wGLiUeJ1PKf8ykw=",
-        )?)),
+        private_key: Some("yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk=".parse()?),
+        public_key: Some("HIgo9xNzJMWLKASShiTqIybxZ0U3wGLiUeJ1PKf8ykw=".parse()?),
         listen_port: 51820,
         fwmark: 0,
         peers: vec![get::Peer {
-            public_key: parse_device_key(&base64::decode(
-                "xTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg=",
-            )?),
-            preshared_key: [0u8; 32],
+            public_key: "xTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg=".parse()?,
+            preshared_key: PresharedKey::zero(),
             endpoint: Some("192.95.5.67:1234".parse()?),
             persistent_keepalive_interval: 0,
             last_handshake_time: Duration::new(0, 0),
@@ -190,7 +167,7 @@ fn large_peer() -> anyhow::Result<()> {
         }],
     };
 
-    let response_device = {
+    let (response_device, entries) = {
         let mut wg = WgSocket::connect()?;
         let mut route = RouteSocket::connect()?;
 
@@ -212,9 +189,12 @@ fn large_peer() -> anyhow::Result<()> {
 
         wg.set_device(set_device_args)?;
         let response_device = wg.get_device(DeviceInterface::from_name(&test_device.ifname))?;
+        let entries = wg
+            .get_device_entries(DeviceInterface::from_name(&test_device.ifname))?
+            .collect::<Result<Vec<_>, _>>()?;
         route.del_device(&test_device.ifname)?;
 
-        response_device
+        (response_device, entries)
     };
 
     // The ifindex can't be determined before response_device is set. So we'll just copy over the
@@ -223,5 +203,161 @@ fn large_peer() -> anyhow::Result<()> {
 
     assert_eq!(test_device, response_device);
 
+    // Streaming the device merges the peer's allowed IPs back together the same way.
+    let mut device_without_peers = test_device.clone();
+    let peers = std::mem::take(&mut device_without_peers.peers);
+    let mut expected_entries = vec![get::DeviceEntry::Device(device_without_peers)];
+    expected_entries.extend(peers.into_iter().map(get::DeviceEntry::Peer));
+    assert_eq!(entries, expected_entries);
+
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn bring_up_interface() -> anyhow::Result<()> {
+    use wireguard_uapi::{address::Address, link::OperState, route::Route};
+
+    let ifname = get_random_ifname();
+    let address: Address = "10.189.0.1/24".parse()?;
+    let allowed_ips: Vec<get::AllowedIp> = vec!["10.189.1.0/24".parse()?, "fd89::/64".parse()?];
+
+    let (link, devices, addresses, routes) = {
+        let mut wg = WgSocket::connect()?;
+        let mut route = RouteSocket::connect()?;
+
+        route.add_device(&ifname)?;
+        let public_key = "xTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg=".parse()?;
+        let set_device_args = {
+            let peer = set::Peer::from_public_key(&public_key)
+                .allowed_ips(create_set_allowed_ips(&allowed_ips));
+            set::Device::from_ifname(&ifname).peers(vec![peer])
+        };
+        wg.set_device(set_device_args)?;
+        let device = wg.get_device(DeviceInterface::from_name(&ifname))?;
+
+        route.set_mtu(DeviceInterface::from_name(&ifname), 1280)?;
+        route.set_link_up(DeviceInterface::from_name(&ifname))?;
+        route.add_address(DeviceInterface::from_name(&ifname), &address)?;
+        route.add_allowed_ip_routes(
+            &device,
+            libc::RT_TABLE_MAIN as u32,
+            None,
+            libc::RTPROT_BOOT,
+        )?;
+
+        let link = route.get_link(DeviceInterface::from_name(&ifname))?;
+        let devices = route.list_devices()?;
+        let addresses = route.list_addresses(DeviceInterface::from_name(&ifname))?;
+        let routes = route.list_routes(device.ifindex)?;
+        route.del_device(&ifname)?;
+        (link, devices, addresses, routes)
+    };
+
+    assert_eq!(link.ifname, ifname);
+    assert!(link.is_up());
+    assert_eq!(link.mtu, 1280);
+    assert_ne!(link.operstate, OperState::Down);
+    assert!(link.stats.is_some());
+    assert!(devices
+        .iter()
+        .any(|device| device.ifname == ifname && device.ifindex == link.ifindex));
+    assert_eq!(addresses, vec![address]);
+    for allowed_ip in &allowed_ips {
+        let expected = Route::from_allowed_ip(link.ifindex, allowed_ip);
+        assert!(routes
+            .iter()
+            .any(|route| route.destination == expected.destination
+                && route.prefix_len == expected.prefix_len));
+    }
+
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn link_events() -> anyhow::Result<()> {
+    use wireguard_uapi::{link::LinkEvent, LinkEventSocket};
+
+    let ifname = get_random_ifname();
+    let mut events = LinkEventSocket::connect()?;
+    let mut route = RouteSocket::connect()?;
+
+    route.add_device(&ifname)?;
+    let added = match events.recv_event()? {
+        LinkEvent::Added(link) => link,
+        event => panic!("Expected the link to be added, got {:?}", event),
+    };
+
+    route.del_device(&ifname)?;
+    let removed = loop {
+        if let LinkEvent::Removed(link) = events.recv_event()? {
+            break link;
+        }
+    };
+
+    assert_eq!(added.ifname, ifname);
+    assert_eq!(removed.ifindex, added.ifindex);
+
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn add_device_in_netns() -> anyhow::Result<()> {
+    use wireguard_uapi::netns::NetNs;
+
+    // A new namespace lives as long as a file refers to it.
+    let netns = std::thread::spawn(|| -> std::io::Result<NetNs> {
+        if unsafe { libc::unshare(libc::CLONE_NEWNET) } == -1 {
+            return Err(std::io::Error::last_os_error());
+        }
+        NetNs::current()
+    })
+    .join()
+    .unwrap()?;
+
+    let ifname = get_random_ifname();
+    let listen_port = rand::random::<u16>();
+
+    RouteSocket::connect()?.add_device_in_netns(&ifname, &netns)?;
+    let mut wg = WgSocket::connect_netns(&netns)?;
+    wg.set_device(set::Device::from_ifname(&ifname).listen_port(listen_port))?;
+    let device = wg.get_device(DeviceInterface::from_name(&ifname))?;
+
+    let outside = RouteSocket::connect()?.list_device_names()?;
+    let inside = RouteSocket::connect_netns(&netns)?.list_device_names()?;
+
+    assert_eq!(device.listen_port, listen_port);
+    assert!(!outside.contains(&ifname));
+    assert_eq!(inside, vec![ifname]);
+
+    Ok(())
+}
+
+#[cfg(all(target_os = "linux", feature = "tokio"))]
+#[tokio::test]
+async fn async_sockets() -> anyhow::Result<()> {
+    use wireguard_uapi::{AsyncRouteSocket, AsyncWgSocket};
+
+    let ifname = get_random_ifname();
+    let listen_port = rand::random::<u16>();
+
+    let (device_names, response_device) = {
+        let mut wg = AsyncWgSocket::connect().await?;
+        let mut route = AsyncRouteSocket::connect().await?;
+
+        route.add_device(&ifname).await?;
+        let device_names = route.list_device_names().await?;
+        let set_device_args = set::Device::from_ifname(&ifname).listen_port(listen_port);
+        wg.set_device(set_device_args).await?;
+        let response_device = wg.get_device(DeviceInterface::from_name(&ifname)).await?;
+        route.del_device(&ifname).await?;
+        (device_names, response_device)
+    };
+
+    assert!(device_names.contains(&ifname));
+    assert_eq!(listen_port, response_device.listen_port);
+
     Ok(())
 }